@@ -1,3 +1,10 @@
+pub const COMP_KEYS: [&str; 28] = [
+    "0", "1", "-1", "D", "A", "M", "!D", "!A", "!M", "-D", "-A", "-M", "D+1", "A+1", "M+1", "D-1",
+    "A-1", "M-1", "D+A", "D+M", "D-A", "D-M", "A-D", "M-D", "D&A", "D&M", "D|A", "D|M",
+];
+pub const DEST_KEYS: [&str; 7] = ["M", "D", "MD", "A", "AM", "AD", "AMD"];
+pub const JUMP_KEYS: [&str; 7] = ["JGT", "JEQ", "JGE", "JLT", "JNE", "JLE", "JMP"];
+
 pub fn comp_map(key: &str) -> &str {
     match key {
         "0" => "0101010",
@@ -0,0 +1,55 @@
+#![allow(dead_code)]
+use crate::code::{comp_map, dest_map, jump_map, COMP_KEYS, DEST_KEYS, JUMP_KEYS};
+
+// 0000000000000111 -> @7, 1110110000010000 -> D=A
+// C instructions whose comp isn't in the table are left in binary.
+pub fn disassemble(instruction: u16) -> String {
+    if instruction & 0x8000 == 0 {
+        return format!("@{}", instruction);
+    }
+    let comp = format!("{:07b}", (instruction >> 6) & 0b1111111);
+    let dest = format!("{:03b}", (instruction >> 3) & 0b111);
+    let jump = format!("{:03b}", instruction & 0b111);
+
+    let comp = match COMP_KEYS.iter().find(|key| comp_map(key) == comp) {
+        Some(comp) => comp,
+        None => return format!("{:016b}", instruction),
+    };
+    let dest = DEST_KEYS.iter().find(|key| dest_map(Some(key)) == dest);
+    let jump = JUMP_KEYS.iter().find(|key| jump_map(Some(key)) == jump);
+    match (dest, jump) {
+        (Some(dest), Some(jump)) => format!("{}={};{}", dest, comp, jump),
+        (Some(dest), None) => format!("{}={}", dest, comp),
+        (None, Some(jump)) => format!("{};{}", comp, jump),
+        (None, None) => comp.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    #[test]
+    fn for_disassemble() {
+        assert_eq!(disassemble(0b0000000000000111), "@7");
+        assert_eq!(disassemble(0b1110110000010000), "D=A");
+        assert_eq!(disassemble(0b1110101010000111), "0;JMP");
+        assert_eq!(disassemble(0b1111110111101000), "AM=M+1");
+        assert_eq!(disassemble(0b1110001100000001), "D;JGT");
+        assert_eq!(disassemble(0b1110111111011010), "MD=1;JEQ");
+        assert_eq!(disassemble(0b1110011011000000), "1110011011000000");
+    }
+
+    #[test]
+    fn for_disassemble_add() {
+        let mut parser = Parser::new();
+        let result = parser.run("src/programs/Add.asm");
+        let asm: Vec<String> = result
+            .to_string()
+            .lines()
+            .map(|line| disassemble(u16::from_str_radix(line, 2).unwrap()))
+            .collect();
+        assert_eq!(asm, vec!["@2", "D=A", "@3", "D=D+A", "@0", "M=D"]);
+    }
+}
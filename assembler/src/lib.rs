@@ -1,4 +1,5 @@
 mod code;
+pub mod disassembler;
 pub mod parser;
 mod symbol_table;
 mod util;
@@ -99,6 +99,21 @@ impl fmt::Display for ParseResult {
     }
 }
 
+impl ParseResult {
+    // (label, ROM address) in the order of the program
+    pub fn labels(&self) -> Vec<(String, u16)> {
+        let mut labels = Vec::new();
+        let mut address = 0;
+        for token in &self.tokens {
+            match token.ctype {
+                LCommand => labels.push((token.symbol.clone().unwrap(), address)),
+                _ => address += 1,
+            }
+        }
+        labels
+    }
+}
+
 #[derive(Debug)]
 pub struct Parser {
     lines: Vec<String>,
@@ -292,6 +307,20 @@ mod tests {
         assert_eq!(result.to_string(), expect);
     }
 
+    #[test]
+    fn for_parser_labels() {
+        let mut parser = Parser::new();
+        let result = parser.run("src/programs/Max.asm");
+        assert_eq!(
+            result.labels(),
+            vec![
+                ("OUTPUT_FIRST".to_string(), 18),
+                ("OUTPUT_D".to_string(), 20),
+                ("INFINITE_LOOP".to_string(), 22)
+            ]
+        );
+    }
+
     #[test]
     fn for_parser_rect() {
         let mut parser = Parser::new();
@@ -1,24 +1,44 @@
-type MyIncomingMessage = {
-  register_index: number;
-  x: number;
-  y: number;
-  input: string;
+// the messages of hardware/src/protocol.rs
+const VERSION = 1;
+
+// [register_index, count, value]: count words from register_index are value
+type Run = [number, number, number];
+
+type Frame = {
+  type: "frame";
+  // the whole screen, e.g. after hello
+  keyframe: boolean;
+  runs: Run[];
 };
 
+type MyIncomingMessage =
+  | { type: "welcome"; version: number }
+  | Frame
+  | { type: "error"; message: string };
+
+type Control =
+  | { command: "run" }
+  | { command: "pause" }
+  | { command: "reset" }
+  | { command: "load"; path: string };
+
 const drawCanvas = (
   ctx: CanvasRenderingContext2D,
-  { x, y, input }: MyIncomingMessage
+  imageData: ImageData,
+  { runs }: Frame
 ) => {
-  const imageData = ctx.createImageData(16, 1);
-  for (let i = 0; i < imageData.data.length; i += 4) {
-    const digit = i / 4;
-    const isFill = input[digit] === "1";
-    imageData.data[i] = 0;
-    imageData.data[i + 1] = 0;
-    imageData.data[i + 2] = 0;
-    imageData.data[i + 3] = isFill ? 255 : 0;
+  for (const [registerIndex, count, value] of runs) {
+    for (let index = registerIndex; index < registerIndex + count; index++) {
+      const x = (index % 32) * 16;
+      const y = Math.floor(index / 32);
+      // the LSB is the leftmost pixel
+      for (let bit = 0; bit < 16; bit++) {
+        const isFill = (value & (1 << bit)) !== 0;
+        imageData.data[(y * 512 + x + bit) * 4 + 3] = isFill ? 255 : 0;
+      }
+    }
   }
-  ctx.putImageData(imageData, x, y);
+  ctx.putImageData(imageData, 0, 0);
 };
 
 const main = () => {
@@ -29,14 +49,25 @@ const main = () => {
   const ctx = canvas.getContext("2d");
   if (!ctx) return;
 
+  const imageData = ctx.createImageData(512, 256);
   const ws = new WebSocket("ws://127.0.0.1:9001");
   ws.addEventListener("open", (e) => {
     console.log("open");
+    ws.send(JSON.stringify({ type: "hello", version: VERSION }));
   });
   ws.addEventListener("message", (e) => {
     const parsed = JSON.parse(e.data) as MyIncomingMessage;
-    console.log("message", parsed);
-    drawCanvas(ctx, parsed);
+    switch (parsed.type) {
+      case "welcome":
+        console.log(`welcome, protocol version ${parsed.version}`);
+        break;
+      case "frame":
+        drawCanvas(ctx, imageData, parsed);
+        break;
+      case "error":
+        console.error(parsed.message);
+        break;
+    }
   });
   ws.addEventListener("error", (e) => {
     console.log("error");
@@ -45,16 +76,25 @@ const main = () => {
     console.log("close");
   });
 
+  const sendControl = (control: Control) => {
+    ws.send(JSON.stringify({ type: "control", ...control }));
+  };
+  for (const command of ["run", "pause", "reset"] as const) {
+    document
+      .getElementById(command)
+      ?.addEventListener("click", () => sendControl({ command }));
+  }
+
   window.addEventListener("keydown", (e) => {
     const key = getAsciiOrHackcode(e.key);
-    const data = { key, down: true };
+    const data = { type: "key", key, down: true };
     console.log(`keydown ${JSON.stringify(data)}`);
     ws.send(JSON.stringify(data));
   });
 
   window.addEventListener("keyup", (e) => {
     const key = getAsciiOrHackcode(e.key);
-    const data = { key, down: false };
+    const data = { type: "key", key, down: false };
     console.log(`keyup ${JSON.stringify(data)}`);
     ws.send(JSON.stringify(data));
   });
@@ -1,11 +1,15 @@
 pub mod alu;
 pub mod arithmetic;
+pub mod bus;
+pub mod chip;
 pub mod cpu;
 pub mod dff;
+pub mod image;
 pub mod keyboard;
 pub mod logic;
 pub mod pc;
 pub mod ram;
 pub mod register;
 pub mod rom;
+pub mod scheduler;
 pub mod screen;
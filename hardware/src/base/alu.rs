@@ -1,7 +1,13 @@
 #![allow(dead_code)]
+use std::sync::OnceLock;
+
 use crate::base::arithmetic::*;
+use crate::base::chip::{check, declare, Chip};
 use crate::base::logic::bit::O;
 use crate::base::logic::*;
+use crate::error::HardwareError;
+use crate::hdl::{from_word, parser::PinDecl, to_word};
+use crate::profile;
 
 // -> (f(x,y), zr, ng)
 pub fn alu(
@@ -14,6 +20,7 @@ pub fn alu(
     f: bit,
     no: bit,
 ) -> (Word, bit, bit) {
+    let _profile = profile::chip("alu");
     let all0 = Word::new([O; 16]);
     let x1 = mux16(x, all0, zx);
     let x2 = mux16(x1, not16(x1), nx);
@@ -38,6 +45,98 @@ pub fn alu(
     (out2, zr, ng)
 }
 
+// the control bits in the order of alu
+const CONTROL: [&str; 6] = ["zx", "nx", "zy", "ny", "f", "no"];
+
+// the pins of ALU, also for the gate of hdl::builtin
+pub(crate) const INPUTS: &[(&str, usize)] = &[
+    ("x", 16),
+    ("y", 16),
+    ("zx", 1),
+    ("nx", 1),
+    ("zy", 1),
+    ("ny", 1),
+    ("f", 1),
+    ("no", 1),
+];
+pub(crate) const OUTPUTS: &[(&str, usize)] = &[("out", 16), ("zr", 1), ("ng", 1)];
+
+// alu with its input pins, as a Chip
+pub struct ALU {
+    x: Word,
+    y: Word,
+    control: [bit; 6],
+}
+
+impl ALU {
+    pub fn new() -> Self {
+        Self {
+            x: Word::new([O; 16]),
+            y: Word::new([O; 16]),
+            control: [O; 6],
+        }
+    }
+
+    // -> (f(x,y), zr, ng)
+    pub fn output(&self) -> (Word, bit, bit) {
+        let [zx, nx, zy, ny, f, no] = self.control;
+        alu(self.x, self.y, zx, nx, zy, ny, f, no)
+    }
+}
+
+impl Default for ALU {
+    fn default() -> Self {
+        ALU::new()
+    }
+}
+
+impl Chip for ALU {
+    fn name(&self) -> &str {
+        "ALU"
+    }
+
+    fn inputs(&self) -> &[PinDecl] {
+        static PINS: OnceLock<Vec<PinDecl>> = OnceLock::new();
+        declare(&PINS, INPUTS)
+    }
+
+    fn outputs(&self) -> &[PinDecl] {
+        static PINS: OnceLock<Vec<PinDecl>> = OnceLock::new();
+        declare(&PINS, OUTPUTS)
+    }
+
+    fn set_input(&mut self, pin: &str, bits: &[bit]) -> Result<(), HardwareError> {
+        check(self, pin, bits)?;
+        match pin {
+            "x" => self.x = to_word(bits),
+            "y" => self.y = to_word(bits),
+            _ => {
+                let i = CONTROL.iter().position(|c| *c == pin).unwrap();
+                self.control[i] = bits[0];
+            }
+        }
+        Ok(())
+    }
+
+    fn get(&self, pin: &str) -> Option<Vec<bit>> {
+        let (out, zr, ng) = self.output();
+        match pin {
+            "x" => Some(from_word(self.x)),
+            "y" => Some(from_word(self.y)),
+            "out" => Some(from_word(out)),
+            "zr" => Some(vec![zr]),
+            "ng" => Some(vec![ng]),
+            _ => CONTROL
+                .iter()
+                .position(|c| *c == pin)
+                .map(|i| vec![self.control[i]]),
+        }
+    }
+
+    // the outputs follow the inputs
+    fn eval(&mut self) {}
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -1,12 +1,15 @@
 #![allow(dead_code)]
 use crate::base::logic::bit::{I, O};
 use crate::base::logic::*;
+use crate::profile;
 
 pub fn half_adder(a: bit, b: bit) -> [bit; 2] {
+    let _profile = profile::chip("half_adder");
     [and(a, b), xor(a, b)]
 }
 
 pub fn full_adder(a: bit, b: bit, c: bit) -> [bit; 2] {
+    let _profile = profile::chip("full_adder");
     let bc = half_adder(b, c);
     let a_bc1 = half_adder(a, bc[1]);
     [or(bc[0], a_bc1[0]), a_bc1[1]]
@@ -17,6 +20,7 @@ pub fn inc(a: bit) -> [bit; 2] {
 }
 
 pub fn add16(a: Word, b: Word) -> Word {
+    let _profile = profile::chip("add16");
     let added15 = half_adder(a[15], b[15]);
     let added14 = full_adder(a[14], b[14], added15[0]);
     let added13 = full_adder(a[13], b[13], added14[0]);
@@ -41,6 +45,7 @@ pub fn add16(a: Word, b: Word) -> Word {
 }
 
 pub fn inc16(a: Word) -> Word {
+    let _profile = profile::chip("inc16");
     add16(
         a,
         Word::new([O, O, O, O, O, O, O, O, O, O, O, O, O, O, O, I]),
@@ -0,0 +1,142 @@
+use std::{
+    fmt::{self, Formatter},
+    ops::{Deref, DerefMut},
+};
+
+use crate::base::logic::{
+    bit::{self, I, O},
+    Word,
+};
+
+/// N wires, MSB first as the addresses of the chips.
+/// The widths of slice, split and concat are checked when they are compiled.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Bus<const N: usize>([bit; N]);
+
+impl<const N: usize> Bus<N> {
+    pub fn new(bits: [bit; N]) -> Self {
+        Self(bits)
+    }
+
+    // the low N bits of the number
+    pub fn from_number(number: usize) -> Self {
+        let mut bits = [O; N];
+        for (i, b) in bits.iter_mut().enumerate() {
+            if number >> (N - 1 - i) & 1 == 1 {
+                *b = I;
+            }
+        }
+        Self(bits)
+    }
+
+    pub fn number(&self) -> usize {
+        self.0
+            .iter()
+            .fold(0, |sum, b| (sum << 1) | if *b == I { 1 } else { 0 })
+    }
+
+    pub fn bits(&self) -> [bit; N] {
+        self.0
+    }
+
+    // M wires from START
+    pub fn slice<const START: usize, const M: usize>(&self) -> Bus<M> {
+        const { assert!(START + M <= N) };
+        let mut bits = [O; M];
+        bits.copy_from_slice(&self.0[START..START + M]);
+        Bus(bits)
+    }
+
+    // the first M wires and the other R
+    pub fn split<const M: usize, const R: usize>(&self) -> (Bus<M>, Bus<R>) {
+        const { assert!(M + R == N) };
+        (self.slice::<0, M>(), self.slice::<M, R>())
+    }
+
+    // these wires, then the M of other
+    pub fn concat<const M: usize, const R: usize>(&self, other: Bus<M>) -> Bus<R> {
+        const { assert!(N + M == R) };
+        let mut bits = [O; R];
+        bits[..N].copy_from_slice(&self.0);
+        bits[N..].copy_from_slice(&other.0);
+        Bus(bits)
+    }
+}
+
+// all wires low
+impl<const N: usize> Default for Bus<N> {
+    fn default() -> Self {
+        Bus([O; N])
+    }
+}
+
+impl<const N: usize> Deref for Bus<N> {
+    type Target = [bit; N];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<const N: usize> DerefMut for Bus<N> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<const N: usize> From<[bit; N]> for Bus<N> {
+    fn from(bits: [bit; N]) -> Self {
+        Bus(bits)
+    }
+}
+
+impl<const N: usize> From<Bus<N>> for [bit; N] {
+    fn from(bus: Bus<N>) -> Self {
+        bus.0
+    }
+}
+
+impl From<Word> for Bus<16> {
+    fn from(word: Word) -> Self {
+        Bus::from_number(u16::from(word) as usize)
+    }
+}
+
+impl From<Bus<16>> for Word {
+    fn from(bus: Bus<16>) -> Self {
+        Word::new(bus.0)
+    }
+}
+
+impl<const N: usize> fmt::Display for Bus<N> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for b in self.0.iter() {
+            write!(f, "{}", if *b == I { 1 } else { 0 })?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn for_bus() {
+        let bus = Bus::<5>::new([I, O, I, I, O]);
+        assert_eq!(bus.number(), 0b10110);
+        assert_eq!(Bus::<5>::from_number(0b110110), bus);
+        assert_eq!(bus.slice::<1, 3>(), Bus::new([O, I, I]));
+
+        let (high, low): (Bus<2>, Bus<3>) = bus.split();
+        assert_eq!((high.number(), low.number()), (0b10, 0b110));
+        assert_eq!(high.concat::<3, 5>(low), bus);
+        assert_eq!(bus[0], I);
+        assert_eq!(bus.to_string(), "10110");
+
+        let word = Word::from(0x8001u16);
+        let wires = Bus::<16>::from(word);
+        assert_eq!(wires.slice::<1, 15>().number(), 1);
+        assert_eq!(Word::from(wires), word);
+    }
+}
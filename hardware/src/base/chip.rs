@@ -0,0 +1,357 @@
+use std::{any::Any, sync::OnceLock};
+
+use crate::{
+    base::{
+        alu::ALU,
+        cpu::CPU,
+        dff::Dff,
+        keyboard::Keyboard,
+        logic::bit,
+        pc::PC,
+        ram::{RAM16K, RAM4K, RAM512, RAM64, RAM8},
+        register::{Bit, Register},
+        rom::ROM32K,
+        screen::Screen,
+    },
+    error::HardwareError,
+    hdl::{self, builtin::pins, parser::PinDecl},
+};
+
+/// A chip driven through its named pins, e.g. by a test script, a tracer or the debugger.
+/// Buses are LSB first: `bits[i]` is `pin[i]`, as in HDL.
+/// The chips in base implement it beside their own signatures,
+/// and are also reached by their names with `builtin`.
+/// As `Any`, the owner of a chip gets its own type back, e.g. the CPU of a machine.
+pub trait Chip: Any {
+    fn name(&self) -> &str;
+    fn inputs(&self) -> &[PinDecl];
+    fn outputs(&self) -> &[PinDecl];
+    // takes effect on the next eval or tick
+    fn set_input(&mut self, pin: &str, bits: &[bit]) -> Result<(), HardwareError>;
+    // the value of an input or an output
+    fn get(&self, pin: &str) -> Option<Vec<bit>>;
+    fn eval(&mut self);
+
+    // sequential chips sample their inputs on tick and change their outputs on tock
+    fn is_clocked(&self) -> bool {
+        false
+    }
+    fn tick(&mut self) {}
+    fn tock(&mut self) {}
+
+    // whether the output changes with the input without the clock
+    fn follows(&self, _output: &str, _input: &str) -> bool {
+        !self.is_clocked()
+    }
+    // the register of the chip takes the bits at once; false if it has none
+    fn set_register(&mut self, _bits: &[bit]) -> bool {
+        false
+    }
+
+    fn input_pin(&self, pin: &str) -> Option<&PinDecl> {
+        self.inputs().iter().find(|p| p.name == pin)
+    }
+    fn output_pin(&self, pin: &str) -> Option<&PinDecl> {
+        self.outputs().iter().find(|p| p.name == pin)
+    }
+}
+
+// the names builtin knows
+const NAMES: &[&str] = &[
+    "Nand",
+    "Not",
+    "And",
+    "Or",
+    "Xor",
+    "Mux",
+    "DMux",
+    "Not16",
+    "And16",
+    "Or16",
+    "Mux16",
+    "Or8Way",
+    "Mux4Way16",
+    "Mux8Way16",
+    "DMux4Way",
+    "DMux8Way",
+    "HalfAdder",
+    "FullAdder",
+    "Add16",
+    "Inc16",
+    "ALU",
+    "DFF",
+    "Bit",
+    "Register",
+    "ARegister",
+    "DRegister",
+    "PC",
+    "RAM8",
+    "RAM64",
+    "RAM512",
+    "RAM4K",
+    "RAM16K",
+    "ROM32K",
+    "Screen",
+    "Keyboard",
+    "CPU",
+];
+
+// a chip by its name, e.g. "ALU", "RAM8" or "CPU", and the gates of hdl::builtin, e.g. "Nand"
+pub fn builtin(name: &str) -> Option<Box<dyn Chip>> {
+    part(name)
+        .or_else(|| hdl::chip::Chip::builtin(name).map(|chip| Box::new(chip) as Box<dyn Chip>))
+}
+
+// a chip of base by its name, which is also the part of the name in HDL
+pub(crate) fn part(name: &str) -> Option<Box<dyn Chip>> {
+    let chip: Box<dyn Chip> = match name {
+        "ALU" => Box::new(ALU::new()),
+        "DFF" => Box::new(Dff::new()),
+        "Bit" => Box::new(Bit::new()),
+        "Register" | "ARegister" | "DRegister" => Box::new(Register::new()),
+        "PC" => Box::new(PC::new()),
+        "RAM8" => Box::new(RAM8::new()),
+        "RAM64" => Box::new(RAM64::new()),
+        "RAM512" => Box::new(RAM512::new()),
+        "RAM4K" => Box::new(RAM4K::new()),
+        "RAM16K" => Box::new(RAM16K::new()),
+        "ROM32K" => Box::new(ROM32K::new()),
+        "Screen" => Box::new(Screen::new(None)),
+        "Keyboard" => Box::new(Keyboard::new(None)),
+        "CPU" => Box::new(CPU::new()),
+        _ => return None,
+    };
+    Some(chip)
+}
+
+pub fn names() -> &'static [&'static str] {
+    NAMES
+}
+
+// the pins of a chip of base, declared on the first use
+pub(crate) fn declare(
+    declared: &'static OnceLock<Vec<PinDecl>>,
+    decl: &[(&str, usize)],
+) -> &'static [PinDecl] {
+    declared.get_or_init(|| pins(decl))
+}
+
+// bits which fit an input of the chip
+pub(crate) fn check(chip: &dyn Chip, pin: &str, bits: &[bit]) -> Result<(), HardwareError> {
+    match chip.input_pin(pin) {
+        None => Err(HardwareError::new(&format!(
+            "{} has no input {}",
+            chip.name(),
+            pin
+        ))),
+        Some(decl) if decl.width != bits.len() => Err(HardwareError::new(&format!(
+            "{} is {} bits wide",
+            pin, decl.width
+        ))),
+        Some(_) => Ok(()),
+    }
+}
+
+impl Chip for hdl::chip::Chip {
+    fn name(&self) -> &str {
+        self.name()
+    }
+
+    fn inputs(&self) -> &[PinDecl] {
+        self.inputs()
+    }
+
+    fn outputs(&self) -> &[PinDecl] {
+        self.outputs()
+    }
+
+    fn set_input(&mut self, pin: &str, bits: &[bit]) -> Result<(), HardwareError> {
+        Ok(self.set(pin, bits)?)
+    }
+
+    fn get(&self, pin: &str) -> Option<Vec<bit>> {
+        self.get(pin)
+    }
+
+    fn eval(&mut self) {
+        self.eval()
+    }
+
+    fn is_clocked(&self) -> bool {
+        self.is_clocked()
+    }
+
+    fn tick(&mut self) {
+        self.tick()
+    }
+
+    fn tock(&mut self) {
+        self.tock()
+    }
+
+    fn follows(&self, output: &str, input: &str) -> bool {
+        self.follows(output, input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::base::logic::{
+        bit::{I, O},
+        Word,
+    };
+
+    fn number(bits: &[bit]) -> usize {
+        bits.iter()
+            .rev()
+            .fold(0, |sum, b| (sum << 1) | if *b == I { 1 } else { 0 })
+    }
+
+    fn bits(number: usize, width: usize) -> Vec<bit> {
+        (0..width)
+            .map(|i| if number >> i & 1 == 1 { I } else { O })
+            .collect()
+    }
+
+    #[test]
+    fn for_chip_builtin() {
+        for name in names() {
+            let chip = builtin(name).unwrap();
+            // ARegister and DRegister are Registers
+            assert!(
+                chip.name() == *name || chip.name() == "Register",
+                "{}",
+                name
+            );
+            assert!(!chip.outputs().is_empty(), "{}", name);
+        }
+        assert!(builtin("Nothing").is_none());
+
+        // x + y
+        let mut alu = builtin("ALU").unwrap();
+        assert_eq!(alu.input_pin("x").unwrap().width, 16);
+        assert_eq!(alu.output_pin("zr").unwrap().width, 1);
+        assert!(!alu.is_clocked());
+        alu.set_input("x", &bits(2, 16)).unwrap();
+        alu.set_input("y", &bits(3, 16)).unwrap();
+        alu.set_input("f", &[I]).unwrap();
+        alu.eval();
+        assert_eq!(number(&alu.get("out").unwrap()), 5);
+        assert_eq!(alu.get("zr").unwrap(), vec![O]);
+        assert!(alu.set_input("x", &[I]).is_err());
+        assert!(alu.set_input("z", &[I]).is_err());
+    }
+
+    // -> outM, writeM before the clock
+    fn run(cpu: &mut dyn Chip, instruction: u16, in_m: usize) -> (usize, bit) {
+        cpu.set_input("instruction", &bits(instruction as usize, 16))
+            .unwrap();
+        cpu.set_input("inM", &bits(in_m, 16)).unwrap();
+        cpu.eval();
+        let out = (
+            number(&cpu.get("outM").unwrap()),
+            cpu.get("writeM").unwrap()[0],
+        );
+        cpu.tick();
+        cpu.tock();
+        out
+    }
+
+    // @7, D=A, @100, M=D+M
+    fn add(cpu: &mut dyn Chip) {
+        run(cpu, 7, 0);
+        run(cpu, 0xec10, 0);
+        assert_eq!(run(cpu, 100, 0).1, O);
+        assert_eq!(number(&cpu.get("addressM").unwrap()), 100);
+        assert_eq!(run(cpu, 0xf088, 5), (12, I));
+        assert_eq!(number(&cpu.get("pc").unwrap()), 4);
+    }
+
+    #[test]
+    fn for_chip_cpu() {
+        let mut cpu = builtin("CPU").unwrap();
+        assert!(cpu.is_clocked());
+        assert_eq!(cpu.output_pin("addressM").unwrap().width, 15);
+        add(cpu.as_mut());
+        add(&mut CPU::new());
+    }
+
+    #[test]
+    fn for_chip_base() {
+        // the chips of base, and the gates of HDL
+        let ram = builtin("RAM16K").unwrap();
+        assert!((ram.as_ref() as &dyn Any).is::<RAM16K>());
+        let nand = builtin("Nand").unwrap();
+        assert!((nand.as_ref() as &dyn Any).is::<hdl::chip::Chip>());
+
+        let mut alu = ALU::new();
+        alu.set_input("x", &bits(2, 16)).unwrap();
+        alu.set_input("y", &bits(3, 16)).unwrap();
+        alu.set_input("f", &[I]).unwrap();
+        assert_eq!(number(&alu.get("out").unwrap()), 5);
+        assert!(alu.set_input("z", &[I]).is_err());
+
+        // the output changes on tock
+        let mut ram = RAM64::new();
+        ram.set_input("in", &bits(9, 16)).unwrap();
+        ram.set_input("load", &[I]).unwrap();
+        ram.set_input("address", &bits(33, 6)).unwrap();
+        Chip::tick(&mut ram);
+        assert_eq!(number(&ram.get("out").unwrap()), 0);
+        Chip::tock(&mut ram);
+        assert_eq!(number(&ram.get("out").unwrap()), 9);
+        ram.set_input("address", &bits(32, 6)).unwrap();
+        assert_eq!(number(&ram.get("out").unwrap()), 0);
+        assert!(ram.set_input("address", &bits(0, 5)).is_err());
+        assert!(ram.follows("out", "address"));
+        assert!(!ram.follows("out", "in"));
+    }
+
+    #[test]
+    fn for_chip_sequential() {
+        let mut dff = Dff::new();
+        dff.set_input("in", &[I]).unwrap();
+        Chip::tick(&mut dff);
+        assert_eq!(dff.get("out").unwrap(), vec![O]);
+        Chip::tock(&mut dff);
+        assert_eq!(dff.get("out").unwrap(), vec![I]);
+
+        // kept without load
+        let mut b = Bit::new();
+        b.set_input("in", &[I]).unwrap();
+        Chip::tick(&mut b);
+        Chip::tock(&mut b);
+        assert_eq!(b.get("out").unwrap(), vec![O]);
+        b.set_input("load", &[I]).unwrap();
+        Chip::tick(&mut b);
+        Chip::tock(&mut b);
+        assert_eq!(b.get("out").unwrap(), vec![I]);
+
+        let mut screen = Screen::new(None);
+        assert_eq!(screen.input_pin("address").unwrap().width, 13);
+        screen.set_input("in", &bits(7, 16)).unwrap();
+        screen.set_input("load", &[I]).unwrap();
+        screen.set_input("address", &bits(100, 13)).unwrap();
+        Chip::tick(&mut screen);
+        Chip::tock(&mut screen);
+        assert_eq!(number(&screen.get("out").unwrap()), 7);
+        assert_eq!(screen.framebuffer().words()[100], 7);
+
+        let mut keyboard = Keyboard::new(None);
+        assert!(keyboard.inputs().is_empty());
+        assert!(!keyboard.is_clocked());
+        keyboard.set(Word::from(75u16));
+        assert_eq!(number(&keyboard.get("out").unwrap()), 75);
+        assert!(keyboard.set_input("in", &[I]).is_err());
+
+        // a register takes a value at once
+        let mut register = Register::new();
+        assert!(register.set_register(&bits(42, 16)));
+        assert_eq!(number(&register.get("out").unwrap()), 42);
+        let mut pc = PC::new();
+        assert!(pc.set_register(&bits(3, 16)));
+        assert_eq!(number(&pc.get("out").unwrap()), 3);
+        assert!(!ROM32K::new().set_register(&bits(3, 16)));
+    }
+}
@@ -1,18 +1,56 @@
 #![allow(dead_code, non_snake_case)]
+use std::sync::OnceLock;
+
 use crate::base::{
     alu::alu,
-    dff::Clock,
-    dff::ClockState::{Tick, Tock},
-    logic::bit::I,
+    bus::Bus,
+    chip::{check, declare, Chip},
+    logic::bit::{I, O},
     logic::{and, bit, mux16, not, or, Word},
     pc::PC,
     register::Register,
 };
+use crate::error::HardwareError;
+use crate::hdl::{from_word, parser::PinDecl, to_word};
+use crate::profile;
+use serde::{Deserialize, Serialize};
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct CPU {
     pc: PC,
     d_register: Register,
     a_register: Register,
+    // zr and ng of the last tick
+    flags: (bit, bit),
+    // inM, instruction and reset of the Chip
+    #[serde(skip, default = "CPU::pins")]
+    pins: (Word, Word, bit),
+    // the signals of the pins, settled by eval until the pins or the registers change
+    #[serde(skip)]
+    settled: Option<Signals>,
+}
+
+// the signals of an instruction, from the registers before the clock
+#[derive(Clone, Copy)]
+struct Signals {
+    alu: Word,
+    zr: bit,
+    ng: bit,
+    // in and load of the A register
+    a_in: Word,
+    load_a: bit,
+    load_d: bit,
+    jump: bit,
+    write_m: bit,
+}
+
+// the output pins of a cycle
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct CPUOutput {
+    pub out_m: Word,
+    pub write_m: bit,
+    pub address_m: [bit; 15],
+    pub pc: [bit; 15],
 }
 
 impl CPU {
@@ -21,33 +59,23 @@ impl CPU {
             pc: PC::new(),
             a_register: Register::new(),
             d_register: Register::new(),
+            flags: (O, O),
+            pins: CPU::pins(),
+            settled: None,
         }
     }
+    fn pins() -> (Word, Word, bit) {
+        (Word::new([O; 16]), Word::new([O; 16]), O)
+    }
 
-    // -> outM, writeM, addressM[15], pc[15]
-    pub fn run(
-        &mut self,
-        clock_t: &Clock,
-        in_m: Word,
-        instruction: Word,
-        reset: bit,
-    ) -> (Word, bit, [bit; 15], [bit; 15]) {
-        let clock_t_1 = match clock_t.state {
-            Tick => {
-                let mut c = Clock::new();
-                c.next();
-                c
-            }
-            Tock => Clock::new(),
-        };
-        let current_a_value = self.a_register.output(&clock_t_1);
-        let current_d_value = self.d_register.output(&clock_t_1);
-        // println!("d_reg: {}, a_reg: {}", current_d_value, current_a_value);
+    fn signals(&self, in_m: Word, instruction: Word) -> Signals {
+        let a_value = self.a_register.output();
+        let d_value = self.d_register.output();
         let (i, a, cccccc, ddd, jjj) = CPU::decode(instruction);
 
         let (alu, zr, ng) = alu(
-            current_d_value,
-            mux16(current_a_value, in_m, a),
+            d_value,
+            mux16(a_value, in_m, a),
             cccccc[0],
             cccccc[1],
             cccccc[2],
@@ -55,65 +83,110 @@ impl CPU {
             cccccc[4],
             cccccc[5],
         );
-
-        self.a_register.input(
-            clock_t,
-            mux16(instruction, alu, i),
-            or(/* A命令 */ not(i), /* C命令 */ ddd[0]),
-        );
-
-        self.d_register.input(clock_t, alu, and(ddd[1], i));
-
         let is_jump = or(
             or(and(jjj[0], ng), and(jjj[1], zr)),
             and(jjj[2], not(or(zr, ng))),
         );
-        self.pc
-            .run(clock_t, current_a_value, I, and(is_jump, i), reset);
+        Signals {
+            alu,
+            zr,
+            ng,
+            a_in: mux16(instruction, alu, i),
+            load_a: or(/* A命令 */ not(i), /* C命令 */ ddd[0]),
+            load_d: and(ddd[1], i),
+            jump: and(is_jump, i),
+            write_m: and(i, ddd[2]),
+        }
+    }
+
+    // outM and writeM of the instruction, and addressM and pc as they will be after the clock
+    pub fn output(&self, in_m: Word, instruction: Word, reset: bit) -> CPUOutput {
+        self.output_of(&self.signals(in_m, instruction), reset)
+    }
 
-        // clock_t_1 = clock_t_+1
-        // この２つは更新後の値を使う
-        let next_a_value = self.a_register.output(&clock_t_1);
-        let next_pc_value = self.pc.output(&clock_t_1);
+    fn output_of(&self, signals: &Signals, reset: bit) -> CPUOutput {
+        let a_value = self.a_register.output();
+        let next_a_value = mux16(a_value, signals.a_in, signals.load_a);
+        let next_pc_value = self.pc.next(a_value, I, signals.jump, reset);
 
-        (
-            alu,
-            and(i, ddd[2]),
-            [
-                next_a_value[1],
-                next_a_value[2],
-                next_a_value[3],
-                next_a_value[4],
-                next_a_value[5],
-                next_a_value[6],
-                next_a_value[7],
-                next_a_value[8],
-                next_a_value[9],
-                next_a_value[10],
-                next_a_value[11],
-                next_a_value[12],
-                next_a_value[13],
-                next_a_value[14],
-                next_a_value[15],
-            ],
-            [
-                next_pc_value[1],
-                next_pc_value[2],
-                next_pc_value[3],
-                next_pc_value[4],
-                next_pc_value[5],
-                next_pc_value[6],
-                next_pc_value[7],
-                next_pc_value[8],
-                next_pc_value[9],
-                next_pc_value[10],
-                next_pc_value[11],
-                next_pc_value[12],
-                next_pc_value[13],
-                next_pc_value[14],
-                next_pc_value[15],
-            ],
-        )
+        CPUOutput {
+            out_m: signals.alu,
+            write_m: signals.write_m,
+            address_m: Bus::from(next_a_value).slice::<1, 15>().bits(),
+            pc: Bus::from(next_pc_value).slice::<1, 15>().bits(),
+        }
+    }
+
+    // the rising edge
+    pub fn tick(&mut self, in_m: Word, instruction: Word, reset: bit) {
+        let signals = self.signals(in_m, instruction);
+        self.edge(signals, reset);
+    }
+
+    fn edge(&mut self, signals: Signals, reset: bit) {
+        let _profile = profile::chip("CPU.run");
+        self.settled = None;
+        let a_value = self.a_register.output();
+        self.flags = (signals.zr, signals.ng);
+
+        self.a_register.tick(signals.a_in, signals.load_a);
+        self.d_register.tick(signals.alu, signals.load_d);
+        self.pc.tick(a_value, I, signals.jump, reset);
+    }
+
+    // the falling edge
+    pub fn tock(&mut self) {
+        self.settled = None;
+        self.a_register.tock();
+        self.d_register.tock();
+        self.pc.tock();
+    }
+
+    // current values of the registers
+    pub fn a_output(&self) -> Word {
+        self.a_register.output()
+    }
+    pub fn d_output(&self) -> Word {
+        self.d_register.output()
+    }
+    pub fn pc_output(&self) -> Word {
+        self.pc.output()
+    }
+    // -> zr, ng of the ALU in the last tick
+    pub fn flags(&self) -> (bit, bit) {
+        self.flags
+    }
+
+    // the inputs of the next tick as a Chip
+    pub(crate) fn set_pins(&mut self, in_m: Word, instruction: Word, reset: bit) {
+        self.pins = (in_m, instruction, reset);
+        self.settled = None;
+    }
+
+    // the outputs of the pins, through the signals eval settled if it did
+    pub(crate) fn settled(&self) -> CPUOutput {
+        let (in_m, instruction, reset) = self.pins;
+        match &self.settled {
+            Some(signals) => self.output_of(signals, reset),
+            None => self.output(in_m, instruction, reset),
+        }
+    }
+
+    // load the registers in a cycle of their own
+    pub fn set_a(&mut self, input: Word) {
+        self.settled = None;
+        self.a_register.tick(input, I);
+        self.a_register.tock();
+    }
+    pub fn set_d(&mut self, input: Word) {
+        self.settled = None;
+        self.d_register.tick(input, I);
+        self.d_register.tock();
+    }
+    pub fn set_pc(&mut self, input: Word) {
+        self.settled = None;
+        self.pc.tick(input, O, I, O);
+        self.pc.tock();
     }
 
     fn decode(word: Word) -> (bit, bit, [bit; 6], [bit; 3], [bit; 3]) {
@@ -127,22 +200,108 @@ impl CPU {
     }
 }
 
+impl Chip for CPU {
+    fn name(&self) -> &str {
+        "CPU"
+    }
+
+    fn inputs(&self) -> &[PinDecl] {
+        static PINS: OnceLock<Vec<PinDecl>> = OnceLock::new();
+        declare(&PINS, &[("inM", 16), ("instruction", 16), ("reset", 1)])
+    }
+
+    fn outputs(&self) -> &[PinDecl] {
+        static PINS: OnceLock<Vec<PinDecl>> = OnceLock::new();
+        declare(
+            &PINS,
+            &[("outM", 16), ("writeM", 1), ("addressM", 15), ("pc", 15)],
+        )
+    }
+
+    fn set_input(&mut self, pin: &str, bits: &[bit]) -> Result<(), HardwareError> {
+        check(self, pin, bits)?;
+        match pin {
+            "inM" => self.pins.0 = to_word(bits),
+            "instruction" => self.pins.1 = to_word(bits),
+            _ => self.pins.2 = bits[0],
+        }
+        self.settled = None;
+        Ok(())
+    }
+
+    fn get(&self, pin: &str) -> Option<Vec<bit>> {
+        let (in_m, instruction, reset) = self.pins;
+        // LSB first, without the high bit
+        let address = |word: Word| from_word(word)[..15].to_vec();
+        match pin {
+            "inM" => Some(from_word(in_m)),
+            "instruction" => Some(from_word(instruction)),
+            "reset" => Some(vec![reset]),
+            "outM" => Some(from_word(self.settled().out_m)),
+            "writeM" => Some(vec![self.settled().write_m]),
+            "addressM" => Some(address(self.a_output())),
+            "pc" => Some(address(self.pc_output())),
+            _ => None,
+        }
+    }
+
+    // the ALU, once for the outputs and the next tick
+    fn eval(&mut self) {
+        let (in_m, instruction, _) = self.pins;
+        self.settled = Some(self.signals(in_m, instruction));
+    }
+
+    fn is_clocked(&self) -> bool {
+        true
+    }
+
+    fn tick(&mut self) {
+        let (in_m, instruction, reset) = self.pins;
+        let signals = match self.settled {
+            Some(signals) => signals,
+            None => self.signals(in_m, instruction),
+        };
+        self.edge(signals, reset);
+    }
+
+    fn tock(&mut self) {
+        CPU::tock(self);
+    }
+
+    // outM and writeM follow inM and instruction, addressM and pc the registers
+    fn follows(&self, output: &str, input: &str) -> bool {
+        matches!(output, "outM" | "writeM") && matches!(input, "inM" | "instruction")
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use bit::O;
 
+    // a cycle, and the outputs before it
+    fn run(cpu: &mut CPU, in_m: Word, instruction: Word, reset: bit) -> CPUOutput {
+        let output = cpu.output(in_m, instruction, reset);
+        cpu.tick(in_m, instruction, reset);
+        cpu.tock();
+        output
+    }
+
     #[test]
     fn for_cpu() {
-        let mut clock = Clock::new();
         let mut cpu = CPU::new();
 
         let word0 = Word::new([O, O, O, O, O, O, O, O, O, O, O, O, O, O, O, O]);
         let word1 = Word::new([I, I, I, I, I, I, I, I, I, I, I, I, I, I, I, I]);
 
         // CLOCK: TICK
-        let (outM, writeM, addressM, pc) = cpu.run(
-            &clock,
+        let CPUOutput {
+            out_m: outM,
+            write_m: writeM,
+            address_m: addressM,
+            pc,
+        } = run(
+            &mut cpu,
             word0,
             /* A命令 addr: [O, I, I, O, O, O, O, O, O, I, I, I, O, O, I] */
             Word::new([O, O, I, I, O, O, O, O, O, O, I, I, I, O, O, I]),
@@ -153,12 +312,14 @@ mod tests {
         assert_eq!(addressM, [O, I, I, O, O, O, O, O, O, I, I, I, O, O, I]); // A命令なのでAレジスタにセットした値が返る
         assert_eq!(pc, [O, O, O, O, O, O, O, O, O, O, O, O, O, O, I]); // PCは１つ上がる
 
-        clock.next();
-        clock.next();
-
-        // // CLOCK: TICK
-        let (outM, writeM, addressM, pc) = cpu.run(
-            &clock,
+        // CLOCK: TICK
+        let CPUOutput {
+            out_m: outM,
+            write_m: writeM,
+            address_m: addressM,
+            pc,
+        } = run(
+            &mut cpu,
             word0,
             /* comp: A, dest: D -> D=A */
             Word::new([I, I, I, O, I, I, O, O, O, O, O, I, O, O, O, O]),
@@ -172,16 +333,18 @@ mod tests {
         assert_eq!(addressM, [O, I, I, O, O, O, O, O, O, I, I, I, O, O, I]);
         assert_eq!(pc, [O, O, O, O, O, O, O, O, O, O, O, O, O, I, O]);
         assert_eq!(
-            cpu.a_register.output(&clock),
+            cpu.a_register.output(),
             Word::new([O, O, I, I, O, O, O, O, O, O, I, I, I, O, O, I])
         );
 
-        clock.next();
-        clock.next();
-
-        // // CLOCK: TICK
-        let (outM, writeM, addressM, pc) = cpu.run(
-            &clock,
+        // CLOCK: TICK
+        let CPUOutput {
+            out_m: outM,
+            write_m: writeM,
+            address_m: addressM,
+            pc,
+        } = run(
+            &mut cpu,
             word0,
             /* comp: A, dest: D -> D=A */
             Word::new([I, I, I, O, I, I, O, O, O, O, O, I, O, O, O, O]),
@@ -195,16 +358,18 @@ mod tests {
         assert_eq!(addressM, [O, I, I, O, O, O, O, O, O, I, I, I, O, O, I]);
         assert_eq!(pc, [O, O, O, O, O, O, O, O, O, O, O, O, O, I, I]);
         assert_eq!(
-            cpu.a_register.output(&clock),
+            cpu.a_register.output(),
             Word::new([O, O, I, I, O, O, O, O, O, O, I, I, I, O, O, I])
         );
 
-        clock.next();
-        clock.next();
-
         // CLOCK: TICK
-        let (outM, writeM, addressM, pc) = cpu.run(
-            &clock,
+        let CPUOutput {
+            out_m: outM,
+            write_m: writeM,
+            address_m: addressM,
+            pc,
+        } = run(
+            &mut cpu,
             word1,
             /* comp: D-M, dest: D -> D=D-M */
             /* D=12345, M=word1=-1 */
@@ -220,16 +385,18 @@ mod tests {
         assert_eq!(addressM, [O, I, I, O, O, O, O, O, O, I, I, I, O, O, I]);
         assert_eq!(pc, [O, O, O, O, O, O, O, O, O, O, O, O, I, O, O]);
         assert_eq!(
-            cpu.a_register.output(&clock),
+            cpu.a_register.output(),
             Word::new([O, O, I, I, O, O, O, O, O, O, I, I, I, O, O, I])
         );
 
-        clock.next();
-        clock.next();
-
         // CLOCK: TICK
-        let (outM, writeM, addressM, pc) = cpu.run(
-            &clock,
+        let CPUOutput {
+            out_m: outM,
+            write_m: writeM,
+            address_m: addressM,
+            pc,
+        } = run(
+            &mut cpu,
             word1,
             /* comp: D-M, dest: D -> D=D-M */
             /* D=12345, M=word1=-1 */
@@ -244,8 +411,25 @@ mod tests {
         assert_eq!(addressM, [O, I, I, O, O, O, O, O, O, I, I, I, O, O, I]);
         assert_eq!(pc, [O, O, O, O, O, O, O, O, O, O, O, O, O, O, O]); // reset
         assert_eq!(
-            cpu.a_register.output(&clock),
+            cpu.a_register.output(),
             Word::new([O, O, I, I, O, O, O, O, O, O, I, I, I, O, O, I])
         );
     }
+
+    #[test]
+    fn for_cpu_registers() {
+        let mut cpu = CPU::new();
+        let word0 = Word::new([O; 16]);
+
+        // @7
+        run(&mut cpu, word0, Word::from("0000000000000111"), O);
+        // D=A
+        run(&mut cpu, word0, Word::from("1110110000010000"), O);
+        // A=-1
+        run(&mut cpu, word0, Word::from("1110111010100000"), O);
+
+        assert_eq!(cpu.a_output(), Word::new([I; 16]));
+        assert_eq!(cpu.d_output(), Word::from("0000000000000111"));
+        assert_eq!(cpu.pc_output(), Word::from("0000000000000011"));
+    }
 }
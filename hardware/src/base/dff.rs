@@ -1,22 +1,32 @@
 #![allow(dead_code)]
+use std::sync::OnceLock;
+
+use crate::base::chip::{check, declare, Chip};
 use crate::base::dff::ClockState::{Tick, Tock};
 use crate::base::logic::bit;
 use crate::base::logic::bit::O;
+use crate::error::HardwareError;
+use crate::hdl::parser::PinDecl;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Copy, Clone)]
 pub enum ClockState {
     Tick,
     Tock,
 }
 
-#[derive(Debug, PartialEq)]
+// The system clock, which the Scheduler owns: Tick from the rising edge, when the chips
+// take their inputs, to the falling edge, and Tock until the next rising edge,
+// when the outputs show what was taken.
+#[derive(Debug, PartialEq, Copy, Clone)]
 pub struct Clock {
     pub state: ClockState,
 }
 
 impl Clock {
+    // at rest, e.g. between two instructions
     pub fn new() -> Self {
-        Clock { state: Tick }
+        Clock { state: Tock }
     }
     pub fn next(&mut self) {
         self.state = match self.state {
@@ -26,31 +36,105 @@ impl Clock {
     }
 }
 
-#[derive(Debug, Copy, Clone)]
+impl Default for Clock {
+    fn default() -> Self {
+        Clock::new()
+    }
+}
+
+// The master latch takes the input on the rising edge and the slave shows it
+// from the falling edge, so the output keeps its value through the cycle.
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 pub struct Dff {
-    prev: bit,
-    current: bit,
+    master: bit,
+    slave: bit,
+    // in of the Chip
+    #[serde(skip, default = "low")]
+    input: bit,
+}
+
+fn low() -> bit {
+    O
 }
 
 impl Dff {
     pub fn new() -> Self {
         Self {
-            prev: O,
-            current: O,
+            master: O,
+            slave: O,
+            input: O,
         }
     }
-    pub fn input(&mut self, clock: &Clock, a: bit) {
-        if clock.state == Tick {
-            self.prev = self.current;
-            self.current = a;
+    pub fn tick(&mut self, a: bit) {
+        self.master = a;
+    }
+    pub fn tock(&mut self) {
+        self.slave = self.master;
+    }
+    pub fn output(&self) -> bit {
+        self.slave
+    }
+    // -> master, slave
+    pub(crate) fn state(&self) -> (bit, bit) {
+        (self.master, self.slave)
+    }
+    pub(crate) fn with_state(master: bit, slave: bit) -> Self {
+        Self {
+            master,
+            slave,
+            input: O,
         }
     }
-    pub fn output(&self, clock: &Clock) -> bit {
-        match clock.state {
-            Tick => self.prev,
-            Tock => self.current,
+}
+
+impl Chip for Dff {
+    fn name(&self) -> &str {
+        "DFF"
+    }
+
+    fn inputs(&self) -> &[PinDecl] {
+        static PINS: OnceLock<Vec<PinDecl>> = OnceLock::new();
+        declare(&PINS, &[("in", 1)])
+    }
+
+    fn outputs(&self) -> &[PinDecl] {
+        static PINS: OnceLock<Vec<PinDecl>> = OnceLock::new();
+        declare(&PINS, &[("out", 1)])
+    }
+
+    fn set_input(&mut self, pin: &str, bits: &[bit]) -> Result<(), HardwareError> {
+        check(self, pin, bits)?;
+        self.input = bits[0];
+        Ok(())
+    }
+
+    fn get(&self, pin: &str) -> Option<Vec<bit>> {
+        match pin {
+            "in" => Some(vec![self.input]),
+            "out" => Some(vec![self.output()]),
+            _ => None,
         }
     }
+
+    fn eval(&mut self) {}
+
+    fn is_clocked(&self) -> bool {
+        true
+    }
+
+    fn tick(&mut self) {
+        Dff::tick(self, self.input);
+    }
+
+    fn tock(&mut self) {
+        Dff::tock(self);
+    }
+
+    fn set_register(&mut self, bits: &[bit]) -> bool {
+        Dff::tick(self, bits[0]);
+        Dff::tock(self);
+        true
+    }
 }
 
 #[cfg(test)]
@@ -61,48 +145,36 @@ mod tests {
     #[test]
     fn for_clock() {
         let mut clock = Clock::new();
-        assert_eq!(clock.state, Tick);
-        clock.next();
         assert_eq!(clock.state, Tock);
         clock.next();
         assert_eq!(clock.state, Tick);
         clock.next();
-        clock.next();
-        assert_eq!(clock.state, Tick);
+        assert_eq!(clock.state, Tock);
     }
 
     #[test]
     fn for_dff() {
-        let mut clock = Clock::new();
-        // prev: 0, current: 0
+        // master: 0, slave: 0
         let mut dff = Dff::new();
 
-        // tick: currentに入力値が入り、prev(i.e. t-1の値)が帰る
-        // prev: 0, current: 1
-        dff.input(&clock, I);
-        assert_eq!(dff.output(&clock), O);
-        clock.next();
+        // tick: masterに入力値が入り、slave(i.e. t-1の値)が帰る
+        dff.tick(I);
+        assert_eq!(dff.output(), O);
 
-        // tock: 何も起こらない。current(i.e. tの値)が帰る
-        // prev: 0, current: 1
-        dff.input(&clock, O);
-        assert_eq!(dff.output(&clock), I);
-        clock.next();
+        // tock: slaveにmasterが移る
+        dff.tock();
+        assert_eq!(dff.output(), I);
 
         // tick
-        // prev: 1, current: 0
-        dff.input(&clock, O);
-        assert_eq!(dff.output(&clock), I);
-        clock.next();
+        dff.tick(O);
+        assert_eq!(dff.output(), I);
 
         // tock
-        // prev: 1, current: 0
-        dff.input(&clock, I);
-        assert_eq!(dff.output(&clock), O);
-        clock.next();
+        dff.tock();
+        assert_eq!(dff.output(), O);
 
-        // tick
-        // prev: 1, current: 0
-        assert_eq!(dff.output(&clock), I);
+        // a tock without a tick keeps the value
+        dff.tock();
+        assert_eq!(dff.output(), O);
     }
 }
@@ -0,0 +1,352 @@
+// The formats of program images for the ROM, which are read and written as 16 bit words.
+//
+//   Hack      a word per line of 16 0/1s, as the assembler writes (.hack, .txt)
+//   BinaryBe  two bytes per word, big-endian (.bin)
+//   BinaryLe  two bytes per word, little-endian
+//   IntelHex  byte addresses, with the bytes of a word big-endian (.hex)
+//   Logisim   "v2.0 raw" and hex words, where "3*0" is three 0s
+use std::{fs, path::Path};
+
+use crate::{
+    base::{logic::Word, rom},
+    error::HardwareError,
+};
+
+pub const LOGISIM_HEADER: &str = "v2.0 raw";
+// the data bytes of a record which export writes
+const HEX_RECORD: usize = 16;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Format {
+    Hack,
+    BinaryBe,
+    BinaryLe,
+    IntelHex,
+    Logisim,
+}
+
+impl Format {
+    // by the extension, or by the header of a Logisim image
+    pub fn detect(path: &Path, bytes: &[u8]) -> Self {
+        let extension = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        match extension.as_str() {
+            "bin" => Format::BinaryBe,
+            "hex" | "ihex" => Format::IntelHex,
+            _ if bytes.starts_with(LOGISIM_HEADER.as_bytes()) => Format::Logisim,
+            _ => Format::Hack,
+        }
+    }
+}
+
+pub fn read<P: AsRef<Path>>(path: P) -> Result<Vec<u16>, HardwareError> {
+    let path = path.as_ref();
+    let bytes = fs::read(path).map_err(|e| HardwareError::new(&e.to_string()).in_file(path))?;
+    parse(&bytes, Format::detect(path, &bytes)).map_err(|e| e.in_file(path))
+}
+
+pub fn read_as<P: AsRef<Path>>(path: P, format: Format) -> Result<Vec<u16>, HardwareError> {
+    let path = path.as_ref();
+    let bytes = fs::read(path).map_err(|e| HardwareError::new(&e.to_string()).in_file(path))?;
+    parse(&bytes, format).map_err(|e| e.in_file(path))
+}
+
+pub fn write<P: AsRef<Path>>(path: P, words: &[u16], format: Format) -> Result<(), HardwareError> {
+    let path = path.as_ref();
+    fs::write(path, export(words, format))
+        .map_err(|e| HardwareError::new(&e.to_string()).in_file(path))
+}
+
+pub fn parse(bytes: &[u8], format: Format) -> Result<Vec<u16>, HardwareError> {
+    match format {
+        Format::Hack => parse_hack(text(bytes)?),
+        Format::BinaryBe => parse_binary(bytes, u16::from_be_bytes),
+        Format::BinaryLe => parse_binary(bytes, u16::from_le_bytes),
+        Format::IntelHex => parse_intel_hex(text(bytes)?),
+        Format::Logisim => parse_logisim(text(bytes)?),
+    }
+}
+
+pub fn export(words: &[u16], format: Format) -> Vec<u8> {
+    match format {
+        Format::Hack => words
+            .iter()
+            .map(|w| format!("{}\n", Word::from(*w)))
+            .collect::<String>()
+            .into_bytes(),
+        Format::BinaryBe => words.iter().flat_map(|w| w.to_be_bytes()).collect(),
+        Format::BinaryLe => words.iter().flat_map(|w| w.to_le_bytes()).collect(),
+        Format::IntelHex => export_intel_hex(words).into_bytes(),
+        Format::Logisim => export_logisim(words).into_bytes(),
+    }
+}
+
+pub fn to_words(words: &[Word]) -> Vec<u16> {
+    words.iter().map(|w| u16::from(*w)).collect()
+}
+
+fn text(bytes: &[u8]) -> Result<&str, HardwareError> {
+    std::str::from_utf8(bytes).map_err(|e| HardwareError::new(&format!("not text: {}", e)))
+}
+
+// blank lines are skipped
+pub fn parse_hack(text: &str) -> Result<Vec<u16>, HardwareError> {
+    text.lines()
+        .enumerate()
+        .map(|(i, line)| (i, line.trim()))
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(i, line)| {
+            if line.len() != 16 || !line.chars().all(|c| c == '0' || c == '1') {
+                return Err(HardwareError::at(i + 1, line, "expected 16 bits"));
+            }
+            Ok(u16::from_str_radix(line, 2).unwrap())
+        })
+        .collect()
+}
+
+fn parse_binary(bytes: &[u8], from_bytes: fn([u8; 2]) -> u16) -> Result<Vec<u16>, HardwareError> {
+    if !bytes.len().is_multiple_of(2) {
+        return Err(HardwareError::new(&format!(
+            "expected an even number of bytes but it was {}",
+            bytes.len()
+        )));
+    }
+    Ok(bytes
+        .chunks(2)
+        .map(|pair| from_bytes([pair[0], pair[1]]))
+        .collect())
+}
+
+// the records of data (00), end of file (01), extended segment address (02)
+// and extended linear address (04); the others are ignored
+fn parse_intel_hex(text: &str) -> Result<Vec<u16>, HardwareError> {
+    let mut bytes: Vec<u8> = Vec::new();
+    let mut base = 0usize;
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let error = |message: &str| HardwareError::at(i + 1, line, message);
+        let record = line
+            .strip_prefix(':')
+            .ok_or_else(|| error("expected a record starting with :"))?;
+        if !record.is_ascii() || !record.len().is_multiple_of(2) || record.len() < 10 {
+            return Err(error("expected the bytes of a record"));
+        }
+        let record = (0..record.len())
+            .step_by(2)
+            .map(|j| u8::from_str_radix(&record[j..j + 2], 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(|_| error("expected hex digits"))?;
+        if record.iter().fold(0u8, |sum, b| sum.wrapping_add(*b)) != 0 {
+            return Err(error("wrong checksum"));
+        }
+        let count = record[0] as usize;
+        if record.len() != count + 5 {
+            return Err(error(&format!("expected {} data bytes", count)));
+        }
+        let offset = u16::from_be_bytes([record[1], record[2]]) as usize;
+        let data = &record[4..4 + count];
+        match record[3] {
+            0x00 => {
+                let start = base + offset;
+                // the bytes of the words of the ROM
+                if start + count > 2 * rom::SIZE {
+                    return Err(error(&format!(
+                        "address {:#x} is past the ROM",
+                        start + count - 1
+                    )));
+                }
+                if bytes.len() < start + count {
+                    bytes.resize(start + count, 0);
+                }
+                bytes[start..start + count].copy_from_slice(data);
+            }
+            0x01 => break,
+            0x02 if count == 2 => base = (u16::from_be_bytes([data[0], data[1]]) as usize) << 4,
+            0x04 if count == 2 => base = (u16::from_be_bytes([data[0], data[1]]) as usize) << 16,
+            0x02 | 0x04 => return Err(error("expected an address of 2 bytes")),
+            _ => {}
+        }
+    }
+    if !bytes.len().is_multiple_of(2) {
+        bytes.push(0);
+    }
+    parse_binary(&bytes, u16::from_be_bytes)
+}
+
+fn export_intel_hex(words: &[u16]) -> String {
+    let bytes: Vec<u8> = words.iter().flat_map(|w| w.to_be_bytes()).collect();
+    let mut hex = String::new();
+    let mut base = 0;
+    for (i, data) in bytes.chunks(HEX_RECORD).enumerate() {
+        let address = i * HEX_RECORD;
+        if address >> 16 != base {
+            base = address >> 16;
+            hex.push_str(&hex_record(0, 0x04, &(base as u16).to_be_bytes()));
+        }
+        hex.push_str(&hex_record(address as u16, 0x00, data));
+    }
+    hex.push_str(&hex_record(0, 0x01, &[]));
+    hex
+}
+
+fn hex_record(address: u16, kind: u8, data: &[u8]) -> String {
+    let mut record = vec![data.len() as u8];
+    record.extend_from_slice(&address.to_be_bytes());
+    record.push(kind);
+    record.extend_from_slice(data);
+    let checksum = record
+        .iter()
+        .fold(0u8, |sum, b| sum.wrapping_add(*b))
+        .wrapping_neg();
+    record.push(checksum);
+    let digits: String = record.iter().map(|b| format!("{:02X}", b)).collect();
+    format!(":{}\n", digits)
+}
+
+fn parse_logisim(text: &str) -> Result<Vec<u16>, HardwareError> {
+    let mut lines = text.lines();
+    let header = lines.next().unwrap_or_default();
+    if header.trim() != LOGISIM_HEADER {
+        return Err(HardwareError::at(
+            1,
+            header,
+            &format!("expected {:?}", LOGISIM_HEADER),
+        ));
+    }
+    let mut words = Vec::new();
+    for (i, line) in lines.enumerate() {
+        let line = line.split('#').next().unwrap();
+        for value in line.split_whitespace() {
+            let error = || HardwareError::at(i + 2, value, "expected a hex word or count*word");
+            let (count, word) = match value.split_once('*') {
+                Some((count, word)) => (count.parse::<usize>().map_err(|_| error())?, word),
+                None => (1, value),
+            };
+            let word = u16::from_str_radix(word, 16).map_err(|_| error())?;
+            // checked before the run is expanded, which may be of any length
+            if count > rom::SIZE - words.len() {
+                return Err(HardwareError::at(
+                    i + 2,
+                    value,
+                    &format!("the words are past the ROM of {}", rom::SIZE),
+                ));
+            }
+            words.extend(std::iter::repeat_n(word, count));
+        }
+    }
+    Ok(words)
+}
+
+// 8 words a line, and the same words in a row as a run
+fn export_logisim(words: &[u16]) -> String {
+    let mut values: Vec<String> = Vec::new();
+    let mut i = 0;
+    while i < words.len() {
+        let count = words[i..].iter().take_while(|w| **w == words[i]).count();
+        values.push(match count {
+            1..=3 => {
+                i += 1;
+                format!("{:x}", words[i - 1])
+            }
+            _ => {
+                i += count;
+                format!("{}*{:x}", count, words[i - 1])
+            }
+        });
+    }
+    let mut logisim = format!("{}\n", LOGISIM_HEADER);
+    for line in values.chunks(8) {
+        logisim.push_str(&line.join(" "));
+        logisim.push('\n');
+    }
+    logisim
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn for_image() {
+        let words = [0x0002, 0xec10, 0x0003, 0xe090, 0, 0, 0, 0, 0x0000, 0xe308];
+        for format in [
+            Format::Hack,
+            Format::BinaryBe,
+            Format::BinaryLe,
+            Format::IntelHex,
+            Format::Logisim,
+        ] {
+            assert_eq!(
+                parse(&export(&words, format), format),
+                Ok(words.to_vec()),
+                "{:?}",
+                format
+            );
+        }
+        assert_eq!(export(&[0xec10], Format::BinaryBe), vec![0xec, 0x10]);
+        assert_eq!(export(&[0xec10], Format::BinaryLe), vec![0x10, 0xec]);
+        assert_eq!(
+            export(&words[..2], Format::IntelHex),
+            b":040000000002EC10FE\n:00000001FF\n".to_vec()
+        );
+        assert_eq!(
+            String::from_utf8(export(&words, Format::Logisim)).unwrap(),
+            "v2.0 raw\n2 ec10 3 e090 5*0 e308\n"
+        );
+
+        // the whole ROM
+        let rom: Vec<u16> = (0..0x8000).collect();
+        assert_eq!(
+            parse(&export(&rom, Format::IntelHex), Format::IntelHex),
+            Ok(rom)
+        );
+
+        assert_eq!(
+            parse(b"0000000000000010\n\n00000000000001x1\n", Format::Hack),
+            Err(HardwareError::at(3, "00000000000001x1", "expected 16 bits"))
+        );
+        assert!(parse(b"000000000000001", Format::Hack).is_err());
+        assert!(parse(&[0, 1, 2], Format::BinaryBe).is_err());
+        assert!(parse(b":040000000002EC10FF\n", Format::IntelHex).is_err());
+        assert!(parse(
+            ":\u{e9}\u{e9}\u{e9}\u{e9}\u{e9}\n".as_bytes(),
+            Format::IntelHex
+        )
+        .is_err());
+        // data at 0x10000, after the base 0x0001 of a type 04 record
+        assert_eq!(
+            parse(b":020000040001F9\n:0200000000EC12\n", Format::IntelHex),
+            Err(HardwareError::at(
+                2,
+                ":0200000000EC12",
+                "address 0x10001 is past the ROM"
+            ))
+        );
+        assert!(parse(b"2 ec10", Format::Logisim).is_err());
+        assert_eq!(
+            parse(b"v2.0 raw\n1 99999999999*0\n", Format::Logisim),
+            Err(HardwareError::at(
+                2,
+                "99999999999*0",
+                "the words are past the ROM of 32768"
+            ))
+        );
+    }
+
+    #[test]
+    fn for_image_detect() {
+        assert_eq!(Format::detect(Path::new("Add.hack"), b""), Format::Hack);
+        assert_eq!(Format::detect(Path::new("Add.bin"), b""), Format::BinaryBe);
+        assert_eq!(Format::detect(Path::new("Add.HEX"), b""), Format::IntelHex);
+        assert_eq!(
+            Format::detect(Path::new("Add"), b"v2.0 raw\n2 ec10\n"),
+            Format::Logisim
+        );
+        assert_eq!(read("src/program/add.txt").unwrap().len(), 6);
+    }
+}
@@ -1,36 +1,104 @@
-use super::logic::{bit::O, Word};
-use serde_json::Value;
-use std::sync::mpsc::Receiver;
+use super::chip::{check, declare, Chip};
+use super::logic::{bit, bit::O, Word};
+use crate::{
+    display::Link,
+    error::HardwareError,
+    hdl::{from_word, parser::PinDecl},
+    protocol::{FromDisplay, ToDisplay},
+};
+use serde::{Deserialize, Serialize};
+use std::sync::{mpsc::Receiver, OnceLock};
 
+// the Hack codes of the keys without a character, by the names of browsers
+const KEYS: &[(&str, u16)] = &[
+    ("Enter", 128),
+    ("Backspace", 129),
+    ("ArrowLeft", 130),
+    ("ArrowUp", 131),
+    ("ArrowRight", 132),
+    ("ArrowDown", 133),
+    ("Home", 134),
+    ("End", 135),
+    ("PageUp", 136),
+    ("PageDown", 137),
+    ("Insert", 138),
+    ("Delete", 139),
+    ("Escape", 140),
+];
+
+// The Hack code of a key: a character like a, a name like ArrowLeft, F1..F12, or Space.
+pub fn code(key: &str) -> Option<u16> {
+    let mut chars = key.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return if c.is_ascii() && !c.is_ascii_control() {
+            Some(c as u16)
+        } else {
+            None
+        };
+    }
+    if key == "Space" {
+        return Some(32);
+    }
+    if let Some(n) = key.strip_prefix('F').and_then(|n| n.parse::<u16>().ok()) {
+        return if (1..=12).contains(&n) {
+            Some(140 + n)
+        } else {
+            None
+        };
+    }
+    KEYS.iter()
+        .find(|(name, _)| *name == key)
+        .map(|(_, code)| *code)
+}
+
+#[derive(Serialize, Deserialize)]
 pub struct Keyboard {
+    #[serde(skip)]
     receiver: Option<Receiver<String>>,
     // 本来はRegisterを使って実装する.
     code: Option<Word>,
+    // for the other messages of the display
+    #[serde(skip)]
+    link: Link,
 }
 
 impl Keyboard {
     pub fn new(receiver: Option<Receiver<String>>) -> Self {
+        Keyboard::with_link(receiver, Link::default())
+    }
+
+    // the link of the screen, which answers the display
+    pub fn with_link(receiver: Option<Receiver<String>>, link: Link) -> Self {
         Self {
             receiver,
             code: None,
+            link,
         }
     }
 
     pub fn input(&mut self) {
         if let Some(ref rx) = self.receiver {
             if let Ok(str) = rx.try_recv() {
-                let v: Value = serde_json::from_str(&str).unwrap();
-                if v["down"].as_bool().unwrap() == false {
-                    self.code = None;
-                } else {
-                    let num = v["key"].as_i64().unwrap();
-                    let str: &str = &format!("{:016b}", num);
-                    self.code = Some(Word::from(str));
+                match FromDisplay::parse(&str) {
+                    Ok(FromDisplay::Key { down: false, .. }) => self.code = None,
+                    Ok(FromDisplay::Key { key, down: true }) => self.code = Some(Word::from(key)),
+                    Ok(message) => self.link.receive(message),
+                    Err(e) => self.link.reply(ToDisplay::Error { message: e.0 }),
                 }
             }
         }
     }
 
+    // press a key without the channel
+    pub fn set(&mut self, code: Word) {
+        self.code = Some(code);
+    }
+
+    // take the key of a snapshot
+    pub(crate) fn restore(&mut self, state: Keyboard) {
+        self.code = state.code;
+    }
+
     pub fn output(&self) -> Word {
         match self.code {
             None => Word::new([O; 16]),
@@ -38,3 +106,32 @@ impl Keyboard {
         }
     }
 }
+
+// the key pressed, without inputs
+impl Chip for Keyboard {
+    fn name(&self) -> &str {
+        "Keyboard"
+    }
+
+    fn inputs(&self) -> &[PinDecl] {
+        &[]
+    }
+
+    fn outputs(&self) -> &[PinDecl] {
+        static PINS: OnceLock<Vec<PinDecl>> = OnceLock::new();
+        declare(&PINS, &[("out", 16)])
+    }
+
+    fn set_input(&mut self, pin: &str, bits: &[bit]) -> Result<(), HardwareError> {
+        check(self, pin, bits)
+    }
+
+    fn get(&self, pin: &str) -> Option<Vec<bit>> {
+        match pin {
+            "out" => Some(from_word(self.output())),
+            _ => None,
+        }
+    }
+
+    fn eval(&mut self) {}
+}
@@ -1,14 +1,23 @@
 #![allow(dead_code, non_camel_case_types)]
 
-use crate::base::logic::bit::{I, O};
+use crate::{
+    base::{
+        arithmetic::{add16, inc16},
+        logic::bit::{I, O},
+    },
+    profile,
+};
 use core::panic;
+use serde::{Deserialize, Serialize};
 use std::{
+    convert::TryFrom,
     fmt::{self, Formatter},
-    ops::Index,
+    ops::{Add, BitAnd, BitOr, Index, Neg, Not},
+    str::FromStr,
     write,
 };
 
-#[derive(Debug, PartialEq, Copy, Clone)]
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
 pub enum bit {
     O,
     I,
@@ -23,12 +32,14 @@ impl fmt::Display for bit {
     }
 }
 
-#[derive(Debug, PartialEq, Copy, Clone)]
+// serialized as u16
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+#[serde(into = "u16", from = "u16")]
 pub struct Word([bit; 16]);
 
+// The infallible path for 16 binary digits known to be right, e.g. in tests.
+// It panics on anything else, so text from a user goes through FromStr.
 impl From<&str> for Word {
-    // TODO from(number)
-
     fn from(str: &str) -> Self {
         let char_bits: Vec<char> = str.chars().collect();
         if char_bits.len() != 16 {
@@ -67,6 +78,175 @@ impl Word {
     pub fn new(bits: [bit; 16]) -> Self {
         Self(bits)
     }
+
+    // e.g. format!("{}", word.signed()) is -1 for 1111111111111111
+    pub fn signed(self) -> Signed {
+        Signed(self)
+    }
+
+    // and this is 65535
+    pub fn unsigned(self) -> Unsigned {
+        Unsigned(self)
+    }
+}
+
+/// A `Word` displayed as a decimal of two's complement.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Signed(pub Word);
+
+/// A `Word` displayed as a decimal of 0..=65535.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Unsigned(pub Word);
+
+impl fmt::Display for Signed {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&i16::from(self.0), f)
+    }
+}
+
+impl fmt::Display for Unsigned {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&u16::from(self.0), f)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct WordError(pub String);
+
+impl fmt::Display for WordError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl From<u16> for Word {
+    fn from(number: u16) -> Self {
+        let mut bits = [O; 16];
+        for (i, bit) in bits.iter_mut().enumerate() {
+            if number & (1 << (15 - i)) != 0 {
+                *bit = I;
+            }
+        }
+        Word::new(bits)
+    }
+}
+
+// two's complement
+impl From<i16> for Word {
+    fn from(number: i16) -> Self {
+        Word::from(number as u16)
+    }
+}
+
+impl From<Word> for u16 {
+    fn from(word: Word) -> Self {
+        word.0
+            .iter()
+            .fold(0, |sum, bit| (sum << 1) | if *bit == I { 1 } else { 0 })
+    }
+}
+
+impl From<Word> for i16 {
+    fn from(word: Word) -> Self {
+        u16::from(word) as i16
+    }
+}
+
+// -32768..=65535, i.e. both of signed and unsigned numbers
+impl TryFrom<i32> for Word {
+    type Error = WordError;
+
+    fn try_from(number: i32) -> Result<Self, Self::Error> {
+        if number < i16::MIN as i32 || number > u16::MAX as i32 {
+            return Err(WordError(format!("{} is out of range", number)));
+        }
+        Ok(Word::from(number as u16))
+    }
+}
+
+// 16 binary digits as From<&str>, 0b.., 0x.. or decimal like -1
+impl FromStr for Word {
+    type Err = WordError;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        let invalid = || WordError(format!("couldn't parse {:?}", str));
+        if str.len() == 16 && str.chars().all(|c| c == '0' || c == '1') {
+            return Ok(Word::from(str));
+        }
+        let (negative, digits) = match str.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, str),
+        };
+        // a single sign, e.g. not --5
+        if digits.starts_with(['-', '+']) {
+            return Err(invalid());
+        }
+        let number = if let Some(hex) = digits.strip_prefix("0x") {
+            i32::from_str_radix(hex, 16)
+        } else if let Some(binary) = digits.strip_prefix("0b") {
+            i32::from_str_radix(binary, 2)
+        } else {
+            digits.parse::<i32>()
+        }
+        .map_err(|_| invalid())?;
+        Word::try_from(if negative { -number } else { number })
+    }
+}
+
+// {:b}, {:x} and {:X} are unsigned with the flags of the formatter, e.g. {:016b} or {:#06x}
+impl fmt::Binary for Word {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        fmt::Binary::fmt(&u16::from(*self), f)
+    }
+}
+
+impl fmt::LowerHex for Word {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        fmt::LowerHex::fmt(&u16::from(*self), f)
+    }
+}
+
+impl fmt::UpperHex for Word {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        fmt::UpperHex::fmt(&u16::from(*self), f)
+    }
+}
+
+// operators are evaluated through the gates
+impl Not for Word {
+    type Output = Word;
+    fn not(self) -> Self::Output {
+        not16(self)
+    }
+}
+
+impl BitAnd for Word {
+    type Output = Word;
+    fn bitand(self, rhs: Self) -> Self::Output {
+        and16(self, rhs)
+    }
+}
+
+impl BitOr for Word {
+    type Output = Word;
+    fn bitor(self, rhs: Self) -> Self::Output {
+        or16(self, rhs)
+    }
+}
+
+// wraps around as the ALU does
+impl Add for Word {
+    type Output = Word;
+    fn add(self, rhs: Self) -> Self::Output {
+        add16(self, rhs)
+    }
+}
+
+impl Neg for Word {
+    type Output = Word;
+    fn neg(self) -> Self::Output {
+        inc16(not16(self))
+    }
 }
 
 impl Index<usize> for Word {
@@ -80,7 +260,7 @@ impl Index<usize> for Word {
 }
 
 pub fn nand(a: bit, b: bit) -> bit {
-    match a {
+    let out = match a {
         O => match b {
             O => I,
             I => I,
@@ -89,7 +269,10 @@ pub fn nand(a: bit, b: bit) -> bit {
             O => I,
             I => O,
         },
-    }
+    };
+    #[cfg(feature = "profile")]
+    let out = profile::nand(a, b, out);
+    out
 }
 
 pub fn not(a: bit) -> bit {
@@ -117,6 +300,7 @@ pub fn dmux(input: bit, sel: bit) -> [bit; 2] {
 }
 
 pub fn not16(bits: Word) -> Word {
+    let _profile = profile::chip("not16");
     Word([
         not(bits[0]),
         not(bits[1]),
@@ -138,6 +322,7 @@ pub fn not16(bits: Word) -> Word {
 }
 
 pub fn and16(a: Word, b: Word) -> Word {
+    let _profile = profile::chip("and16");
     Word([
         and(a[0], b[0]),
         and(a[1], b[1]),
@@ -159,6 +344,7 @@ pub fn and16(a: Word, b: Word) -> Word {
 }
 
 pub fn or16(a: Word, b: Word) -> Word {
+    let _profile = profile::chip("or16");
     Word([
         or(a[0], b[0]),
         or(a[1], b[1]),
@@ -180,6 +366,7 @@ pub fn or16(a: Word, b: Word) -> Word {
 }
 
 pub fn mux16(a: Word, b: Word, sel: bit) -> Word {
+    let _profile = profile::chip("mux16");
     Word([
         mux(a[0], b[0], sel),
         mux(a[1], b[1], sel),
@@ -201,6 +388,7 @@ pub fn mux16(a: Word, b: Word, sel: bit) -> Word {
 }
 
 pub fn or8way(a: [bit; 8]) -> bit {
+    let _profile = profile::chip("or8way");
     or(
         or(or(a[0], a[7]), or(a[1], a[6])),
         or(or(a[2], a[5]), or(a[3], a[4])),
@@ -222,6 +410,7 @@ pub fn mux3(a: bit, b: bit, c: bit, d: bit, e: bit, f: bit, g: bit, h: bit, sel:
 }
 
 pub fn mux4way16(a: Word, b: Word, c: Word, d: Word, sel: [bit; 2]) -> Word {
+    let _profile = profile::chip("mux4way16");
     Word::new([
         mux2(a[0], b[0], c[0], d[0], sel),
         mux2(a[1], b[1], c[1], d[1], sel),
@@ -253,6 +442,7 @@ pub fn mux8way16(
     h: Word,
     sel: [bit; 3],
 ) -> Word {
+    let _profile = profile::chip("mux8way16");
     Word::new([
         mux3(a[0], b[0], c[0], d[0], e[0], f[0], g[0], h[0], sel),
         mux3(a[1], b[1], c[1], d[1], e[1], f[1], g[1], h[1], sel),
@@ -274,6 +464,7 @@ pub fn mux8way16(
 }
 
 pub fn dmux4way(input: bit, sel: [bit; 2]) -> [bit; 4] {
+    let _profile = profile::chip("dmux4way");
     [
         and(and(not(xor(sel[0], O)), not(xor(sel[1], O))), input),
         and(and(not(xor(sel[0], O)), not(xor(sel[1], I))), input),
@@ -283,6 +474,7 @@ pub fn dmux4way(input: bit, sel: [bit; 2]) -> [bit; 4] {
 }
 
 pub fn dmux8way(input: bit, sel: [bit; 3]) -> [bit; 8] {
+    let _profile = profile::chip("dmux8way");
     let nxor = |a: bit, b: bit| -> bit { not(xor(a, b)) };
     [
         and(
@@ -333,6 +525,8 @@ mod tests {
     use crate::base::logic::mux3;
     use crate::base::logic::mux4way16;
     use crate::base::logic::mux8way16;
+    use crate::base::logic::WordError;
+    use std::convert::TryFrom;
 
     #[test]
     fn for_nand() {
@@ -600,4 +794,51 @@ mod tests {
     fn for_word_from3() {
         Word::from("000000000000000a");
     }
+
+    #[test]
+    fn for_word_numeric() {
+        assert_eq!(Word::from(5u16), Word::from("0000000000000101"));
+        assert_eq!(Word::from(-1i16), Word::from("1111111111111111"));
+        assert_eq!(u16::from(Word::from("1000000000000001")), 32769);
+        assert_eq!(i16::from(Word::from("1000000000000001")), -32767);
+        assert_eq!(Word::try_from(65535), Ok(Word::from(-1i16)));
+        assert_eq!(
+            Word::try_from(65536),
+            Err(WordError("65536 is out of range".to_string()))
+        );
+
+        assert_eq!("-2".parse(), Ok(Word::from(-2i16)));
+        assert_eq!("0x7fff".parse(), Ok(Word::from(32767u16)));
+        assert_eq!("0b101".parse(), Ok(Word::from(5u16)));
+        assert_eq!("0000000000000011".parse(), Ok(Word::from(3u16)));
+        assert!("0x".parse::<Word>().is_err());
+        assert!("-32769".parse::<Word>().is_err());
+
+        assert!("--5".parse::<Word>().is_err());
+        assert!("-+5".parse::<Word>().is_err());
+
+        let word = Word::from(0xbeefu16);
+        assert_eq!(format!("{:x} {:X}", word, word), "beef BEEF");
+        assert_eq!(format!("{:b}", word), "1011111011101111");
+        assert_eq!(
+            format!("{:016b} {:#06x}", Word::from(5u16), word),
+            "0000000000000101 0xbeef"
+        );
+        assert_eq!(word.signed().to_string(), "-16657");
+        assert_eq!(word.unsigned().to_string(), "48879");
+        assert_eq!(format!("{:>6}", Word::from(-1i16).signed()), "    -1");
+    }
+
+    #[test]
+    fn for_word_operators() {
+        let a = Word::from(0b1100u16);
+        let b = Word::from(0b1010u16);
+        assert_eq!(a & b, Word::from(0b1000u16));
+        assert_eq!(a | b, Word::from(0b1110u16));
+        assert_eq!(!a, Word::from(!0b1100u16));
+        assert_eq!(a + b, Word::from(22u16));
+        assert_eq!(Word::from(-1i16) + Word::from(1u16), Word::from(0u16));
+        assert_eq!(-a, Word::from(-12i16));
+        assert_eq!(-Word::from(0u16), Word::from(0u16));
+    }
 }
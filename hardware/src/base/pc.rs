@@ -1,51 +1,119 @@
 #![allow(dead_code)]
+use std::sync::OnceLock;
+
 use crate::base::arithmetic::inc16;
+use crate::base::chip::{check, declare, Chip};
 use crate::base::logic::bit::O;
+use crate::base::{logic::bit::I, logic::mux16};
 use crate::base::{
-    dff::Clock,
-    dff::ClockState::{Tick, Tock},
     logic::{bit, Word},
     register::Register,
 };
-use crate::base::{logic::bit::I, logic::mux16};
+use crate::error::HardwareError;
+use crate::hdl::{from_word, parser::PinDecl, to_word};
+use crate::profile;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct PC {
     register: Register,
+    // in, and inc, load and reset of the Chip
+    #[serde(skip, default = "PC::pins")]
+    pins: (Word, [bit; 3]),
 }
 
 impl PC {
     pub fn new() -> Self {
         Self {
             register: Register::new(),
+            pins: PC::pins(),
         }
     }
-    pub fn input(&mut self, clock_t: &Clock, input: Word, incr: bit, load: bit, reset: bit) {
-        let clock_t_1 = match clock_t.state {
-            Tick => {
-                let mut c = Clock::new();
-                c.next();
-                c
-            }
-            Tock => Clock::new(),
-        };
-        let out_t_1 = self.output(&clock_t_1);
+    fn pins() -> (Word, [bit; 3]) {
+        (Word::new([O; 16]), [O; 3])
+    }
+    // out(t+1) of the inputs
+    pub fn next(self, input: Word, incr: bit, load: bit, reset: bit) -> Word {
+        let out_t = self.output();
         let zero = Word::new([O; 16]);
-
-        let input_tmp = mux16(
-            mux16(mux16(out_t_1, inc16(out_t_1), incr), input, load),
+        mux16(
+            mux16(mux16(out_t, inc16(out_t), incr), input, load),
             zero,
             reset,
-        );
-        self.register.input(clock_t, input_tmp, I);
+        )
+    }
+    // the rising edge
+    pub fn tick(&mut self, input: Word, incr: bit, load: bit, reset: bit) {
+        let _profile = profile::chip("PC.input");
+        let next = self.next(input, incr, load, reset);
+        self.register.tick(next, I);
+    }
+    // the falling edge
+    pub fn tock(&mut self) {
+        self.register.tock();
+    }
+    pub fn output(self) -> Word {
+        self.register.output()
+    }
+}
+
+// the single bit inputs, in the order of input
+const CONTROL: [&str; 3] = ["inc", "load", "reset"];
+
+impl Chip for PC {
+    fn name(&self) -> &str {
+        "PC"
+    }
+
+    fn inputs(&self) -> &[PinDecl] {
+        static PINS: OnceLock<Vec<PinDecl>> = OnceLock::new();
+        declare(&PINS, &[("in", 16), ("load", 1), ("inc", 1), ("reset", 1)])
     }
-    pub fn output(self, clock_t: &Clock) -> Word {
-        self.register.output(clock_t)
+
+    fn outputs(&self) -> &[PinDecl] {
+        static PINS: OnceLock<Vec<PinDecl>> = OnceLock::new();
+        declare(&PINS, &[("out", 16)])
+    }
+
+    fn set_input(&mut self, pin: &str, bits: &[bit]) -> Result<(), HardwareError> {
+        check(self, pin, bits)?;
+        match CONTROL.iter().position(|c| *c == pin) {
+            Some(i) => self.pins.1[i] = bits[0],
+            None => self.pins.0 = to_word(bits),
+        }
+        Ok(())
+    }
+
+    fn get(&self, pin: &str) -> Option<Vec<bit>> {
+        match pin {
+            "in" => Some(from_word(self.pins.0)),
+            "out" => Some(from_word(self.output())),
+            _ => CONTROL
+                .iter()
+                .position(|c| *c == pin)
+                .map(|i| vec![self.pins.1[i]]),
+        }
+    }
+
+    fn eval(&mut self) {}
+
+    fn is_clocked(&self) -> bool {
+        true
+    }
+
+    fn tick(&mut self) {
+        let (input, [inc, load, reset]) = self.pins;
+        PC::tick(self, input, inc, load, reset);
+    }
+
+    fn tock(&mut self) {
+        PC::tock(self);
     }
 
-    pub fn run(&mut self, clock_t: &Clock, input: Word, incr: bit, load: bit, reset: bit) -> Word {
-        self.input(clock_t, input, incr, load, reset);
-        self.output(clock_t)
+    fn set_register(&mut self, bits: &[bit]) -> bool {
+        PC::tick(self, to_word(bits), O, I, O);
+        PC::tock(self);
+        true
     }
 }
 
@@ -56,58 +124,50 @@ mod tests {
     #[test]
     fn for_pc() {
         let mut pc = PC::new();
-        let mut clock = Clock::new();
         let zero = Word::new([O; 16]);
 
-        pc.input(&clock, zero, O, I, O);
-        assert_eq!(pc.output(&clock), zero);
+        pc.tick(zero, O, I, O);
+        assert_eq!(pc.output(), zero);
 
-        clock.next();
-        clock.next();
-        pc.input(&clock, zero, I, O, O);
-        assert_eq!(pc.output(&clock), zero);
+        pc.tock();
+        pc.tick(zero, I, O, O);
+        assert_eq!(pc.output(), zero);
 
-        clock.next();
-        clock.next();
-        pc.input(&clock, zero, I, O, O);
+        pc.tock();
+        pc.tick(zero, I, O, O);
         assert_eq!(
-            pc.output(&clock),
+            pc.output(),
             Word::new([O, O, O, O, O, O, O, O, O, O, O, O, O, O, O, I])
         );
 
-        clock.next();
-        clock.next();
-        pc.input(&clock, zero, I, O, O);
+        pc.tock();
+        pc.tick(zero, I, O, O);
         assert_eq!(
-            pc.output(&clock),
+            pc.output(),
             Word::new([O, O, O, O, O, O, O, O, O, O, O, O, O, O, I, O])
         );
 
-        clock.next();
-        clock.next();
-        pc.input(&clock, zero, O, O, I);
+        pc.tock();
+        pc.tick(zero, O, O, I);
         assert_eq!(
-            pc.output(&clock),
+            pc.output(),
             Word::new([O, O, O, O, O, O, O, O, O, O, O, O, O, O, I, I])
         );
 
-        clock.next();
-        clock.next();
+        pc.tock();
         let word = Word::new([O, I, O, I, O, I, O, I, O, I, O, I, O, I, O, I]);
-        pc.input(&clock, word, O, I, O);
+        pc.tick(word, O, I, O);
         assert_eq!(
-            pc.output(&clock),
+            pc.output(),
             Word::new([O, O, O, O, O, O, O, O, O, O, O, O, O, O, O, O])
         );
 
-        clock.next();
-        clock.next();
-        pc.input(&clock, zero, I, O, O);
-        assert_eq!(pc.output(&clock), word);
+        pc.tock();
+        pc.tick(zero, I, O, O);
+        assert_eq!(pc.output(), word);
 
-        clock.next();
-        clock.next();
-        pc.input(&clock, zero, I, O, O);
-        assert_eq!(pc.output(&clock), inc16(word));
+        pc.tock();
+        pc.tick(zero, I, O, O);
+        assert_eq!(pc.output(), inc16(word));
     }
 }
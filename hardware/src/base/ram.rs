@@ -1,240 +1,458 @@
 #![allow(dead_code)]
-use crate::base::logic::dmux4way;
-use crate::base::logic::dmux8way;
+use std::sync::OnceLock;
+
+use crate::base::bus::Bus;
+use crate::base::chip::{check, declare, Chip};
 use crate::base::logic::Word;
-use crate::base::logic::{bit, mux8way16};
+use crate::base::logic::{bit, bit::O, dmux, dmux8way, mux16, mux8way16};
 use crate::base::register::Register;
-use crate::base::{dff::Clock, logic::mux4way16};
-
-#[derive(Debug, Clone, Copy)]
+use crate::error::HardwareError;
+use crate::hdl::{builtin::pins, from_word, msb_first, parser::PinDecl, to_word};
+use crate::profile;
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+
+// the chip which every Ram is built from
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct RAM8 {
     registers: [Register; 8],
+    #[serde(skip)]
+    pins: Pins<3>,
 }
 
 impl RAM8 {
     pub fn new() -> Self {
         Self {
             registers: [Register::new(); 8],
+            pins: Pins::default(),
         }
     }
-    pub fn input(&mut self, clock_t: &Clock, input: Word, address: [bit; 3], load: bit) {
+    // in the order of the addresses
+    pub(crate) fn registers(&self) -> impl Iterator<Item = &Register> + '_ {
+        self.registers.iter()
+    }
+    pub(crate) fn registers_mut(&mut self) -> impl Iterator<Item = &mut Register> + '_ {
+        self.registers.iter_mut()
+    }
+    // the rising edge
+    pub fn tick(&mut self, input: Word, address: [bit; 3], load: bit) {
+        let _profile = profile::chip("RAM8.input");
         let load8 = dmux8way(load, address);
-        self.registers[0].input(clock_t, input, load8[0]);
-        self.registers[1].input(clock_t, input, load8[1]);
-        self.registers[2].input(clock_t, input, load8[2]);
-        self.registers[3].input(clock_t, input, load8[3]);
-        self.registers[4].input(clock_t, input, load8[4]);
-        self.registers[5].input(clock_t, input, load8[5]);
-        self.registers[6].input(clock_t, input, load8[6]);
-        self.registers[7].input(clock_t, input, load8[7]);
-    }
-    pub fn output(self, clock_t: &Clock, address: [bit; 3]) -> Word {
+        self.registers[0].tick(input, load8[0]);
+        self.registers[1].tick(input, load8[1]);
+        self.registers[2].tick(input, load8[2]);
+        self.registers[3].tick(input, load8[3]);
+        self.registers[4].tick(input, load8[4]);
+        self.registers[5].tick(input, load8[5]);
+        self.registers[6].tick(input, load8[6]);
+        self.registers[7].tick(input, load8[7]);
+    }
+    // the falling edge
+    pub fn tock(&mut self) {
+        for register in self.registers.iter_mut() {
+            register.tock();
+        }
+    }
+    pub fn output(&self, address: [bit; 3]) -> Word {
+        let _profile = profile::chip("RAM8.output");
         mux8way16(
-            self.registers[0].output(&clock_t),
-            self.registers[1].output(&clock_t),
-            self.registers[2].output(&clock_t),
-            self.registers[3].output(&clock_t),
-            self.registers[4].output(&clock_t),
-            self.registers[5].output(&clock_t),
-            self.registers[6].output(&clock_t),
-            self.registers[7].output(&clock_t),
+            self.registers[0].output(),
+            self.registers[1].output(),
+            self.registers[2].output(),
+            self.registers[3].output(),
+            self.registers[4].output(),
+            self.registers[5].output(),
+            self.registers[6].output(),
+            self.registers[7].output(),
             address,
         )
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-pub struct RAM64 {
-    rams: [RAM8; 8],
+impl Addressed<3> for RAM8 {
+    fn new() -> Self {
+        RAM8::new()
+    }
+    fn tick(&mut self, input: Word, address: Bus<3>, load: bit) {
+        RAM8::tick(self, input, address.bits(), load)
+    }
+    fn tock(&mut self) {
+        RAM8::tock(self)
+    }
+    fn output(&self, address: Bus<3>) -> Word {
+        RAM8::output(self, address.bits())
+    }
+    fn registers(&self) -> Box<dyn Iterator<Item = &Register> + '_> {
+        Box::new(RAM8::registers(self))
+    }
+    fn registers_mut(&mut self) -> Box<dyn Iterator<Item = &mut Register> + '_> {
+        Box::new(RAM8::registers_mut(self))
+    }
 }
 
-impl RAM64 {
-    pub fn new() -> Self {
+pub type RAM64 = Ram<6>;
+pub type RAM512 = Ram<9>;
+pub type RAM4K = Ram<12>;
+pub type RAM16K = Ram<14>;
+
+/// 2^N registers, built as the chips of the course: RAM8s at the bottom,
+/// and above them 8 (or 4, or 2) RAMs selected by the high bits of the address.
+/// RAM16K is 4 RAM4Ks, each 8 RAM512s, and so on. Each level is a Bank of its own type,
+/// so the addresses are split with widths checked when they are compiled.
+pub type Ram<const N: usize> = <Bits<N> as Layout>::Ram;
+
+/// A chip of 2^N registers, selected by an address of N wires.
+pub trait Addressed<const N: usize>: Sized + 'static {
+    fn new() -> Self;
+    // the rising edge, when the register at the address takes the input if load
+    fn tick(&mut self, input: Word, address: Bus<N>, load: bit);
+    // the falling edge, from which the registers show what they took
+    fn tock(&mut self);
+    fn output(&self, address: Bus<N>) -> Word;
+    // in the order of the addresses
+    fn registers(&self) -> Box<dyn Iterator<Item = &Register> + '_>;
+    fn registers_mut(&mut self) -> Box<dyn Iterator<Item = &mut Register> + '_>;
+}
+
+pub struct Bits<const N: usize>;
+
+// the chips of Ram<N>
+pub trait Layout {
+    type Ram;
+}
+
+// The high bits select one of 8 RAMs, or of 4 or 2 for the bits left over,
+// e.g. RAM16K takes 2 bits and RAM4K the other 12.
+macro_rules! layout {
+    ($($n:literal => $ram:ty,)*) => {
+        $(impl Layout for Bits<$n> {
+            type Ram = $ram;
+        })*
+    };
+}
+
+layout! {
+    3 => RAM8,
+    4 => Bank<Ram<3>, 1, 3, 4>,
+    5 => Bank<Ram<3>, 2, 3, 5>,
+    6 => Bank<Ram<3>, 3, 3, 6>,
+    7 => Bank<Ram<6>, 1, 6, 7>,
+    8 => Bank<Ram<6>, 2, 6, 8>,
+    9 => Bank<Ram<6>, 3, 6, 9>,
+    10 => Bank<Ram<9>, 1, 9, 10>,
+    11 => Bank<Ram<9>, 2, 9, 11>,
+    12 => Bank<Ram<9>, 3, 9, 12>,
+    13 => Bank<Ram<12>, 1, 12, 13>,
+    14 => Bank<Ram<12>, 2, 12, 14>,
+    15 => Bank<Ram<12>, 3, 12, 15>,
+    16 => Bank<Ram<15>, 1, 15, 16>,
+}
+
+/// 2^SEL RAMs of 2^R registers, selected by the high SEL bits of the N of the address.
+/// The RAMs are on the heap, so that the big ones fit in any stack and are read without copies.
+#[derive(Debug, Clone)]
+pub struct Bank<T, const SEL: usize, const R: usize, const N: usize> {
+    rams: Vec<T>,
+    pins: Pins<N>,
+}
+
+impl<T: Addressed<R>, const SEL: usize, const R: usize, const N: usize> Addressed<N>
+    for Bank<T, SEL, R, N>
+{
+    fn new() -> Self {
+        const { assert!(SEL >= 1 && SEL <= 3) };
         Self {
-            rams: [RAM8::new(); 8],
+            rams: (0..1 << SEL).map(|_| T::new()).collect(),
+            pins: Pins::default(),
         }
     }
-    pub fn input(&mut self, clock_t: &Clock, input: Word, address: [bit; 6], load: bit) {
-        let load_bit = dmux8way(load, [address[0], address[1], address[2]]);
-        let register_address = [address[3], address[4], address[5]];
-        self.rams[0].input(clock_t, input, register_address, load_bit[0]);
-        self.rams[1].input(clock_t, input, register_address, load_bit[1]);
-        self.rams[2].input(clock_t, input, register_address, load_bit[2]);
-        self.rams[3].input(clock_t, input, register_address, load_bit[3]);
-        self.rams[4].input(clock_t, input, register_address, load_bit[4]);
-        self.rams[5].input(clock_t, input, register_address, load_bit[5]);
-        self.rams[6].input(clock_t, input, register_address, load_bit[6]);
-        self.rams[7].input(clock_t, input, register_address, load_bit[7]);
-    }
-    pub fn output(self, clock_t: &Clock, address: [bit; 6]) -> Word {
-        let register_address = [address[3], address[4], address[5]];
-        mux8way16(
-            self.rams[0].output(clock_t, register_address),
-            self.rams[1].output(clock_t, register_address),
-            self.rams[2].output(clock_t, register_address),
-            self.rams[3].output(clock_t, register_address),
-            self.rams[4].output(clock_t, register_address),
-            self.rams[5].output(clock_t, register_address),
-            self.rams[6].output(clock_t, register_address),
-            self.rams[7].output(clock_t, register_address),
-            [address[0], address[1], address[2]],
-        )
+
+    fn tick(&mut self, input: Word, address: Bus<N>, load: bit) {
+        let _profile = profile::chip(chip(N, "input"));
+        let (select, address) = address.split::<SEL, R>();
+        // as DMux8Way is built from DMux, a select bit at a time
+        let mut loads = [O; 8];
+        loads[0] = load;
+        for (level, sel) in select.iter().enumerate() {
+            for i in (0..1 << level).rev() {
+                [loads[2 * i], loads[2 * i + 1]] = dmux(loads[i], *sel);
+            }
+        }
+        for (ram, load) in self.rams.iter_mut().zip(loads) {
+            ram.tick(input, address, load);
+        }
     }
-}
 
-#[derive(Debug, Clone, Copy)]
-pub struct RAM512 {
-    rams: [RAM64; 8],
+    fn tock(&mut self) {
+        for ram in self.rams.iter_mut() {
+            ram.tock();
+        }
+    }
+
+    fn output(&self, address: Bus<N>) -> Word {
+        let _profile = profile::chip(chip(N, "output"));
+        let (select, address) = address.split::<SEL, R>();
+        let mut outs = [Word::new([O; 16]); 8];
+        for (out, ram) in outs.iter_mut().zip(&self.rams) {
+            *out = ram.output(address);
+        }
+        // as Mux8Way16 is built from Mux16, the low select bit first
+        for (level, sel) in select.iter().rev().enumerate() {
+            for i in 0..1 << (SEL - 1 - level) {
+                outs[i] = mux16(outs[2 * i], outs[2 * i + 1], *sel);
+            }
+        }
+        outs[0]
+    }
+
+    fn registers(&self) -> Box<dyn Iterator<Item = &Register> + '_> {
+        Box::new(self.rams.iter().flat_map(|ram| ram.registers()))
+    }
+
+    fn registers_mut(&mut self) -> Box<dyn Iterator<Item = &mut Register> + '_> {
+        Box::new(self.rams.iter_mut().flat_map(|ram| ram.registers_mut()))
+    }
 }
-impl RAM512 {
+
+impl<T: Addressed<R>, const SEL: usize, const R: usize, const N: usize> Bank<T, SEL, R, N> {
     pub fn new() -> Self {
-        Self {
-            rams: [RAM64::new(); 8],
+        Addressed::new()
+    }
+    // in the order of the addresses
+    pub(crate) fn registers(&self) -> impl Iterator<Item = &Register> + '_ {
+        Addressed::registers(self)
+    }
+    pub fn tick(&mut self, input: Word, address: [bit; N], load: bit) {
+        Addressed::tick(self, input, Bus::from(address), load)
+    }
+    pub fn tock(&mut self) {
+        Addressed::tock(self)
+    }
+    pub fn output(&self, address: [bit; N]) -> Word {
+        Addressed::output(self, Bus::from(address))
+    }
+
+    // the serialized registers of a snapshot, in place
+    pub(crate) fn restore(&mut self, state: Value) -> Result<(), serde_json::Error> {
+        self.fill(Vec::<Register>::deserialize(state)?)
+    }
+
+    fn fill<E: Error>(&mut self, values: Vec<Register>) -> Result<(), E> {
+        if values.len() != 1 << N {
+            return Err(E::invalid_length(
+                values.len(),
+                &format!("{} registers", 1 << N).as_str(),
+            ));
         }
+        for (register, value) in self.registers_mut().zip(values) {
+            *register = value;
+        }
+        Ok(())
     }
-    pub fn input(&mut self, clock_t: &Clock, input: Word, address: [bit; 9], load: bit) {
-        let load_bit = dmux8way(load, [address[0], address[1], address[2]]);
-        let register_address = [
-            address[3], address[4], address[5], address[6], address[7], address[8],
-        ];
-        self.rams[0].input(clock_t, input, register_address, load_bit[0]);
-        self.rams[1].input(clock_t, input, register_address, load_bit[1]);
-        self.rams[2].input(clock_t, input, register_address, load_bit[2]);
-        self.rams[3].input(clock_t, input, register_address, load_bit[3]);
-        self.rams[4].input(clock_t, input, register_address, load_bit[4]);
-        self.rams[5].input(clock_t, input, register_address, load_bit[5]);
-        self.rams[6].input(clock_t, input, register_address, load_bit[6]);
-        self.rams[7].input(clock_t, input, register_address, load_bit[7]);
-    }
-    pub fn output(self, clock_t: &Clock, address: [bit; 9]) -> Word {
-        let register_address = [
-            address[3], address[4], address[5], address[6], address[7], address[8],
-        ];
-        mux8way16(
-            self.rams[0].output(clock_t, register_address),
-            self.rams[1].output(clock_t, register_address),
-            self.rams[2].output(clock_t, register_address),
-            self.rams[3].output(clock_t, register_address),
-            self.rams[4].output(clock_t, register_address),
-            self.rams[5].output(clock_t, register_address),
-            self.rams[6].output(clock_t, register_address),
-            self.rams[7].output(clock_t, register_address),
-            [address[0], address[1], address[2]],
-        )
+}
+
+impl<T: Addressed<R>, const SEL: usize, const R: usize, const N: usize> Default
+    for Bank<T, SEL, R, N>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// a flat sequence of registers, whatever the chips inside
+impl<T: Addressed<R>, const SEL: usize, const R: usize, const N: usize> Serialize
+    for Bank<T, SEL, R, N>
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.registers())
     }
 }
 
+impl<'de, T: Addressed<R>, const SEL: usize, const R: usize, const N: usize> Deserialize<'de>
+    for Bank<T, SEL, R, N>
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut ram = Self::new();
+        ram.fill(Vec::<Register>::deserialize(deserializer)?)?;
+        Ok(ram)
+    }
+}
+
+// in, load and address of a RAM as a Chip, also of Screen
 #[derive(Debug, Clone, Copy)]
-pub struct RAM4K {
-    rams: [RAM512; 8],
+pub(crate) struct Pins<const N: usize> {
+    pub(crate) input: Word,
+    pub(crate) load: bit,
+    pub(crate) address: Bus<N>,
 }
-impl RAM4K {
-    pub fn new() -> Self {
+
+impl<const N: usize> Default for Pins<N> {
+    fn default() -> Self {
         Self {
-            rams: [RAM512::new(); 8],
+            input: Word::new([O; 16]),
+            load: O,
+            address: Bus::default(),
         }
     }
-    pub fn input(&mut self, clock_t: &Clock, input: Word, address: [bit; 12], load: bit) {
-        let load_bit = dmux8way(load, [address[0], address[1], address[2]]);
-        let register_address = [
-            address[3],
-            address[4],
-            address[5],
-            address[6],
-            address[7],
-            address[8],
-            address[9],
-            address[10],
-            address[11],
-        ];
-        self.rams[0].input(clock_t, input, register_address, load_bit[0]);
-        self.rams[1].input(clock_t, input, register_address, load_bit[1]);
-        self.rams[2].input(clock_t, input, register_address, load_bit[2]);
-        self.rams[3].input(clock_t, input, register_address, load_bit[3]);
-        self.rams[4].input(clock_t, input, register_address, load_bit[4]);
-        self.rams[5].input(clock_t, input, register_address, load_bit[5]);
-        self.rams[6].input(clock_t, input, register_address, load_bit[6]);
-        self.rams[7].input(clock_t, input, register_address, load_bit[7]);
-    }
-    pub fn output(self, clock_t: &Clock, address: [bit; 12]) -> Word {
-        let register_address = [
-            address[3],
-            address[4],
-            address[5],
-            address[6],
-            address[7],
-            address[8],
-            address[9],
-            address[10],
-            address[11],
-        ];
-        mux8way16(
-            self.rams[0].output(clock_t, register_address),
-            self.rams[1].output(clock_t, register_address),
-            self.rams[2].output(clock_t, register_address),
-            self.rams[3].output(clock_t, register_address),
-            self.rams[4].output(clock_t, register_address),
-            self.rams[5].output(clock_t, register_address),
-            self.rams[6].output(clock_t, register_address),
-            self.rams[7].output(clock_t, register_address),
-            [address[0], address[1], address[2]],
-        )
+}
+
+impl<const N: usize> Pins<N> {
+    pub(crate) fn inputs() -> &'static [PinDecl] {
+        // for every width of address
+        static PINS: OnceLock<Vec<Vec<PinDecl>>> = OnceLock::new();
+        &PINS.get_or_init(|| {
+            (0..=16)
+                .map(|bits| pins(&[("in", 16), ("load", 1), ("address", bits)]))
+                .collect()
+        })[N]
+    }
+
+    pub(crate) fn outputs() -> &'static [PinDecl] {
+        static PINS: OnceLock<Vec<PinDecl>> = OnceLock::new();
+        declare(&PINS, &[("out", 16)])
+    }
+
+    // out follows the address, and the rest only the clock
+    pub(crate) fn follows(output: &str, input: &str) -> bool {
+        output == "out" && input == "address"
+    }
+
+    pub(crate) fn set(&mut self, pin: &str, bits: &[bit]) {
+        match pin {
+            "in" => self.input = to_word(bits),
+            "load" => self.load = bits[0],
+            _ => self.address = Bus::new(msb_first(bits)),
+        }
+    }
+
+    // the inputs, or the output of the RAM
+    pub(crate) fn get<R: Addressed<N>>(&self, ram: &R, pin: &str) -> Option<Vec<bit>> {
+        match pin {
+            "in" => Some(from_word(self.input)),
+            "load" => Some(vec![self.load]),
+            "address" => Some(self.address.iter().rev().copied().collect()),
+            "out" => Some(from_word(ram.output(self.address))),
+            _ => None,
+        }
+    }
+
+    // the rising edge to the RAM
+    fn tick<R: Addressed<N>>(&self, ram: &mut R) {
+        ram.tick(self.input, self.address, self.load);
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-pub struct RAM16K {
-    rams: [RAM4K; 4],
+impl Chip for RAM8 {
+    fn name(&self) -> &str {
+        "RAM8"
+    }
+
+    fn inputs(&self) -> &[PinDecl] {
+        Pins::<3>::inputs()
+    }
+
+    fn outputs(&self) -> &[PinDecl] {
+        Pins::<3>::outputs()
+    }
+
+    fn set_input(&mut self, pin: &str, bits: &[bit]) -> Result<(), HardwareError> {
+        check(self, pin, bits)?;
+        self.pins.set(pin, bits);
+        Ok(())
+    }
+
+    fn get(&self, pin: &str) -> Option<Vec<bit>> {
+        self.pins.get(self, pin)
+    }
+
+    fn eval(&mut self) {}
+
+    fn is_clocked(&self) -> bool {
+        true
+    }
+
+    fn tick(&mut self) {
+        let pins = self.pins;
+        pins.tick(self);
+    }
+
+    fn tock(&mut self) {
+        RAM8::tock(self);
+    }
+
+    fn follows(&self, output: &str, input: &str) -> bool {
+        Pins::<3>::follows(output, input)
+    }
 }
-impl RAM16K {
-    pub fn new() -> Self {
-        Self {
-            rams: [RAM4K::new(); 4],
-        }
+
+impl<T: Addressed<R>, const SEL: usize, const R: usize, const N: usize> Chip
+    for Bank<T, SEL, R, N>
+{
+    fn name(&self) -> &str {
+        name(N)
     }
-    pub fn input(&mut self, clock_t: &Clock, input: Word, address: [bit; 14], load: bit) {
-        let load_bit = dmux4way(load, [address[0], address[1]]);
-        let register_address = [
-            address[2],
-            address[3],
-            address[4],
-            address[5],
-            address[6],
-            address[7],
-            address[8],
-            address[9],
-            address[10],
-            address[11],
-            address[12],
-            address[13],
-        ];
-        self.rams[0].input(clock_t, input, register_address, load_bit[0]);
-        self.rams[1].input(clock_t, input, register_address, load_bit[1]);
-        self.rams[2].input(clock_t, input, register_address, load_bit[2]);
-        self.rams[3].input(clock_t, input, register_address, load_bit[3]);
-    }
-    pub fn output(self, clock_t: &Clock, address: [bit; 14]) -> Word {
-        let register_address = [
-            address[2],
-            address[3],
-            address[4],
-            address[5],
-            address[6],
-            address[7],
-            address[8],
-            address[9],
-            address[10],
-            address[11],
-            address[12],
-            address[13],
-        ];
-        mux4way16(
-            self.rams[0].output(clock_t, register_address),
-            self.rams[1].output(clock_t, register_address),
-            self.rams[2].output(clock_t, register_address),
-            self.rams[3].output(clock_t, register_address),
-            [address[0], address[1]],
-        )
+
+    fn inputs(&self) -> &[PinDecl] {
+        Pins::<N>::inputs()
+    }
+
+    fn outputs(&self) -> &[PinDecl] {
+        Pins::<N>::outputs()
+    }
+
+    fn set_input(&mut self, pin: &str, bits: &[bit]) -> Result<(), HardwareError> {
+        check(self, pin, bits)?;
+        self.pins.set(pin, bits);
+        Ok(())
+    }
+
+    fn get(&self, pin: &str) -> Option<Vec<bit>> {
+        self.pins.get(self, pin)
+    }
+
+    fn eval(&mut self) {}
+
+    fn is_clocked(&self) -> bool {
+        true
+    }
+
+    fn tick(&mut self) {
+        let pins = self.pins;
+        pins.tick(self);
+    }
+
+    fn tock(&mut self) {
+        Addressed::tock(self);
+    }
+
+    fn follows(&self, output: &str, input: &str) -> bool {
+        Pins::<N>::follows(output, input)
+    }
+}
+
+fn name(bits: usize) -> &'static str {
+    match bits {
+        6 => "RAM64",
+        9 => "RAM512",
+        12 => "RAM4K",
+        14 => "RAM16K",
+        _ => "RAM",
+    }
+}
+
+// the names of the profile, as the chips of the course
+fn chip(bits: usize, pin: &str) -> &'static str {
+    match (bits, pin) {
+        (6, "input") => "RAM64.input",
+        (6, _) => "RAM64.output",
+        (9, "input") => "RAM512.input",
+        (9, _) => "RAM512.output",
+        (12, "input") => "RAM4K.input",
+        (12, _) => "RAM4K.output",
+        (14, "input") => "RAM16K.input",
+        (14, _) => "RAM16K.output",
+        (_, "input") => "RAM.input",
+        _ => "RAM.output",
     }
 }
 
@@ -253,122 +471,114 @@ mod tests {
         let zero = Word::new([O; 16]);
         let one = Word::new([I; 16]);
         let mut ram8 = RAM8::new();
-        let mut clock = Clock::new();
 
         // initial state
-        assert_eq!(ram8.output(&clock, [O, O, O]), zero);
-        assert_eq!(ram8.output(&clock, [O, O, I]), zero);
-        assert_eq!(ram8.output(&clock, [O, I, O]), zero);
-        assert_eq!(ram8.output(&clock, [O, I, I]), zero);
-        assert_eq!(ram8.output(&clock, [I, O, O]), zero);
-        assert_eq!(ram8.output(&clock, [I, O, I]), zero);
-        assert_eq!(ram8.output(&clock, [I, I, O]), zero);
-        assert_eq!(ram8.output(&clock, [I, I, I]), zero);
+        assert_eq!(ram8.output([O, O, O]), zero);
+        assert_eq!(ram8.output([O, O, I]), zero);
+        assert_eq!(ram8.output([O, I, O]), zero);
+        assert_eq!(ram8.output([O, I, I]), zero);
+        assert_eq!(ram8.output([I, O, O]), zero);
+        assert_eq!(ram8.output([I, O, I]), zero);
+        assert_eq!(ram8.output([I, I, O]), zero);
+        assert_eq!(ram8.output([I, I, I]), zero);
 
         // set one into register[address]
-        ram8.input(&clock, one, [O, O, O], I); // register[0]: prev: 0, cur: 1
-        assert_eq!(ram8.output(&clock, [O, O, O]), zero);
-        assert_eq!(ram8.output(&clock, [O, O, I]), zero);
-        assert_eq!(ram8.output(&clock, [O, I, O]), zero);
-        assert_eq!(ram8.output(&clock, [O, I, I]), zero);
-        assert_eq!(ram8.output(&clock, [I, O, O]), zero);
-        assert_eq!(ram8.output(&clock, [I, O, I]), zero);
-        assert_eq!(ram8.output(&clock, [I, I, O]), zero);
-        assert_eq!(ram8.output(&clock, [I, I, I]), zero);
-
-        clock.next();
-        // Tock (should happen nothing)
-        ram8.input(&clock, one, [O, O, I], I); // register[0]: prev: 0, cur: 1
-        assert_eq!(ram8.output(&clock, [O, O, O]), one); //
-        assert_eq!(ram8.output(&clock, [O, O, I]), zero);
-        assert_eq!(ram8.output(&clock, [O, I, O]), zero);
-        assert_eq!(ram8.output(&clock, [O, I, I]), zero);
-        assert_eq!(ram8.output(&clock, [I, O, O]), zero);
-        assert_eq!(ram8.output(&clock, [I, O, I]), zero);
-        assert_eq!(ram8.output(&clock, [I, I, O]), zero);
-        assert_eq!(ram8.output(&clock, [I, I, I]), zero);
-
-        clock.next();
+        ram8.tick(one, [O, O, O], I);
+        assert_eq!(ram8.output([O, O, O]), zero);
+        assert_eq!(ram8.output([O, O, I]), zero);
+        assert_eq!(ram8.output([O, I, O]), zero);
+        assert_eq!(ram8.output([O, I, I]), zero);
+        assert_eq!(ram8.output([I, O, O]), zero);
+        assert_eq!(ram8.output([I, O, I]), zero);
+        assert_eq!(ram8.output([I, I, O]), zero);
+        assert_eq!(ram8.output([I, I, I]), zero);
+
+        // Tock
+        ram8.tock();
+        assert_eq!(ram8.output([O, O, O]), one);
+        assert_eq!(ram8.output([O, O, I]), zero);
+        assert_eq!(ram8.output([O, I, O]), zero);
+        assert_eq!(ram8.output([O, I, I]), zero);
+        assert_eq!(ram8.output([I, O, O]), zero);
+        assert_eq!(ram8.output([I, O, I]), zero);
+        assert_eq!(ram8.output([I, I, O]), zero);
+        assert_eq!(ram8.output([I, I, I]), zero);
+
         // Tick
-        ram8.input(&clock, one, [O, O, I], I); // register[0]: prev: 1, cur: I, register[1]: prev: 0, cur: 1
-        assert_eq!(ram8.output(&clock, [O, O, O]), one);
-        assert_eq!(ram8.output(&clock, [O, O, I]), zero);
-        assert_eq!(ram8.output(&clock, [O, I, O]), zero);
-        assert_eq!(ram8.output(&clock, [O, I, I]), zero);
-        assert_eq!(ram8.output(&clock, [I, O, O]), zero);
-        assert_eq!(ram8.output(&clock, [I, O, I]), zero);
-        assert_eq!(ram8.output(&clock, [I, I, O]), zero);
-        assert_eq!(ram8.output(&clock, [I, I, I]), zero);
-
-        clock.next();
+        ram8.tick(one, [O, O, I], I);
+        assert_eq!(ram8.output([O, O, O]), one);
+        assert_eq!(ram8.output([O, O, I]), zero);
+        assert_eq!(ram8.output([O, I, O]), zero);
+        assert_eq!(ram8.output([O, I, I]), zero);
+        assert_eq!(ram8.output([I, O, O]), zero);
+        assert_eq!(ram8.output([I, O, I]), zero);
+        assert_eq!(ram8.output([I, I, O]), zero);
+        assert_eq!(ram8.output([I, I, I]), zero);
+
         // Tock
-        ram8.input(&clock, one, [O, O, I], I); // register[0]: prev: 1, cur: I, register[1]: prev: 0, cur: 1
-        assert_eq!(ram8.output(&clock, [O, O, O]), one); // remained
-        assert_eq!(ram8.output(&clock, [O, O, I]), one); // updated
-        assert_eq!(ram8.output(&clock, [O, I, O]), zero);
-        assert_eq!(ram8.output(&clock, [O, I, I]), zero);
-        assert_eq!(ram8.output(&clock, [I, O, O]), zero);
-        assert_eq!(ram8.output(&clock, [I, O, I]), zero);
-        assert_eq!(ram8.output(&clock, [I, I, O]), zero);
-        assert_eq!(ram8.output(&clock, [I, I, I]), zero);
-
-        clock.next();
+        ram8.tock();
+        assert_eq!(ram8.output([O, O, O]), one); // remained
+        assert_eq!(ram8.output([O, O, I]), one); // updated
+        assert_eq!(ram8.output([O, I, O]), zero);
+        assert_eq!(ram8.output([O, I, I]), zero);
+        assert_eq!(ram8.output([I, O, O]), zero);
+        assert_eq!(ram8.output([I, O, I]), zero);
+        assert_eq!(ram8.output([I, I, O]), zero);
+        assert_eq!(ram8.output([I, I, I]), zero);
+
         // Tick
-        ram8.input(&clock, zero, [O, O, O], I); // register[0]をzeroに更新 register[0]: prev: 1, cur: 0, register[1]: prev: 1, cur: 1
-        assert_eq!(ram8.output(&clock, [O, O, O]), one);
-        assert_eq!(ram8.output(&clock, [O, O, I]), one);
-        assert_eq!(ram8.output(&clock, [O, I, O]), zero);
-        assert_eq!(ram8.output(&clock, [O, I, I]), zero);
-        assert_eq!(ram8.output(&clock, [I, O, O]), zero);
-        assert_eq!(ram8.output(&clock, [I, O, I]), zero);
-        assert_eq!(ram8.output(&clock, [I, I, O]), zero);
-        assert_eq!(ram8.output(&clock, [I, I, I]), zero);
-
-        clock.next();
-        // Tock (Tock時間のoutputにあまり興味はない)
-        ram8.input(&clock, zero, [O, O, O], I); // register[0]: prev: 1, cur: 0, register[1]: prev: 1, cur: 1
-        assert_eq!(ram8.output(&clock, [O, O, O]), zero); // curの値
-        assert_eq!(ram8.output(&clock, [O, O, I]), one);
-        assert_eq!(ram8.output(&clock, [O, I, O]), zero);
-        assert_eq!(ram8.output(&clock, [O, I, I]), zero);
-        assert_eq!(ram8.output(&clock, [I, O, O]), zero);
-        assert_eq!(ram8.output(&clock, [I, O, I]), zero);
-        assert_eq!(ram8.output(&clock, [I, I, O]), zero);
-        assert_eq!(ram8.output(&clock, [I, I, I]), zero);
-
-        clock.next();
+        ram8.tick(zero, [O, O, O], I);
+        assert_eq!(ram8.output([O, O, O]), one);
+        assert_eq!(ram8.output([O, O, I]), one);
+        assert_eq!(ram8.output([O, I, O]), zero);
+        assert_eq!(ram8.output([O, I, I]), zero);
+        assert_eq!(ram8.output([I, O, O]), zero);
+        assert_eq!(ram8.output([I, O, I]), zero);
+        assert_eq!(ram8.output([I, I, O]), zero);
+        assert_eq!(ram8.output([I, I, I]), zero);
+
+        // Tock
+        ram8.tock();
+        assert_eq!(ram8.output([O, O, O]), zero);
+        assert_eq!(ram8.output([O, O, I]), one);
+        assert_eq!(ram8.output([O, I, O]), zero);
+        assert_eq!(ram8.output([O, I, I]), zero);
+        assert_eq!(ram8.output([I, O, O]), zero);
+        assert_eq!(ram8.output([I, O, I]), zero);
+        assert_eq!(ram8.output([I, I, O]), zero);
+        assert_eq!(ram8.output([I, I, I]), zero);
+
         // Tick
-        ram8.input(&clock, one, [O, O, I], I); // register[1]をoneに更新 register[1]: prev:0, cur:1
-        assert_eq!(ram8.output(&clock, [O, O, O]), zero);
-        assert_eq!(ram8.output(&clock, [O, O, I]), one);
-        assert_eq!(ram8.output(&clock, [O, I, O]), zero);
-        assert_eq!(ram8.output(&clock, [O, I, I]), zero);
-        assert_eq!(ram8.output(&clock, [I, O, O]), zero);
-        assert_eq!(ram8.output(&clock, [I, O, I]), zero);
-        assert_eq!(ram8.output(&clock, [I, I, O]), zero);
-        assert_eq!(ram8.output(&clock, [I, I, I]), zero);
-
-        clock.next();
+        ram8.tick(one, [O, O, I], I);
+        assert_eq!(ram8.output([O, O, O]), zero);
+        assert_eq!(ram8.output([O, O, I]), one);
+        assert_eq!(ram8.output([O, I, O]), zero);
+        assert_eq!(ram8.output([O, I, I]), zero);
+        assert_eq!(ram8.output([I, O, O]), zero);
+        assert_eq!(ram8.output([I, O, I]), zero);
+        assert_eq!(ram8.output([I, I, O]), zero);
+        assert_eq!(ram8.output([I, I, I]), zero);
+
         // Tock
-        assert_eq!(ram8.output(&clock, [O, O, O]), zero);
-        assert_eq!(ram8.output(&clock, [O, O, I]), one);
-        assert_eq!(ram8.output(&clock, [O, I, O]), zero);
-        assert_eq!(ram8.output(&clock, [O, I, I]), zero);
-        assert_eq!(ram8.output(&clock, [I, O, O]), zero);
-        assert_eq!(ram8.output(&clock, [I, O, I]), zero);
-        assert_eq!(ram8.output(&clock, [I, I, O]), zero);
-        assert_eq!(ram8.output(&clock, [I, I, I]), zero);
-
-        clock.next();
+        ram8.tock();
+        assert_eq!(ram8.output([O, O, O]), zero);
+        assert_eq!(ram8.output([O, O, I]), one);
+        assert_eq!(ram8.output([O, I, O]), zero);
+        assert_eq!(ram8.output([O, I, I]), zero);
+        assert_eq!(ram8.output([I, O, O]), zero);
+        assert_eq!(ram8.output([I, O, I]), zero);
+        assert_eq!(ram8.output([I, I, O]), zero);
+        assert_eq!(ram8.output([I, I, I]), zero);
+
         // Tick
-        assert_eq!(ram8.output(&clock, [O, O, O]), zero);
-        assert_eq!(ram8.output(&clock, [O, O, I]), one);
-        assert_eq!(ram8.output(&clock, [O, I, O]), zero);
-        assert_eq!(ram8.output(&clock, [O, I, I]), zero);
-        assert_eq!(ram8.output(&clock, [I, O, O]), zero);
-        assert_eq!(ram8.output(&clock, [I, O, I]), zero);
-        assert_eq!(ram8.output(&clock, [I, I, O]), zero);
-        assert_eq!(ram8.output(&clock, [I, I, I]), zero);
+        assert_eq!(ram8.output([O, O, O]), zero);
+        assert_eq!(ram8.output([O, O, I]), one);
+        assert_eq!(ram8.output([O, I, O]), zero);
+        assert_eq!(ram8.output([O, I, I]), zero);
+        assert_eq!(ram8.output([I, O, O]), zero);
+        assert_eq!(ram8.output([I, O, I]), zero);
+        assert_eq!(ram8.output([I, I, O]), zero);
+        assert_eq!(ram8.output([I, I, I]), zero);
     }
 
     #[test]
@@ -376,189 +586,163 @@ mod tests {
         let zero = Word::new([O; 16]);
         let word1 = Word::new([I, O, I, O, I, O, I, O, I, O, I, O, I, O, I, O]);
         let mut ram8 = RAM8::new();
-        let mut clock = Clock::new();
 
-        // Tick
-        ram8.input(&clock, word1, [O, O, O], I);
-
-        clock.next(); // Tock
-        clock.next(); // Tick
-        ram8.input(&clock, word1, [I, O, I], I);
-        assert_eq!(ram8.output(&clock, [O, O, O]), word1);
-        assert_eq!(ram8.output(&clock, [O, O, I]), zero);
-        assert_eq!(ram8.output(&clock, [O, I, O]), zero);
-        assert_eq!(ram8.output(&clock, [O, I, I]), zero);
-        assert_eq!(ram8.output(&clock, [I, O, O]), zero);
-        assert_eq!(ram8.output(&clock, [I, O, I]), zero);
-        assert_eq!(ram8.output(&clock, [I, I, O]), zero);
-        assert_eq!(ram8.output(&clock, [I, I, I]), zero);
-
-        clock.next(); // Tock
-        clock.next(); // Tick
-        ram8.input(&clock, word1, [I, I, I], O);
-        assert_eq!(ram8.output(&clock, [O, O, O]), word1);
-        assert_eq!(ram8.output(&clock, [O, O, I]), zero);
-        assert_eq!(ram8.output(&clock, [O, I, O]), zero);
-        assert_eq!(ram8.output(&clock, [O, I, I]), zero);
-        assert_eq!(ram8.output(&clock, [I, O, O]), zero);
-        assert_eq!(ram8.output(&clock, [I, O, I]), word1);
-        assert_eq!(ram8.output(&clock, [I, I, O]), zero);
-        assert_eq!(ram8.output(&clock, [I, I, I]), zero);
-
-        clock.next(); // Tock
-        clock.next(); // Tick
-        ram8.input(&clock, zero, [O, O, O], I);
-        assert_eq!(ram8.output(&clock, [O, O, O]), word1);
-        assert_eq!(ram8.output(&clock, [O, O, I]), zero);
-        assert_eq!(ram8.output(&clock, [O, I, O]), zero);
-        assert_eq!(ram8.output(&clock, [O, I, I]), zero);
-        assert_eq!(ram8.output(&clock, [I, O, O]), zero);
-        assert_eq!(ram8.output(&clock, [I, O, I]), word1);
-        assert_eq!(ram8.output(&clock, [I, I, O]), zero);
-        assert_eq!(ram8.output(&clock, [I, I, I]), zero);
-
-        clock.next(); // Tock
-        clock.next(); // Tick
-        ram8.input(&clock, zero, [O, O, O], O);
-        assert_eq!(ram8.output(&clock, [O, O, O]), zero);
-        assert_eq!(ram8.output(&clock, [O, O, I]), zero);
-        assert_eq!(ram8.output(&clock, [O, I, O]), zero);
-        assert_eq!(ram8.output(&clock, [O, I, I]), zero);
-        assert_eq!(ram8.output(&clock, [I, O, O]), zero);
-        assert_eq!(ram8.output(&clock, [I, O, I]), word1);
-        assert_eq!(ram8.output(&clock, [I, I, O]), zero);
-        assert_eq!(ram8.output(&clock, [I, I, I]), zero);
+        ram8.tick(word1, [O, O, O], I);
+        ram8.tock();
+        ram8.tick(word1, [I, O, I], I);
+        assert_eq!(ram8.output([O, O, O]), word1);
+        assert_eq!(ram8.output([O, O, I]), zero);
+        assert_eq!(ram8.output([O, I, O]), zero);
+        assert_eq!(ram8.output([O, I, I]), zero);
+        assert_eq!(ram8.output([I, O, O]), zero);
+        assert_eq!(ram8.output([I, O, I]), zero);
+        assert_eq!(ram8.output([I, I, O]), zero);
+        assert_eq!(ram8.output([I, I, I]), zero);
+
+        ram8.tock();
+        ram8.tick(word1, [I, I, I], O);
+        assert_eq!(ram8.output([O, O, O]), word1);
+        assert_eq!(ram8.output([O, O, I]), zero);
+        assert_eq!(ram8.output([O, I, O]), zero);
+        assert_eq!(ram8.output([O, I, I]), zero);
+        assert_eq!(ram8.output([I, O, O]), zero);
+        assert_eq!(ram8.output([I, O, I]), word1);
+        assert_eq!(ram8.output([I, I, O]), zero);
+        assert_eq!(ram8.output([I, I, I]), zero);
+
+        ram8.tock();
+        ram8.tick(zero, [O, O, O], I);
+        assert_eq!(ram8.output([O, O, O]), word1);
+        assert_eq!(ram8.output([O, O, I]), zero);
+        assert_eq!(ram8.output([O, I, O]), zero);
+        assert_eq!(ram8.output([O, I, I]), zero);
+        assert_eq!(ram8.output([I, O, O]), zero);
+        assert_eq!(ram8.output([I, O, I]), word1);
+        assert_eq!(ram8.output([I, I, O]), zero);
+        assert_eq!(ram8.output([I, I, I]), zero);
+
+        ram8.tock();
+        ram8.tick(zero, [O, O, O], O);
+        assert_eq!(ram8.output([O, O, O]), zero);
+        assert_eq!(ram8.output([O, O, I]), zero);
+        assert_eq!(ram8.output([O, I, O]), zero);
+        assert_eq!(ram8.output([O, I, I]), zero);
+        assert_eq!(ram8.output([I, O, O]), zero);
+        assert_eq!(ram8.output([I, O, I]), word1);
+        assert_eq!(ram8.output([I, I, O]), zero);
+        assert_eq!(ram8.output([I, I, I]), zero);
     }
 
     #[test]
     fn for_ram64() {
         let zero = Word::new([O; 16]);
-        let mut clock = Clock::new();
         let mut ram64 = RAM64::new();
         let word1 = Word::new([I, O, I, O, I, O, I, O, I, O, I, O, I, O, I, O]);
 
-        ram64.input(&clock, word1, [O, O, O, O, O, O], I);
-        assert_eq!(ram64.output(&clock, [O, O, O, O, O, O]), zero);
-
-        clock.next();
-        clock.next();
-        ram64.input(&clock, word1, [O, O, I, O, O, O], I);
-        assert_eq!(ram64.output(&clock, [O, O, O, O, O, O]), word1);
-
-        clock.next();
-        clock.next();
-        ram64.input(&clock, word1, [O, O, O, O, O, I], O);
-        assert_eq!(ram64.output(&clock, [O, O, O, O, O, O]), word1);
-        assert_eq!(ram64.output(&clock, [O, O, I, O, O, O]), word1);
-
-        clock.next();
-        clock.next();
-        ram64.input(&clock, word1, [O, O, O, O, O, I], I);
-        assert_eq!(ram64.output(&clock, [O, O, O, O, O, O]), word1);
-        assert_eq!(ram64.output(&clock, [O, O, I, O, O, O]), word1);
-        assert_eq!(ram64.output(&clock, [O, O, O, O, O, I]), zero);
-
-        clock.next();
-        clock.next();
-        ram64.input(&clock, word1, [O, O, O, O, O, I], I);
-        assert_eq!(ram64.output(&clock, [O, O, O, O, O, O]), word1);
-        assert_eq!(ram64.output(&clock, [O, O, I, O, O, O]), word1);
-        assert_eq!(ram64.output(&clock, [O, O, O, O, O, I]), word1);
+        ram64.tick(word1, [O, O, O, O, O, O], I);
+        assert_eq!(ram64.output([O, O, O, O, O, O]), zero);
+
+        ram64.tock();
+        ram64.tick(word1, [O, O, I, O, O, O], I);
+        assert_eq!(ram64.output([O, O, O, O, O, O]), word1);
+
+        ram64.tock();
+        ram64.tick(word1, [O, O, O, O, O, I], O);
+        assert_eq!(ram64.output([O, O, O, O, O, O]), word1);
+        assert_eq!(ram64.output([O, O, I, O, O, O]), word1);
+
+        ram64.tock();
+        ram64.tick(word1, [O, O, O, O, O, I], I);
+        assert_eq!(ram64.output([O, O, O, O, O, O]), word1);
+        assert_eq!(ram64.output([O, O, I, O, O, O]), word1);
+        assert_eq!(ram64.output([O, O, O, O, O, I]), zero);
+
+        ram64.tock();
+        ram64.tick(word1, [O, O, O, O, O, I], I);
+        assert_eq!(ram64.output([O, O, O, O, O, O]), word1);
+        assert_eq!(ram64.output([O, O, I, O, O, O]), word1);
+        assert_eq!(ram64.output([O, O, O, O, O, I]), word1);
     }
 
     #[test]
     fn for_ram4k() {
         let zero = Word::new([O; 16]);
-        let mut clock = Clock::new();
         let mut ram4k = RAM4K::new();
         let word1 = Word::new([I, O, I, O, I, O, I, O, I, O, I, O, I, O, I, O]);
 
-        ram4k.input(&clock, word1, [O, O, O, O, O, O, O, O, O, O, O, O], I);
-        assert_eq!(
-            ram4k.output(&clock, [O, O, O, O, O, O, O, O, O, O, O, O]),
-            zero
-        );
-
-        clock.next();
-        clock.next();
-        ram4k.input(&clock, word1, [O, O, O, O, O, O, O, O, I, O, O, O], I);
+        ram4k.tick(word1, [O, O, O, O, O, O, O, O, O, O, O, O], I);
+        assert_eq!(ram4k.output([O, O, O, O, O, O, O, O, O, O, O, O]), zero);
+
+        ram4k.tock();
+        ram4k.tick(word1, [O, O, O, O, O, O, O, O, I, O, O, O], I);
+        assert_eq!(ram4k.output([O, O, O, O, O, O, O, O, O, O, O, O]), word1);
+
+        ram4k.tock();
+        ram4k.tick(word1, [O, O, O, O, O, O, O, O, O, O, O, I], O);
+        assert_eq!(ram4k.output([O, O, O, O, O, O, O, O, O, O, O, O]), word1);
+        assert_eq!(ram4k.output([O, O, O, O, O, O, O, O, I, O, O, O]), word1);
+
+        ram4k.tock();
+        ram4k.tick(word1, [O, O, O, O, O, O, O, O, O, O, O, I], I);
+        assert_eq!(ram4k.output([O, O, O, O, O, O, O, O, O, O, O, O]), word1);
+        assert_eq!(ram4k.output([O, O, O, O, O, O, O, O, I, O, O, O]), word1);
+        assert_eq!(ram4k.output([O, O, O, O, O, O, O, O, O, O, O, I]), zero);
+
+        ram4k.tock();
+        ram4k.tick(Word::new([I; 16]), [I, I, I, I, I, I, I, I, I, I, I, I], I);
+        assert_eq!(ram4k.output([O, O, O, O, O, O, O, O, O, O, O, O]), word1);
+        assert_eq!(ram4k.output([O, O, O, O, O, O, O, O, I, O, O, O]), word1);
+        assert_eq!(ram4k.output([O, O, O, O, O, O, O, O, O, O, O, I]), word1);
+
+        ram4k.tock();
+        ram4k.tick(Word::new([I; 16]), [I, I, I, I, I, I, I, I, I, I, I, I], I);
+        assert_eq!(ram4k.output([O, O, O, O, O, O, O, O, O, O, O, O]), word1);
+        assert_eq!(ram4k.output([O, O, O, O, O, O, O, O, I, O, O, O]), word1);
+        assert_eq!(ram4k.output([O, O, O, O, O, O, O, O, O, O, O, I]), word1);
         assert_eq!(
-            ram4k.output(&clock, [O, O, O, O, O, O, O, O, O, O, O, O]),
-            word1
-        );
-
-        clock.next();
-        clock.next();
-        ram4k.input(&clock, word1, [O, O, O, O, O, O, O, O, O, O, O, I], O);
-        assert_eq!(
-            ram4k.output(&clock, [O, O, O, O, O, O, O, O, O, O, O, O]),
-            word1
-        );
-        assert_eq!(
-            ram4k.output(&clock, [O, O, O, O, O, O, O, O, I, O, O, O]),
-            word1
-        );
-
-        clock.next();
-        clock.next();
-        ram4k.input(&clock, word1, [O, O, O, O, O, O, O, O, O, O, O, I], I);
-        assert_eq!(
-            ram4k.output(&clock, [O, O, O, O, O, O, O, O, O, O, O, O]),
-            word1
-        );
-        assert_eq!(
-            ram4k.output(&clock, [O, O, O, O, O, O, O, O, I, O, O, O]),
-            word1
-        );
-        assert_eq!(
-            ram4k.output(&clock, [O, O, O, O, O, O, O, O, O, O, O, I]),
-            zero
+            ram4k.output([I, I, I, I, I, I, I, I, I, I, I, I]),
+            Word::new([I; 16])
         );
+    }
 
-        clock.next();
-        clock.next();
-        ram4k.input(
-            &clock,
-            Word::new([I; 16]),
-            [I, I, I, I, I, I, I, I, I, I, I, I],
-            I,
-        );
-        assert_eq!(
-            ram4k.output(&clock, [O, O, O, O, O, O, O, O, O, O, O, O]),
-            word1
-        );
-        assert_eq!(
-            ram4k.output(&clock, [O, O, O, O, O, O, O, O, I, O, O, O]),
-            word1
-        );
-        assert_eq!(
-            ram4k.output(&clock, [O, O, O, O, O, O, O, O, O, O, O, I]),
-            word1
-        );
+    // written on a tick and read from the tock, at the first, last and some address
+    fn write_read<const A: usize>(addresses: &[usize])
+    where
+        Bits<A>: Layout<Ram: Addressed<A>>,
+    {
+        let mut ram = Ram::<A>::new();
+        assert_eq!(ram.registers().count(), 1 << A);
+        for (i, address) in addresses.iter().enumerate() {
+            let address = Bus::<A>::from_number(*address);
+            ram.tick(Word::from(i as u16 + 1), address, I);
+            ram.tock();
+        }
+        for (i, address) in addresses.iter().enumerate() {
+            let address = Bus::<A>::from_number(*address);
+            assert_eq!(ram.output(address), Word::from(i as u16 + 1), "{}", A);
+        }
+    }
 
-        clock.next();
-        clock.next();
-        ram4k.input(
-            &clock,
-            Word::new([I; 16]),
-            [I, I, I, I, I, I, I, I, I, I, I, I],
-            I,
-        );
-        assert_eq!(
-            ram4k.output(&clock, [O, O, O, O, O, O, O, O, O, O, O, O]),
-            word1
-        );
-        assert_eq!(
-            ram4k.output(&clock, [O, O, O, O, O, O, O, O, I, O, O, O]),
-            word1
-        );
-        assert_eq!(
-            ram4k.output(&clock, [O, O, O, O, O, O, O, O, O, O, O, I]),
-            word1
-        );
+    #[test]
+    fn for_ram_sizes() {
+        // 2 RAM8s, the screen, the ROM and a 64K bank
+        write_read::<4>(&[0, 15, 9]);
+        write_read::<13>(&[0, 8191, 4096]);
+        write_read::<15>(&[0, 32767, 16385]);
+        write_read::<16>(&[0, 65535, 32768]);
+
+        // a snapshot is the registers in the order of the addresses
+        let mut ram = RAM64::new();
+        ram.tick(Word::from(7u16), Bus::from_number(62).bits(), I);
+        ram.tock();
+        let state = serde_json::to_value(&ram).unwrap();
+        assert_eq!(state.as_array().unwrap().len(), 64);
+        let mut restored = RAM64::new();
+        restored.restore(state).unwrap();
         assert_eq!(
-            ram4k.output(&clock, [I, I, I, I, I, I, I, I, I, I, I, I]),
-            Word::new([I; 16])
+            restored.output(Bus::from_number(62).bits()),
+            Word::from(7u16)
         );
+        assert!(RAM512::new().restore(serde_json::json!([])).is_err());
     }
 }
@@ -1,225 +1,337 @@
 #![allow(dead_code)]
-use crate::base::dff::ClockState::{Tick, Tock};
+use std::sync::OnceLock;
+
+use crate::base::chip::{check, declare, Chip};
+use crate::base::logic::mux;
 use crate::base::logic::Word;
-use crate::base::{dff::Clock, logic::mux};
 use crate::base::{dff::Dff, logic::bit};
+use crate::error::HardwareError;
+use crate::hdl::{from_word, parser::PinDecl, to_word};
+use crate::profile;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 pub struct Bit {
     dff: Dff,
+    // in and load of the Chip
+    #[serde(skip, default = "low")]
+    pins: (bit, bit),
+}
+
+fn low() -> (bit, bit) {
+    (bit::O, bit::O)
 }
 
 impl Bit {
     pub fn new() -> Self {
-        Self { dff: Dff::new() }
-    }
-    // clock(t), a(t-1), load(t-1)
-    pub fn input(&mut self, clock_t: &Clock, input: bit, load: bit) {
-        let clock_t_1 = match clock_t.state {
-            Tick => {
-                let mut c = Clock::new();
-                c.next();
-                c
-            }
-            Tock => Clock::new(),
-        };
-        let out_t_1 = self.output(&clock_t_1);
-        // Save out(t) into DFF
-        // if load(t-1) then out(t) = in(t-1)
-        // else out(t) = out(t-1)
-        self.dff.input(clock_t, mux(out_t_1, input, load));
-    }
-    pub fn output(self, clock: &Clock) -> bit {
-        // Get out(t) from DFF
-        self.dff.output(clock)
-    }
-    pub fn run(&mut self, clock_t: &Clock, input: bit, load: bit) -> bit {
-        self.input(clock_t, input, load);
-        self.output(clock_t)
+        Self {
+            dff: Dff::new(),
+            pins: (bit::O, bit::O),
+        }
+    }
+    // the rising edge
+    // if load(t-1) then out(t) = in(t-1)
+    // else out(t) = out(t-1)
+    pub fn tick(&mut self, input: bit, load: bit) {
+        self.dff.tick(mux(self.output(), input, load));
+    }
+    // the falling edge, from which out(t) shows
+    pub fn tock(&mut self) {
+        self.dff.tock();
+    }
+    pub fn output(self) -> bit {
+        self.dff.output()
+    }
+}
+
+impl Chip for Bit {
+    fn name(&self) -> &str {
+        "Bit"
+    }
+
+    fn inputs(&self) -> &[PinDecl] {
+        static PINS: OnceLock<Vec<PinDecl>> = OnceLock::new();
+        declare(&PINS, &[("in", 1), ("load", 1)])
+    }
+
+    fn outputs(&self) -> &[PinDecl] {
+        static PINS: OnceLock<Vec<PinDecl>> = OnceLock::new();
+        declare(&PINS, &[("out", 1)])
+    }
+
+    fn set_input(&mut self, pin: &str, bits: &[bit]) -> Result<(), HardwareError> {
+        check(self, pin, bits)?;
+        match pin {
+            "in" => self.pins.0 = bits[0],
+            _ => self.pins.1 = bits[0],
+        }
+        Ok(())
+    }
+
+    fn get(&self, pin: &str) -> Option<Vec<bit>> {
+        match pin {
+            "in" => Some(vec![self.pins.0]),
+            "load" => Some(vec![self.pins.1]),
+            "out" => Some(vec![self.output()]),
+            _ => None,
+        }
+    }
+
+    fn eval(&mut self) {}
+
+    fn is_clocked(&self) -> bool {
+        true
+    }
+
+    fn tick(&mut self) {
+        let (input, load) = self.pins;
+        Bit::tick(self, input, load);
+    }
+
+    fn tock(&mut self) {
+        Bit::tock(self);
+    }
+
+    fn set_register(&mut self, bits: &[bit]) -> bool {
+        Bit::tick(self, bits[0], bit::I);
+        Bit::tock(self);
+        true
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+// serialized as the master and slave words of the dffs, to keep RAM snapshots small
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(into = "Dffs", from = "Dffs")]
 pub struct Register {
     bits: [Bit; 16],
+    // in and load of the Chip
+    pins: (Word, bit),
+}
+
+#[derive(Serialize, Deserialize)]
+struct Dffs(Word, Word);
+
+impl From<Register> for Dffs {
+    fn from(register: Register) -> Self {
+        let mut master = [bit::O; 16];
+        let mut slave = [bit::O; 16];
+        for (i, b) in register.bits.iter().enumerate() {
+            (master[i], slave[i]) = b.dff.state();
+        }
+        Dffs(Word::new(master), Word::new(slave))
+    }
+}
+
+impl From<Dffs> for Register {
+    fn from(Dffs(master, slave): Dffs) -> Self {
+        let mut register = Register::new();
+        for (i, b) in register.bits.iter_mut().enumerate() {
+            b.dff = Dff::with_state(master[i], slave[i]);
+        }
+        register
+    }
 }
 
 impl Register {
     pub fn new() -> Self {
         Self {
             bits: [Bit::new(); 16],
+            pins: (Word::new([bit::O; 16]), bit::O),
         }
     }
-    pub fn input(&mut self, clock_t: &Clock, input: Word, load: bit) {
-        self.bits[0].input(clock_t, input[0], load);
-        self.bits[1].input(clock_t, input[1], load);
-        self.bits[2].input(clock_t, input[2], load);
-        self.bits[3].input(clock_t, input[3], load);
-        self.bits[4].input(clock_t, input[4], load);
-        self.bits[5].input(clock_t, input[5], load);
-        self.bits[6].input(clock_t, input[6], load);
-        self.bits[7].input(clock_t, input[7], load);
-        self.bits[8].input(clock_t, input[8], load);
-        self.bits[9].input(clock_t, input[9], load);
-        self.bits[10].input(clock_t, input[10], load);
-        self.bits[11].input(clock_t, input[11], load);
-        self.bits[12].input(clock_t, input[12], load);
-        self.bits[13].input(clock_t, input[13], load);
-        self.bits[14].input(clock_t, input[14], load);
-        self.bits[15].input(clock_t, input[15], load);
-    }
-    pub fn output(self, clock: &Clock) -> Word {
+    // the rising edge
+    pub fn tick(&mut self, input: Word, load: bit) {
+        let _profile = profile::chip("Register.input");
+        self.bits[0].tick(input[0], load);
+        self.bits[1].tick(input[1], load);
+        self.bits[2].tick(input[2], load);
+        self.bits[3].tick(input[3], load);
+        self.bits[4].tick(input[4], load);
+        self.bits[5].tick(input[5], load);
+        self.bits[6].tick(input[6], load);
+        self.bits[7].tick(input[7], load);
+        self.bits[8].tick(input[8], load);
+        self.bits[9].tick(input[9], load);
+        self.bits[10].tick(input[10], load);
+        self.bits[11].tick(input[11], load);
+        self.bits[12].tick(input[12], load);
+        self.bits[13].tick(input[13], load);
+        self.bits[14].tick(input[14], load);
+        self.bits[15].tick(input[15], load);
+    }
+    // the falling edge
+    pub fn tock(&mut self) {
+        for b in self.bits.iter_mut() {
+            b.tock();
+        }
+    }
+    pub fn output(self) -> Word {
+        let _profile = profile::chip("Register.output");
         Word::new([
-            self.bits[0].output(clock),
-            self.bits[1].output(clock),
-            self.bits[2].output(clock),
-            self.bits[3].output(clock),
-            self.bits[4].output(clock),
-            self.bits[5].output(clock),
-            self.bits[6].output(clock),
-            self.bits[7].output(clock),
-            self.bits[8].output(clock),
-            self.bits[9].output(clock),
-            self.bits[10].output(clock),
-            self.bits[11].output(clock),
-            self.bits[12].output(clock),
-            self.bits[13].output(clock),
-            self.bits[14].output(clock),
-            self.bits[15].output(clock),
+            self.bits[0].output(),
+            self.bits[1].output(),
+            self.bits[2].output(),
+            self.bits[3].output(),
+            self.bits[4].output(),
+            self.bits[5].output(),
+            self.bits[6].output(),
+            self.bits[7].output(),
+            self.bits[8].output(),
+            self.bits[9].output(),
+            self.bits[10].output(),
+            self.bits[11].output(),
+            self.bits[12].output(),
+            self.bits[13].output(),
+            self.bits[14].output(),
+            self.bits[15].output(),
         ])
     }
-    pub fn run(&mut self, clock_t: &Clock, input: Word, load: bit) -> Word {
-        self.input(clock_t, input, load);
-        self.output(clock_t)
-    }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use bit::{I, O};
+impl Chip for Register {
+    fn name(&self) -> &str {
+        "Register"
+    }
 
-    #[test]
-    fn for_bit() {
-        let mut clock = Clock::new();
-        let mut bit = Bit::new();
+    fn inputs(&self) -> &[PinDecl] {
+        static PINS: OnceLock<Vec<PinDecl>> = OnceLock::new();
+        declare(&PINS, &[("in", 16), ("load", 1)])
+    }
 
-        bit.input(&clock, I, I);
-        // Tick prev: O, cur: I
-        assert_eq!(bit.output(&clock), O);
+    fn outputs(&self) -> &[PinDecl] {
+        static PINS: OnceLock<Vec<PinDecl>> = OnceLock::new();
+        declare(&PINS, &[("out", 16)])
+    }
 
-        clock.next();
-        bit.input(&clock, O, O);
-        // Tock prev: I, cur: I  load=0の時、cur=prevとなるので古い値がが引き継がれてゆく。保存
-        assert_eq!(bit.output(&clock), I);
+    fn set_input(&mut self, pin: &str, bits: &[bit]) -> Result<(), HardwareError> {
+        check(self, pin, bits)?;
+        match pin {
+            "in" => self.pins.0 = to_word(bits),
+            _ => self.pins.1 = bits[0],
+        }
+        Ok(())
+    }
 
-        clock.next();
-        bit.input(&clock, O, O);
-        // Tick prev: I, cur: I
-        assert_eq!(bit.output(&clock), I);
+    fn get(&self, pin: &str) -> Option<Vec<bit>> {
+        match pin {
+            "in" => Some(from_word(self.pins.0)),
+            "load" => Some(vec![self.pins.1]),
+            "out" => Some(from_word(self.output())),
+            _ => None,
+        }
+    }
 
-        clock.next();
-        bit.input(&clock, O, O);
-        // Tock prev: I, cur: I
-        assert_eq!(bit.output(&clock), I);
+    fn eval(&mut self) {}
 
-        clock.next();
-        bit.input(&clock, O, I);
-        // Tick prev: I, cur: O
-        assert_eq!(bit.output(&clock), I);
+    fn is_clocked(&self) -> bool {
+        true
+    }
 
-        clock.next();
-        // Tock prev: O, cur: O
-        bit.input(&clock, O, O);
-        assert_eq!(bit.output(&clock), O);
+    fn tick(&mut self) {
+        let (input, load) = self.pins;
+        Register::tick(self, input, load);
     }
 
+    fn tock(&mut self) {
+        Register::tock(self);
+    }
+
+    fn set_register(&mut self, bits: &[bit]) -> bool {
+        Register::tick(self, to_word(bits), bit::I);
+        Register::tock(self);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bit::{I, O};
+
     #[test]
-    fn for_bit2() {
-        let mut clock = Clock::new();
+    fn for_bit() {
         let mut bit = Bit::new();
 
-        let out1 = bit.run(&clock, I, I);
-        // Tick prev: O, cur: I
-        assert_eq!(out1, O);
+        bit.tick(I, I);
+        // Tick master: I, slave: O
+        assert_eq!(bit.output(), O);
 
-        clock.next();
-        let out2 = bit.run(&clock, O, O);
-        // Tock prev: I, cur: I
-        assert_eq!(out2, I);
+        bit.tock();
+        // Tock master: I, slave: I
+        assert_eq!(bit.output(), I);
 
-        clock.next();
-        let out3 = bit.run(&clock, O, O);
-        // Tick prev: I, cur: I
-        assert_eq!(out3, I);
+        bit.tick(O, O);
+        // Tick master: I, slave: I  load=0の時、master=slaveとなるので古い値がが引き継がれてゆく。保存
+        assert_eq!(bit.output(), I);
 
-        clock.next();
-        let out4 = bit.run(&clock, O, O);
-        // Tock prev: I, cur: I
-        assert_eq!(out4, I);
+        bit.tock();
+        // Tock master: I, slave: I
+        assert_eq!(bit.output(), I);
 
-        clock.next();
-        let out5 = bit.run(&clock, O, I);
-        // Tick prev: I, cur: O
-        assert_eq!(out5, I);
+        bit.tick(O, I);
+        // Tick master: O, slave: I
+        assert_eq!(bit.output(), I);
 
-        clock.next();
-        // Tock prev: O, cur: O
-        let out6 = bit.run(&clock, O, O);
-        assert_eq!(out6, O);
+        bit.tock();
+        // Tock master: O, slave: O
+        assert_eq!(bit.output(), O);
     }
 
     #[test]
     fn for_register() {
         let zero = Word::new([O; 16]);
         let one = Word::new([I; 16]);
-        let mut clock = Clock::new();
         let mut register = Register::new();
 
-        assert_eq!(register.output(&clock), zero);
+        assert_eq!(register.output(), zero);
 
-        register.input(&clock, one, I);
-        assert_eq!(register.output(&clock), zero);
+        register.tick(one, I);
+        assert_eq!(register.output(), zero);
+        register.tock();
+        assert_eq!(register.output(), one);
 
-        clock.next();
-        register.input(&clock, one, O);
-        assert_eq!(register.output(&clock), one);
+        register.tick(zero, O);
+        assert_eq!(register.output(), one);
+        register.tock();
+        assert_eq!(register.output(), one);
 
-        clock.next();
-        register.input(&clock, one, O);
-        assert_eq!(register.output(&clock), one);
-
-        clock.next();
-        register.input(&clock, zero, O);
-        assert_eq!(register.output(&clock), one);
-
-        clock.next();
-        register.input(&clock, zero, I);
-        assert_eq!(register.output(&clock), one);
-
-        clock.next();
-        register.input(&clock, zero, O);
-        assert_eq!(register.output(&clock), zero);
+        register.tick(zero, I);
+        assert_eq!(register.output(), one);
+        register.tock();
+        assert_eq!(register.output(), zero);
     }
 
     #[test]
     fn for_register2() {
         let something = Word::new([O, I, O, I, O, I, O, I, O, I, O, I, O, I, O, I]);
         let zero = Word::new([O; 16]);
-        let mut clock = Clock::new();
         let mut register = Register::new();
 
-        assert_eq!(register.run(&clock, something, I), zero);
-
-        clock.next();
-        assert_eq!(register.run(&clock, something, O), something);
-        clock.next();
-        assert_eq!(register.run(&clock, something, O), something);
-        clock.next();
-        assert_eq!(register.run(&clock, zero, O), something);
-        clock.next();
-        assert_eq!(register.run(&clock, zero, I), something);
-        clock.next();
-        assert_eq!(register.run(&clock, zero, O), zero);
+        // in, load and out after the cycle
+        for (input, load, out) in [
+            (something, I, something),
+            (something, O, something),
+            (zero, O, something),
+            (zero, I, zero),
+            (something, O, zero),
+        ] {
+            register.tick(input, load);
+            register.tock();
+            assert_eq!(register.output(), out);
+        }
+    }
+
+    #[test]
+    fn for_register_serde() {
+        let mut register = Register::new();
+        register.tick(Word::from(5u16), I);
+        // master, slave
+        let json = serde_json::to_string(&register).unwrap();
+        assert_eq!(json, "[5,0]");
+
+        let mut restored: Register = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.output(), Word::from(0u16));
+        restored.tock();
+        assert_eq!(restored.output(), Word::from(5u16));
     }
 }
@@ -1,170 +1,178 @@
 #![allow(dead_code)]
-use std::{
-    fs::File,
-    io::{BufRead, BufReader},
-};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{path::Path, sync::OnceLock};
 
-use crate::base::{
-    dff::Clock,
-    logic::bit::{I, O},
-    logic::{bit, dmux8way, mux8way16, Word},
-    ram::RAM4K,
+use crate::{
+    base::{
+        bus::Bus,
+        chip::{check, declare, Chip},
+        image,
+        logic::bit::{I, O},
+        logic::{bit, Word},
+        ram::Ram,
+    },
+    error::HardwareError,
+    hdl::{from_word, msb_first, parser::PinDecl},
 };
 
 use super::arithmetic::add16;
 
+pub const SIZE: usize = 32768;
+
+// serialized as the registers of the RAM
+#[derive(Serialize, Deserialize)]
+#[serde(transparent)]
 pub struct ROM32K {
-    rams: [RAM4K; 8],
+    ram: Ram<15>,
+    // the address of the Chip
+    #[serde(skip)]
+    address: Bus<15>,
+}
+
+impl ROM32K {
+    // the serialized registers of a snapshot
+    pub(crate) fn restore(&mut self, state: Value) -> Result<(), serde_json::Error> {
+        self.ram.restore(state)
+    }
 }
 
 impl ROM32K {
     pub fn new() -> Self {
         Self {
-            rams: [RAM4K::new(); 8],
+            ram: Ram::<15>::new(),
+            address: Bus::default(),
         }
     }
 
-    // the same as RAMn
-    fn input_to_rams(&mut self, clock_t: &Clock, input: Word, address: [bit; 15]) {
-        let load_bit = dmux8way(I, [address[0], address[1], address[2]]);
-        let register_addr = [
-            address[3],
-            address[4],
-            address[5],
-            address[6],
-            address[7],
-            address[8],
-            address[9],
-            address[10],
-            address[11],
-            address[12],
-            address[13],
-            address[14],
-        ];
-
-        self.rams[0].input(clock_t, input, register_addr, load_bit[0]);
-        self.rams[1].input(clock_t, input, register_addr, load_bit[1]);
-        self.rams[2].input(clock_t, input, register_addr, load_bit[2]);
-        self.rams[3].input(clock_t, input, register_addr, load_bit[3]);
-        self.rams[4].input(clock_t, input, register_addr, load_bit[4]);
-        self.rams[5].input(clock_t, input, register_addr, load_bit[5]);
-        self.rams[6].input(clock_t, input, register_addr, load_bit[6]);
-        self.rams[7].input(clock_t, input, register_addr, load_bit[7]);
-    }
-
     // 読み取り専用
-    pub fn output(&mut self, clock_t: &Clock, address: [bit; 15]) -> Word {
-        let register_address = [
-            address[3],
-            address[4],
-            address[5],
-            address[6],
-            address[7],
-            address[8],
-            address[9],
-            address[10],
-            address[11],
-            address[12],
-            address[13],
-            address[14],
-        ];
-        mux8way16(
-            self.rams[0].output(clock_t, register_address),
-            self.rams[1].output(clock_t, register_address),
-            self.rams[2].output(clock_t, register_address),
-            self.rams[3].output(clock_t, register_address),
-            self.rams[4].output(clock_t, register_address),
-            self.rams[5].output(clock_t, register_address),
-            self.rams[6].output(clock_t, register_address),
-            self.rams[7].output(clock_t, register_address),
-            [address[0], address[1], address[2]],
-        )
+    pub fn output(&self, address: [bit; 15]) -> Word {
+        self.ram.output(address)
     }
 
     // return last address
-    pub fn load(&mut self, filename: &str) -> Word {
-        let clock_t = Clock::new();
-        let file = File::open(filename).expect(&format!("Fail to open {}", filename));
+    // the format is detected as image::read does
+    pub fn load(&mut self, filename: &str) -> Result<Word, HardwareError> {
+        let words = image::read(filename)?;
+        self.load_words(&words)
+            .map_err(|e| e.in_file(Path::new(filename)))
+    }
+
+    pub fn load_words(&mut self, words: &[u16]) -> Result<Word, HardwareError> {
+        check_size(words)?;
 
         let mut line_counter = Word::new([O; 16]);
-        for line_result in BufReader::new(file).lines() {
-            let line: &str = &line_result.expect("couldn't read lines");
-            let instruction = Word::from(line);
-            let address = [
-                line_counter[1],
-                line_counter[2],
-                line_counter[3],
-                line_counter[4],
-                line_counter[5],
-                line_counter[6],
-                line_counter[7],
-                line_counter[8],
-                line_counter[9],
-                line_counter[10],
-                line_counter[11],
-                line_counter[12],
-                line_counter[13],
-                line_counter[14],
-                line_counter[15],
-            ];
-            self.input_to_rams(&clock_t, instruction, address);
+        for word in words {
+            let instruction = Word::from(*word);
+            let address: Bus<15> = Bus::from(line_counter).slice::<1, 15>();
+            // a cycle a word
+            self.ram.tick(instruction, address.bits(), I);
+            self.ram.tock();
             line_counter = add16(
                 line_counter,
                 Word::new([O, O, O, O, O, O, O, O, O, O, O, O, O, O, O, I]),
             )
         }
-        // load=0で仮にinputを呼ぶことでbitのstateをcurrentからprevに移す
-        for i in 0..8 {
-            self.rams[i].input(&clock_t, Word::new([O; 16]), [I; 12], O);
+        Ok(line_counter)
+    }
+}
+
+// read only, so not clocked
+impl Chip for ROM32K {
+    fn name(&self) -> &str {
+        "ROM32K"
+    }
+
+    fn inputs(&self) -> &[PinDecl] {
+        static PINS: OnceLock<Vec<PinDecl>> = OnceLock::new();
+        declare(&PINS, &[("address", 15)])
+    }
+
+    fn outputs(&self) -> &[PinDecl] {
+        static PINS: OnceLock<Vec<PinDecl>> = OnceLock::new();
+        declare(&PINS, &[("out", 16)])
+    }
+
+    fn set_input(&mut self, pin: &str, bits: &[bit]) -> Result<(), HardwareError> {
+        check(self, pin, bits)?;
+        self.address = Bus::new(msb_first(bits));
+        Ok(())
+    }
+
+    fn get(&self, pin: &str) -> Option<Vec<bit>> {
+        match pin {
+            "address" => Some(self.address.iter().rev().copied().collect()),
+            "out" => Some(from_word(self.output(self.address.bits()))),
+            _ => None,
         }
-        line_counter
     }
+
+    fn eval(&mut self) {}
+}
+
+// the program has to fit in the 15 bit addresses of the ROM
+pub fn check_size(words: &[u16]) -> Result<(), HardwareError> {
+    if words.len() > SIZE {
+        return Err(HardwareError::new(&format!(
+            "expected at most {} words of ROM but it was {}",
+            SIZE,
+            words.len()
+        )));
+    }
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
-    use super::ROM32K;
+    use super::{ROM32K, SIZE};
     use crate::base::logic::bit::{I, O};
-    use crate::base::{dff::Clock, logic::Word};
+    use crate::base::logic::Word;
 
     #[test]
     fn for_rom_load() {
-        let clock = Clock::new();
         let mut rom = ROM32K::new();
-        rom.load("src/base/tests/sample.txt");
+        rom.load("src/base/tests/sample.txt").unwrap();
 
-        assert_eq!(rom.output(&clock, [O; 15]), Word::new([O; 16]));
+        assert_eq!(rom.output([O; 15]), Word::new([O; 16]));
         assert_eq!(
-            rom.output(&clock, [O, O, O, O, O, O, O, O, O, O, O, O, O, O, I]),
+            rom.output([O, O, O, O, O, O, O, O, O, O, O, O, O, O, I]),
             Word::new([O, O, O, O, O, O, O, O, O, O, O, O, O, O, O, I])
         );
         assert_eq!(
-            rom.output(&clock, [O, O, O, O, O, O, O, O, O, O, O, O, O, I, O]), // 3行目
+            rom.output([O, O, O, O, O, O, O, O, O, O, O, O, O, I, O]), // 3行目
             Word::new([O, O, O, O, O, O, O, O, O, O, O, I, O, O, O, I])
         );
         assert_eq!(
-            rom.output(&clock, [O, O, O, O, O, O, O, O, O, O, O, O, O, I, I]),
+            rom.output([O, O, O, O, O, O, O, O, O, O, O, O, O, I, I]),
             Word::new([O, O, O, O, O, I, I, I, I, I, I, I, I, I, I, I])
         );
         assert_eq!(
-            rom.output(&clock, [O, O, O, O, O, O, O, O, O, O, O, O, I, O, O]),
+            rom.output([O, O, O, O, O, O, O, O, O, O, O, O, I, O, O]),
             Word::new([O, O, O, O, I, I, I, I, I, I, I, I, I, I, I, I])
         );
         assert_eq!(
-            rom.output(&clock, [O, O, O, O, O, O, O, O, O, O, O, O, I, O, I]),
+            rom.output([O, O, O, O, O, O, O, O, O, O, O, O, I, O, I]),
             Word::new([O, O, O, I, I, I, I, I, I, I, I, I, I, I, I, I])
         );
         assert_eq!(
-            rom.output(&clock, [O, O, O, O, O, O, O, O, O, O, O, O, I, I, O]),
+            rom.output([O, O, O, O, O, O, O, O, O, O, O, O, I, I, O]),
             Word::new([O, O, I, I, I, I, I, I, I, I, I, I, I, I, I, I])
         );
         assert_eq!(
-            rom.output(&clock, [O, O, O, O, O, O, O, O, O, O, O, O, I, I, I]),
+            rom.output([O, O, O, O, O, O, O, O, O, O, O, O, I, I, I]),
             Word::new([O, I, I, I, I, I, I, I, I, I, I, I, I, I, I, I])
         );
         assert_eq!(
-            rom.output(&clock, [O, O, O, O, O, O, O, O, O, O, O, I, O, O, O]),
+            rom.output([O, O, O, O, O, O, O, O, O, O, O, I, O, O, O]),
             Word::new([I, I, I, I, I, I, I, I, I, I, I, I, I, I, I, I])
         );
     }
+
+    #[test]
+    fn for_rom_load_error() {
+        let mut rom = ROM32K::new();
+        assert!(rom.load_words(&vec![0; SIZE + 1]).is_err());
+        let e = rom.load("src/program/missing.txt").unwrap_err();
+        assert_eq!(e.path, Some("src/program/missing.txt".to_string()));
+    }
 }
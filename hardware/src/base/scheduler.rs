@@ -0,0 +1,198 @@
+use std::any::Any;
+
+use crate::base::{
+    chip::Chip,
+    dff::{Clock, ClockState},
+};
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct DomainId(usize);
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct ChipId(usize, usize);
+
+// chips clocked every period cycles of the system clock
+struct Domain {
+    name: String,
+    period: usize,
+    chips: Vec<Box<dyn Chip>>,
+}
+
+/// Owns the system clock and drives its edges to the sequential chips attached to it.
+/// The clock rests at Tock; a cycle is the rising edge (tick) and the falling edge (tock).
+/// A domain of period n sees one edge of each in n system cycles.
+pub struct Scheduler {
+    clock: Clock,
+    // half cycles since the start
+    time: usize,
+    domains: Vec<Domain>,
+}
+
+impl Scheduler {
+    // with the domain "main" of period 1
+    pub fn new() -> Self {
+        let mut scheduler = Self {
+            clock: Clock::new(),
+            time: 0,
+            domains: Vec::new(),
+        };
+        scheduler.add_domain("main", 1);
+        scheduler
+    }
+
+    pub fn main(&self) -> DomainId {
+        DomainId(0)
+    }
+
+    pub fn add_domain(&mut self, name: &str, period: usize) -> DomainId {
+        assert!(period > 0, "the period of {} is 0", name);
+        self.domains.push(Domain {
+            name: name.to_string(),
+            period,
+            chips: Vec::new(),
+        });
+        DomainId(self.domains.len() - 1)
+    }
+
+    pub fn domain(&self, name: &str) -> Option<DomainId> {
+        self.domains
+            .iter()
+            .position(|d| d.name == name)
+            .map(DomainId)
+    }
+
+    pub fn attach(&mut self, domain: DomainId, chip: Box<dyn Chip>) -> ChipId {
+        let chips = &mut self.domains[domain.0].chips;
+        chips.push(chip);
+        ChipId(domain.0, chips.len() - 1)
+    }
+
+    pub fn chip(&self, id: ChipId) -> &dyn Chip {
+        self.domains[id.0].chips[id.1].as_ref()
+    }
+
+    pub fn chip_mut(&mut self, id: ChipId) -> &mut (dyn Chip + 'static) {
+        self.domains[id.0].chips[id.1].as_mut()
+    }
+
+    // the chip as the type it was attached with
+    pub fn get<T: Chip>(&self, id: ChipId) -> &T {
+        (self.chip(id) as &dyn Any)
+            .downcast_ref()
+            .expect("the chip was attached with another type")
+    }
+
+    pub fn get_mut<T: Chip>(&mut self, id: ChipId) -> &mut T {
+        (self.chip_mut(id) as &mut dyn Any)
+            .downcast_mut()
+            .expect("the chip was attached with another type")
+    }
+
+    pub fn clock(&self) -> &Clock {
+        &self.clock
+    }
+
+    pub fn time(&self) -> usize {
+        self.time
+    }
+
+    pub fn cycles(&self) -> usize {
+        self.time / 2
+    }
+
+    // whether the domain gets the edges of the current cycle
+    pub fn is_due(&self, domain: DomainId) -> bool {
+        self.cycles().is_multiple_of(self.domains[domain.0].period)
+    }
+
+    // half a cycle: the next edge to the chips of the domains which are due
+    pub fn step(&mut self) {
+        let cycles = self.cycles();
+        self.clock.next();
+        let rising = self.clock.state == ClockState::Tick;
+        for domain in self.domains.iter_mut() {
+            if !cycles.is_multiple_of(domain.period) {
+                continue;
+            }
+            for chip in domain.chips.iter_mut() {
+                if rising {
+                    chip.tick();
+                } else {
+                    chip.tock();
+                }
+            }
+        }
+        self.time += 1;
+    }
+
+    // to the next rest at Tock
+    pub fn cycle(&mut self) {
+        self.step();
+        if self.clock.state == ClockState::Tick {
+            self.step();
+        }
+    }
+
+    pub fn run(&mut self, cycles: usize) {
+        for _ in 0..cycles {
+            self.cycle();
+        }
+    }
+
+    // settle the combinational outputs of every chip, e.g. after set_input
+    pub fn eval(&mut self) {
+        for domain in self.domains.iter_mut() {
+            for chip in domain.chips.iter_mut() {
+                chip.eval();
+            }
+        }
+    }
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Scheduler::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::base::{
+        chip::builtin,
+        logic::bit::{I, O},
+    };
+
+    #[test]
+    fn for_scheduler() {
+        let mut scheduler = Scheduler::new();
+        let slow = scheduler.add_domain("slow", 2);
+        assert_eq!(scheduler.domain("slow"), Some(slow));
+        let fast = scheduler.attach(scheduler.main(), builtin("Bit").unwrap());
+        let slow = scheduler.attach(slow, builtin("Bit").unwrap());
+        for id in [fast, slow] {
+            let bit = scheduler.chip_mut(id);
+            bit.set_input("in", &[I]).unwrap();
+            bit.set_input("load", &[I]).unwrap();
+        }
+
+        // the outputs change on the falling edge
+        scheduler.step();
+        assert_eq!(scheduler.clock().state, ClockState::Tick);
+        assert_eq!(scheduler.chip(fast).get("out"), Some(vec![O]));
+        scheduler.step();
+        assert_eq!(scheduler.chip(fast).get("out"), Some(vec![I]));
+        assert_eq!(scheduler.chip(slow).get("out"), Some(vec![I]));
+        assert_eq!((scheduler.time(), scheduler.cycles()), (2, 1));
+
+        // the slow domain misses the edges of every other cycle
+        scheduler.chip_mut(fast).set_input("in", &[O]).unwrap();
+        scheduler.chip_mut(slow).set_input("in", &[O]).unwrap();
+        scheduler.cycle();
+        assert_eq!(scheduler.chip(fast).get("out"), Some(vec![O]));
+        assert_eq!(scheduler.chip(slow).get("out"), Some(vec![I]));
+        scheduler.run(1);
+        assert_eq!(scheduler.chip(slow).get("out"), Some(vec![O]));
+        assert_eq!(scheduler.clock().state, ClockState::Tock);
+    }
+}
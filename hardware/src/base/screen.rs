@@ -1,163 +1,426 @@
 #![allow(dead_code)]
-use std::sync::mpsc::Sender;
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+    sync::mpsc::Sender,
+};
 
 use crate::base::logic::{
-    bit::{self, I, O},
-    dmux, mux, Word,
+    bit::{self, I},
+    Word,
+};
+use crate::base::{
+    bus::Bus,
+    chip::{check, Chip},
+    ram::{Pins, Ram},
+};
+use crate::{
+    display::{Frames, Link},
+    error::HardwareError,
+    hdl::parser::PinDecl,
+    profile,
 };
-use crate::base::{dff::Clock, ram::RAM4K};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+
+pub const WIDTH: usize = 512;
+pub const HEIGHT: usize = 256;
+// the words of the screen memory map
+pub const WORDS: usize = WIDTH * HEIGHT / 16;
 
 #[derive(Debug)]
 pub struct Screen {
-    rams: [RAM4K; 2],
-    sender: Option<Sender<String>>,
+    // two RAM4Ks
+    ram: Ram<13>,
+    frames: Frames,
+    pins: Pins<13>,
+}
+
+// only the RAM, without the display
+impl Serialize for Screen {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.ram.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Screen {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ram::<13>::deserialize(deserializer).map(|ram| Self {
+            ram,
+            frames: Frames::new(None),
+            pins: Pins::default(),
+        })
+    }
 }
 
 impl Screen {
     pub fn new(sender: Option<Sender<String>>) -> Self {
         Self {
-            rams: [RAM4K::new(); 2],
-            sender,
+            ram: Ram::<13>::new(),
+            frames: Frames::new(sender),
+            pins: Pins::default(),
         }
     }
 
-    pub fn input(&mut self, clock_t: &Clock, input: Word, address: [bit; 13], load: bit) {
-        let ram_addr = [
-            address[1],
-            address[2],
-            address[3],
-            address[4],
-            address[5],
-            address[6],
-            address[7],
-            address[8],
-            address[9],
-            address[10],
-            address[11],
-            address[12],
-        ];
-        let load_bits = dmux(load, address[0]);
-        self.rams[0].input(clock_t, input, ram_addr, load_bits[0]);
-        self.rams[1].input(clock_t, input, ram_addr, load_bits[1]);
+    // for the keyboard, which reads what the display sends
+    pub fn link(&self) -> Link {
+        self.frames.link()
+    }
+
+    pub fn set_frame_rate(&mut self, frame_rate: u32) {
+        self.frames.set_frame_rate(frame_rate);
+    }
+
+    // send the last changes to the display now
+    pub fn flush(&mut self) {
+        self.frames.flush();
+    }
+
+    // the rising edge
+    pub fn tick(&mut self, input: Word, address: [bit; 13], load: bit) {
+        let _profile = profile::chip("Screen.input");
+        self.ram.tick(input, address, load);
 
         if load == I {
-            self.send_message(input, address);
+            self.frames
+                .write(Bus::from(address).number(), u16::from(input));
         }
     }
 
-    fn send_message(&self, input: Word, address: [bit; 13]) {
-        let digit = 13;
-        let register_index = (0..digit).fold(0, |sum, i| {
-            sum + (address[i].to_string().parse::<usize>().unwrap())
-                * 2usize.pow((digit - 1 - i) as u32)
-        });
-        let y = register_index / 32;
-        let x = register_index % 32;
-        let message = format!(
-            "{{\"register_index\":{},\"x\":{},\"y\":{},\"input\":\"{}\"}}",
-            register_index, x, y, input
+    // the falling edge
+    pub fn tock(&mut self) {
+        self.ram.tock();
+    }
+
+    // once a cycle, to send a frame to the display when one is due
+    pub fn poll(&mut self) {
+        self.frames.tick();
+    }
+
+    pub fn output(&self, address: [bit; 13]) -> Word {
+        let _profile = profile::chip("Screen.output");
+        self.ram.output(address)
+    }
+
+    // take the serialized RAM of a snapshot and redraw the display
+    pub(crate) fn restore(&mut self, state: Value) -> Result<(), serde_json::Error> {
+        self.ram.restore(state)?;
+        let framebuffer = self.framebuffer();
+        self.frames.write_all(framebuffer.words());
+        Ok(())
+    }
+
+    // the pixels of the RAM, without the display
+    pub fn framebuffer(&self) -> Framebuffer {
+        let words = self
+            .ram
+            .registers()
+            .map(|register| u16::from(register.output()))
+            .collect();
+        Framebuffer::new(words)
+    }
+
+    pub fn print(&self) {
+        print!("{}", self.framebuffer().to_blocks());
+    }
+}
+
+impl Chip for Screen {
+    fn name(&self) -> &str {
+        "Screen"
+    }
+
+    fn inputs(&self) -> &[PinDecl] {
+        Pins::<13>::inputs()
+    }
+
+    fn outputs(&self) -> &[PinDecl] {
+        Pins::<13>::outputs()
+    }
+
+    fn set_input(&mut self, pin: &str, bits: &[bit]) -> Result<(), HardwareError> {
+        check(self, pin, bits)?;
+        self.pins.set(pin, bits);
+        Ok(())
+    }
+
+    fn get(&self, pin: &str) -> Option<Vec<bit>> {
+        self.pins.get(&self.ram, pin)
+    }
+
+    fn eval(&mut self) {}
+
+    fn is_clocked(&self) -> bool {
+        true
+    }
+
+    // to the display as well as to the RAM
+    fn tick(&mut self) {
+        let Pins {
+            input,
+            load,
+            address,
+        } = self.pins;
+        Screen::tick(self, input, address.bits(), load);
+    }
+
+    fn tock(&mut self) {
+        Screen::tock(self);
+    }
+
+    fn follows(&self, output: &str, input: &str) -> bool {
+        Pins::<13>::follows(output, input)
+    }
+}
+
+/// The 512x256 black and white pixels of the screen memory map.
+/// Pixel (x, y) is bit x % 16 of word 32 * y + x / 16, counted from the LSB.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Framebuffer {
+    words: Vec<u16>,
+}
+
+impl Framebuffer {
+    pub fn new(words: Vec<u16>) -> Self {
+        assert_eq!(
+            words.len(),
+            WORDS,
+            "expected {} words of screen but it was {}",
+            WORDS,
+            words.len()
         );
+        Self { words }
+    }
+
+    pub fn words(&self) -> &[u16] {
+        &self.words
+    }
 
-        if let Some(ref tx) = self.sender {
-            tx.send(message).unwrap();
+    // true for black
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        assert!(x < WIDTH && y < HEIGHT, "({}, {}) is off the screen", x, y);
+        self.words[y * WIDTH / 16 + x / 16] & (1 << (x % 16)) != 0
+    }
+
+    // the pixels of each row from the left
+    pub fn rows(&self) -> impl Iterator<Item = Vec<bool>> + '_ {
+        (0..HEIGHT).map(move |y| (0..WIDTH).map(|x| self.pixel(x, y)).collect())
+    }
+
+    // binary PBM, where 1 is black like the Hack screen
+    pub fn write_pbm<W: Write>(&self, writer: W) -> io::Result<()> {
+        let mut writer = BufWriter::new(writer);
+        write!(writer, "P4\n{} {}\n", WIDTH, HEIGHT)?;
+        for row in self.rows() {
+            let bytes: Vec<u8> = row
+                .chunks(8)
+                .map(|pixels| {
+                    pixels
+                        .iter()
+                        .fold(0, |byte, pixel| (byte << 1) | *pixel as u8)
+                })
+                .collect();
+            writer.write_all(&bytes)?;
         }
+        writer.flush()
     }
 
-    pub fn output(&self, clock_t: &Clock, address: [bit; 13]) -> Word {
-        let ram_addr = [
-            address[1],
-            address[2],
-            address[3],
-            address[4],
-            address[5],
-            address[6],
-            address[7],
-            address[8],
-            address[9],
-            address[10],
-            address[11],
-            address[12],
-        ];
-        let out1 = self.rams[0].output(clock_t, ram_addr);
-        let out2 = self.rams[1].output(clock_t, ram_addr);
-        Word::new([
-            mux(out1[0], out2[0], address[0]),
-            mux(out1[1], out2[1], address[0]),
-            mux(out1[2], out2[2], address[0]),
-            mux(out1[3], out2[3], address[0]),
-            mux(out1[4], out2[4], address[0]),
-            mux(out1[5], out2[5], address[0]),
-            mux(out1[6], out2[6], address[0]),
-            mux(out1[7], out2[7], address[0]),
-            mux(out1[8], out2[8], address[0]),
-            mux(out1[9], out2[9], address[0]),
-            mux(out1[10], out2[10], address[0]),
-            mux(out1[11], out2[11], address[0]),
-            mux(out1[12], out2[12], address[0]),
-            mux(out1[13], out2[13], address[0]),
-            mux(out1[14], out2[14], address[0]),
-            mux(out1[15], out2[15], address[0]),
-        ])
-    }
-
-    pub fn print(self) {
-        // wip
-        let clock = Clock::new();
-        println!(
-            "{}{}",
-            self.rams[0]
-                .output(&clock, [O, O, O, O, O, O, O, O, O, O, O, O])
-                .to_string(),
-            self.rams[0]
-                .output(&clock, [O, O, O, O, O, O, O, O, O, O, O, I])
-                .to_string()
-        );
+    // the binary PBM of write_pbm, e.g. a golden image
+    pub fn read_pbm<R: Read>(reader: R) -> io::Result<Self> {
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+        let mut bytes = Vec::new();
+        BufReader::new(reader).read_to_end(&mut bytes)?;
+
+        // P4, the width and the height, with comments, then a whitespace before the pixels
+        let mut header = Vec::new();
+        let mut i = 0;
+        while header.len() < 3 {
+            while i < bytes.len() && (bytes[i].is_ascii_whitespace() || bytes[i] == b'#') {
+                if bytes[i] == b'#' {
+                    while i < bytes.len() && bytes[i] != b'\n' {
+                        i += 1;
+                    }
+                } else {
+                    i += 1;
+                }
+            }
+            let start = i;
+            while i < bytes.len() && !bytes[i].is_ascii_whitespace() {
+                i += 1;
+            }
+            if start == i {
+                return Err(invalid("expected a PBM header".to_string()));
+            }
+            header.push(String::from_utf8_lossy(&bytes[start..i]).to_string());
+        }
+        let expected = ["P4".to_string(), WIDTH.to_string(), HEIGHT.to_string()];
+        if header != expected {
+            return Err(invalid(format!(
+                "expected a {}x{} binary PBM but the header was {}",
+                WIDTH,
+                HEIGHT,
+                header.join(" ")
+            )));
+        }
+        let pixels = bytes.get(i + 1..).unwrap_or(&[]);
+        if pixels.len() != WIDTH * HEIGHT / 8 {
+            return Err(invalid(format!(
+                "expected {} bytes of pixels but it was {}",
+                WIDTH * HEIGHT / 8,
+                pixels.len()
+            )));
+        }
+
+        // each byte is 8 pixels from the left, so the bits are reversed in the words
+        let words = pixels
+            .chunks(2)
+            .map(|pair| u16::from(pair[0].reverse_bits()) | u16::from(pair[1].reverse_bits()) << 8)
+            .collect();
+        Ok(Self::new(words))
+    }
+
+    // binary PGM, black on white
+    pub fn write_pgm<W: Write>(&self, writer: W) -> io::Result<()> {
+        let mut writer = BufWriter::new(writer);
+        write!(writer, "P5\n{} {}\n255\n", WIDTH, HEIGHT)?;
+        for row in self.rows() {
+            let bytes: Vec<u8> = row
+                .iter()
+                .map(|pixel| if *pixel { 0 } else { 255 })
+                .collect();
+            writer.write_all(&bytes)?;
+        }
+        writer.flush()
+    }
+
+    // .pbm or .pgm by the extension
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("pbm") => self.write_pbm(File::create(path)?),
+            Some("pgm") => self.write_pgm(File::create(path)?),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("expected a .pbm or .pgm path but it was {:?}", path),
+            )),
+        }
+    }
+
+    // a line per row, # for black and . for white
+    pub fn to_ascii(&self) -> String {
+        let mut text = String::with_capacity((WIDTH + 1) * HEIGHT);
+        for row in self.rows() {
+            text.extend(row.iter().map(|pixel| if *pixel { '#' } else { '.' }));
+            text.push('\n');
+        }
+        text
+    }
+
+    // a line per two rows with half blocks, to fit a terminal better
+    pub fn to_blocks(&self) -> String {
+        let mut text = String::new();
+        for y in (0..HEIGHT).step_by(2) {
+            text.extend(
+                (0..WIDTH).map(|x| match (self.pixel(x, y), self.pixel(x, y + 1)) {
+                    (false, false) => ' ',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (true, true) => '█',
+                }),
+            );
+            text.push('\n');
+        }
+        text
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::base::logic::bit::O;
 
     #[test]
     fn for_screen() {
-        let mut clock = Clock::new();
         let mut screen = Screen::new(None);
         let word1 = Word::new([I; 16]);
 
-        screen.input(&clock, word1, [O, O, O, O, O, O, O, O, O, O, O, O, O], I);
-        clock.next();
-        clock.next();
-        screen.input(&clock, word1, [O, O, O, O, O, O, O, O, O, O, O, O, I], I);
+        screen.tick(word1, [O, O, O, O, O, O, O, O, O, O, O, O, O], I);
+        screen.tock();
+        screen.tick(word1, [O, O, O, O, O, O, O, O, O, O, O, O, I], I);
         assert_eq!(
-            screen.output(&clock, [O, O, O, O, O, O, O, O, O, O, O, O, O]),
+            screen.output([O, O, O, O, O, O, O, O, O, O, O, O, O]),
             word1
         );
-        clock.next();
-        clock.next();
-        screen.input(&clock, word1, [O, O, O, O, O, O, O, O, O, O, O, I, O], O);
+        screen.tock();
+        screen.tick(word1, [O, O, O, O, O, O, O, O, O, O, O, I, O], O);
         assert_eq!(
-            screen.output(&clock, [O, O, O, O, O, O, O, O, O, O, O, O, O]),
+            screen.output([O, O, O, O, O, O, O, O, O, O, O, O, O]),
             word1
         );
         assert_eq!(
-            screen.output(&clock, [O, O, O, O, O, O, O, O, O, O, O, O, I]),
+            screen.output([O, O, O, O, O, O, O, O, O, O, O, O, I]),
             word1
         );
-        clock.next();
-        clock.next();
-        screen.input(&clock, word1, [O, O, O, O, O, O, O, O, O, O, O, I, O], O);
+        screen.tock();
+        screen.tick(word1, [O, O, O, O, O, O, O, O, O, O, O, I, O], O);
         assert_eq!(
-            screen.output(&clock, [O, O, O, O, O, O, O, O, O, O, O, O, O]),
+            screen.output([O, O, O, O, O, O, O, O, O, O, O, O, O]),
             word1
         );
         assert_eq!(
-            screen.output(&clock, [O, O, O, O, O, O, O, O, O, O, O, O, I]),
+            screen.output([O, O, O, O, O, O, O, O, O, O, O, O, I]),
             word1
         );
         assert_eq!(
-            screen.output(&clock, [O, O, O, O, O, O, O, O, O, O, O, I, O]),
+            screen.output([O, O, O, O, O, O, O, O, O, O, O, I, O]),
             Word::new([O; 16])
         );
     }
+
+    #[test]
+    fn for_framebuffer() {
+        let mut screen = Screen::new(None);
+        // the first two pixels of the first row, and the last pixel of the second row
+        screen.tick(Word::from(0b11u16), [O; 13], I);
+        screen.tock();
+        let address = [O, O, O, O, O, O, O, I, I, I, I, I, I];
+        screen.tick(Word::from(0x8000u16), address, I);
+        screen.tock();
+        let framebuffer = screen.framebuffer();
+        assert!(framebuffer.pixel(0, 0) && framebuffer.pixel(1, 0));
+        assert!(!framebuffer.pixel(2, 0));
+        assert!(framebuffer.pixel(511, 1));
+        assert_eq!(framebuffer.words()[63], 0x8000);
+        assert_eq!(
+            framebuffer.rows().filter(|row| row.contains(&true)).count(),
+            2
+        );
+
+        let mut pbm = Vec::new();
+        framebuffer.write_pbm(&mut pbm).unwrap();
+        assert!(pbm.starts_with(b"P4\n512 256\n"));
+        assert_eq!(pbm.len(), 11 + 64 * 256);
+        assert_eq!(pbm[11], 0b1100_0000);
+        assert_eq!(pbm[11 + 64 + 63], 1);
+
+        assert_eq!(Framebuffer::read_pbm(&pbm[..]).unwrap(), framebuffer);
+        assert!(Framebuffer::read_pbm(&pbm[..pbm.len() - 1]).is_err());
+        let mut comment = b"P4\n# rect\n".to_vec();
+        comment.extend_from_slice(&pbm[3..]);
+        assert_eq!(Framebuffer::read_pbm(&comment[..]).unwrap(), framebuffer);
+
+        let mut pgm = Vec::new();
+        framebuffer.write_pgm(&mut pgm).unwrap();
+        assert_eq!(pgm.len(), 15 + 512 * 256);
+        assert_eq!(&pgm[15..18], &[0, 0, 255]);
+
+        let ascii = framebuffer.to_ascii();
+        assert!(ascii.starts_with("##..."));
+        assert_eq!(ascii.lines().count(), 256);
+        let blocks = framebuffer.to_blocks();
+        assert!(blocks.starts_with("▀▀  "));
+        assert_eq!(blocks.lines().next().unwrap().chars().last(), Some('▄'));
+        assert_eq!(blocks.lines().count(), 128);
+    }
 }
@@ -1,165 +1,564 @@
 #![allow(dead_code)]
+pub mod device;
+pub mod fast;
+pub mod gate;
+pub mod golden;
+pub mod hdl;
+pub mod keys;
+pub mod script;
+pub mod snapshot;
+pub mod trace;
+
 use std::{
     convert::TryInto,
-    sync::mpsc::{Receiver, Sender},
+    io::{self, Write},
+    mem,
+    ops::Range,
+    path::Path,
+    sync::{
+        mpsc::{Receiver, Sender},
+        OnceLock,
+    },
 };
 
 use crate::{
     base::{
-        arithmetic::add16,
-        cpu::CPU,
-        dff::Clock,
+        chip::{check, declare, Chip},
+        image,
         keyboard::Keyboard,
         logic::bit::{I, O},
-        logic::{and, bit, mux4way16, not, xor, Word},
+        logic::{and, bit, Word},
         ram::RAM16K,
-        rom::ROM32K,
-        screen::Screen,
+        screen::{Framebuffer, Screen},
+    },
+    computer::{
+        device::Device,
+        fast::FastMachine,
+        gate::GateMachine,
+        keys::KeyScript,
+        snapshot::SnapshotError,
+        trace::{Format, Record, TraceWriter},
     },
+    display::Link,
+    error::HardwareError,
+    hdl::{from_word, msb_first, parser::PinDecl, to_word},
+    protocol::Control,
     util::str_to_binary,
 };
+use serde::{
+    ser::{Error as _, SerializeMap},
+    Serialize, Serializer,
+};
+use serde_json::Value;
+
+pub const SCREEN: u16 = 16384;
+pub const KBD: u16 = 24576;
 
+// a device and the addresses it answers
+struct Mapped {
+    name: String,
+    range: Range<u16>,
+    device: Box<dyn Device>,
+}
+
+/// The data memory: devices mapped to ranges of the 15 bit address space.
+/// Addresses which no device answers read 0.
 pub struct Memory {
-    ram: RAM16K,
-    screen: Screen,
-    keyboard: Keyboard,
+    devices: Vec<Mapped>,
+    // the controls of the display
+    link: Link,
+    // in, load and address of the Chip
+    pins: (Word, bit, [bit; 15]),
 }
 
 impl Memory {
+    // the Hack map: RAM16K, then the screen and the keyboard
     pub fn new(channel: Option<(Sender<String>, Receiver<String>)>) -> Self {
         let (tx, rx) = match channel {
             Some(tuple) => (Some(tuple.0), Some(tuple.1)),
             None => (None, None),
         };
+        let screen = Screen::new(tx);
+        let keyboard = Keyboard::with_link(rx, screen.link());
+        let mut memory = Self::empty();
+        memory.link = screen.link();
+        memory.attach("ram", 0..SCREEN, Box::new(RAM16K::new()));
+        memory.attach("screen", SCREEN..KBD, Box::new(screen));
+        // the keyboard answers the rest of the addresses, as the mux of the Memory chip does
+        memory.attach("keyboard", KBD..0x8000, Box::new(keyboard));
+        memory
+    }
+
+    pub fn empty() -> Self {
         Self {
-            ram: RAM16K::new(),
-            screen: Screen::new(tx),
-            keyboard: Keyboard::new(rx),
+            devices: Vec::new(),
+            link: Link::default(),
+            pins: (Word::new([O; 16]), O, [O; 15]),
         }
     }
 
-    pub fn input(&mut self, clock_t: &Clock, input: Word, address: [bit; 15], load: bit) {
-        self.ram.input(
-            clock_t,
-            input,
-            [
-                address[1],
-                address[2],
-                address[3],
-                address[4],
-                address[5],
-                address[6],
-                address[7],
-                address[8],
-                address[9],
-                address[10],
-                address[11],
-                address[12],
-                address[13],
-                address[14],
-            ],
-            and(not(address[0]), load),
+    // The name is the key of the device in snapshots.
+    pub fn attach(&mut self, name: &str, range: Range<u16>, device: Box<dyn Device>) {
+        assert!(
+            range.start < range.end && range.end <= 0x8000,
+            "expected a range of 15 bit addresses but it was {:?}",
+            range
         );
-        self.screen.input(
-            clock_t,
-            input,
-            [
-                address[2],
-                address[3],
-                address[4],
-                address[5],
-                address[6],
-                address[7],
-                address[8],
-                address[9],
-                address[10],
-                address[11],
-                address[12],
-                address[13],
-                address[14],
-            ],
-            and(and(address[0], not(address[1])), load),
+        if let Some(mapped) = self
+            .devices
+            .iter()
+            .find(|m| m.range.start < range.end && range.start < m.range.end)
+        {
+            panic!(
+                "{} at {:?} overlaps {} at {:?}",
+                name, range, mapped.name, mapped.range
+            );
+        }
+        assert!(
+            self.devices.iter().all(|m| m.name != name),
+            "{} is already attached",
+            name
         );
-        self.keyboard.input();
+        self.devices.push(Mapped {
+            name: name.to_string(),
+            range,
+            device,
+        });
+    }
+
+    // the rising edge
+    pub fn tick(&mut self, input: Word, address: [bit; 15], load: bit) {
+        let address = to_address(address);
+        // every device is clocked, but only the addressed one loads
+        for mapped in self.devices.iter_mut() {
+            let (offset, selected) = mapped.offset(address);
+            mapped.device.write(offset, input, and(selected, load));
+        }
+        self.poll();
+    }
+
+    // the falling edge
+    pub fn tock(&mut self) {
+        for mapped in self.devices.iter_mut() {
+            mapped.device.tock();
+        }
+    }
+
+    // the inputs of the next tick as a Chip
+    pub(crate) fn set_pins(&mut self, input: Word, address: [bit; 15], load: bit) {
+        self.pins = (input, load, address);
     }
-    pub fn output(&self, clock_t: &Clock, address: [bit; 15]) -> Word {
-        let ram_out = self.ram.output(
-            clock_t,
-            [
-                address[1],
-                address[2],
-                address[3],
-                address[4],
-                address[5],
-                address[6],
-                address[7],
-                address[8],
-                address[9],
-                address[10],
-                address[11],
-                address[12],
-                address[13],
-                address[14],
-            ],
-        );
-        let screen_out = self.screen.output(
-            clock_t,
-            [
-                address[2],
-                address[3],
-                address[4],
-                address[5],
-                address[6],
-                address[7],
-                address[8],
-                address[9],
-                address[10],
-                address[11],
-                address[12],
-                address[13],
-                address[14],
-            ],
-        );
 
-        let keyboard_out = self.keyboard.output();
-        mux4way16(
-            ram_out,
-            ram_out,
-            screen_out,
-            keyboard_out,
-            [address[0], address[1]],
+    // poll the devices without a write, e.g. while the machine waits
+    pub fn poll(&mut self) {
+        for mapped in self.devices.iter_mut() {
+            mapped.device.poll();
+        }
+    }
+
+    pub fn take_controls(&mut self) -> Vec<Control> {
+        self.link.take_controls()
+    }
+
+    pub fn output(&self, address: [bit; 15]) -> Word {
+        let address = to_address(address);
+        match self.devices.iter().find(|m| m.range.contains(&address)) {
+            Some(mapped) => mapped.device.read(address - mapped.range.start),
+            None => Word::new([O; 16]),
+        }
+    }
+
+    // the pixels of the screen device, or else of the words at SCREEN
+    pub fn framebuffer(&self) -> Framebuffer {
+        if let Some(framebuffer) = self.devices.iter().find_map(|m| m.device.framebuffer()) {
+            return framebuffer;
+        }
+        Framebuffer::new(
+            (SCREEN..KBD)
+                .map(|address| u16::from(self.output(from_address(address))))
+                .collect(),
         )
     }
+
+    pub fn set_frame_rate(&mut self, frame_rate: u32) {
+        for mapped in self.devices.iter_mut() {
+            mapped.device.set_frame_rate(frame_rate);
+        }
+    }
+
+    pub fn flush(&mut self) {
+        for mapped in self.devices.iter_mut() {
+            mapped.device.flush();
+        }
+    }
+
+    // from outside the program, e.g. a key or a test input
+    pub fn set(&mut self, address: u16, input: Word) {
+        if let Some(mapped) = self.devices.iter_mut().find(|m| m.range.contains(&address)) {
+            mapped.device.set(address - mapped.range.start, input);
+        }
+    }
+
+    pub fn set_keyboard(&mut self, code: Word) {
+        self.set(KBD, code);
+    }
+
+    // take the serialized state of a snapshot, but keep the channel
+    pub fn restore(&mut self, mut state: Value) -> Result<(), serde_json::Error> {
+        for mapped in self.devices.iter_mut() {
+            mapped.device.restore(state[&mapped.name].take())?;
+        }
+        Ok(())
+    }
+}
+
+impl Mapped {
+    // the address within the device, and whether the device answers it
+    fn offset(&self, address: u16) -> (u16, bit) {
+        if self.range.contains(&address) {
+            (address - self.range.start, I)
+        } else {
+            (0, O)
+        }
+    }
+}
+
+impl Chip for Memory {
+    fn name(&self) -> &str {
+        "Memory"
+    }
+
+    fn inputs(&self) -> &[PinDecl] {
+        static PINS: OnceLock<Vec<PinDecl>> = OnceLock::new();
+        declare(&PINS, &[("in", 16), ("load", 1), ("address", 15)])
+    }
+
+    fn outputs(&self) -> &[PinDecl] {
+        static PINS: OnceLock<Vec<PinDecl>> = OnceLock::new();
+        declare(&PINS, &[("out", 16)])
+    }
+
+    fn set_input(&mut self, pin: &str, bits: &[bit]) -> Result<(), HardwareError> {
+        check(self, pin, bits)?;
+        match pin {
+            "in" => self.pins.0 = to_word(bits),
+            "load" => self.pins.1 = bits[0],
+            _ => self.pins.2 = msb_first(bits),
+        }
+        Ok(())
+    }
+
+    fn get(&self, pin: &str) -> Option<Vec<bit>> {
+        let (input, load, address) = self.pins;
+        match pin {
+            "in" => Some(from_word(input)),
+            "load" => Some(vec![load]),
+            "address" => Some(address.iter().rev().copied().collect()),
+            "out" => Some(from_word(self.output(address))),
+            _ => None,
+        }
+    }
+
+    fn eval(&mut self) {}
+
+    fn is_clocked(&self) -> bool {
+        true
+    }
+
+    fn tick(&mut self) {
+        let (input, load, address) = self.pins;
+        Memory::tick(self, input, address, load);
+    }
+
+    fn tock(&mut self) {
+        Memory::tock(self);
+    }
+}
+
+// a map from the names of the devices to their snapshots
+impl Serialize for Memory {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.devices.len()))?;
+        for mapped in self.devices.iter() {
+            let state = mapped.device.snapshot().map_err(S::Error::custom)?;
+            map.serialize_entry(&mapped.name, &state)?;
+        }
+        map.end()
+    }
+}
+
+/// Selects how a `Computer` evaluates instructions.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Backend {
+    // CPU, ROM32K and RAM16K built on nand
+    Gate,
+    // native u16 registers and memory
+    Fast,
+}
+
+/// A Hack machine: ROM, CPU and memory-mapped RAM, screen and keyboard.
+pub trait Machine {
+    // return last address
+    // in any format of image::read
+    fn load(&mut self, filename: &str) -> Result<Word, HardwareError> {
+        let words = image::read(filename)?;
+        self.load_words(&words)
+            .map_err(|e| e.in_file(Path::new(filename)))
+    }
+    fn load_words(&mut self, words: &[u16]) -> Result<Word, HardwareError>;
+    // execute the instruction at pc and advance to the next one
+    // -> writeM
+    fn execute(&mut self, reset: bit) -> bit;
+    fn pc(&self) -> [bit; 15];
+    fn a(&self) -> Word;
+    fn d(&self) -> Word;
+    fn memory_out(&self, address: [bit; 15]) -> Word;
+    // overwrite the state, e.g. from test scripts
+    fn set_memory(&mut self, address: [bit; 15], input: Word);
+    fn set_pc(&mut self, pc: [bit; 15]);
+    fn set_a(&mut self, input: Word);
+    fn set_d(&mut self, input: Word);
+    // keep the memory accesses of execute for take_events while on
+    fn record_events(&mut self, _on: bool) {}
+    fn take_events(&mut self) -> Vec<Event> {
+        Vec::new()
+    }
+    // keep the CPU pins of execute for take_record while on
+    fn record_cycles(&mut self, _on: bool) {}
+    fn take_record(&mut self) -> Option<Record> {
+        None
+    }
+    // the whole state as JSON for Computer::save, and back
+    fn snapshot(&self) -> Result<Value, SnapshotError> {
+        Err(SnapshotError("the machine can't be saved".to_string()))
+    }
+    fn restore(&mut self, _state: Value) -> Result<(), SnapshotError> {
+        Err(SnapshotError("the machine can't be restored".to_string()))
+    }
+    // the pixels of the screen memory map
+    fn framebuffer(&self) -> Framebuffer {
+        Framebuffer::new(
+            (SCREEN..KBD)
+                .map(|address| u16::from(self.memory_out(from_address(address))))
+                .collect(),
+        )
+    }
+    // how often the screen is sent to the display, and sending the rest now
+    fn set_frame_rate(&mut self, _frame_rate: u32) {}
+    fn flush_display(&mut self) {}
+    // read and answer the display without executing, and what it asked the runner to do
+    fn poll_display(&mut self) {}
+    fn take_controls(&mut self) -> Vec<Control> {
+        Vec::new()
+    }
+    // sample the signals of every clock phase into the writer, until None finishes it
+    fn dump_vcd(&mut self, writer: Option<Box<dyn Write>>) -> io::Result<()> {
+        match writer {
+            Some(_) => Err(io::Error::other("the machine has no clock phases")),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A memory access of an instruction, or the PC reaching an address.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Event {
+    // the instruction at pc reads M
+    Read { pc: u16, address: u16 },
+    // the instruction at pc writes M
+    Write { pc: u16, address: u16, value: Word },
+    // the instruction at the address is executed next
+    Pc(u16),
+}
+
+// the events of a machine while it's recording
+#[derive(Debug, Default)]
+pub(crate) struct Events(Option<Vec<Event>>);
+
+impl Events {
+    pub(crate) fn record(&mut self, on: bool) {
+        match (on, &self.0) {
+            (true, None) => self.0 = Some(Vec::new()),
+            (false, Some(_)) => self.0 = None,
+            _ => (),
+        }
+    }
+
+    pub(crate) fn is_recording(&self) -> bool {
+        self.0.is_some()
+    }
+
+    pub(crate) fn push(&mut self, event: Event) {
+        if let Some(events) = self.0.as_mut() {
+            events.push(event);
+        }
+    }
+
+    pub(crate) fn take(&mut self) -> Vec<Event> {
+        self.0.as_mut().map(mem::take).unwrap_or_default()
+    }
+}
+
+/// The events a callback of `Computer::watch` is called on.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Watch {
+    // e.g. Read(KBD..KBD + 1) or Write(SCREEN..KBD)
+    Read(Range<u16>),
+    Write(Range<u16>),
+    Pc(u16),
+}
+
+impl Watch {
+    fn matches(&self, event: &Event) -> bool {
+        match (self, event) {
+            (Watch::Read(range), Event::Read { address, .. }) => range.contains(address),
+            (Watch::Write(range), Event::Write { address, .. }) => range.contains(address),
+            (Watch::Pc(watch), Event::Pc(pc)) => watch == pc,
+            _ => false,
+        }
+    }
+}
+
+/// Whether a watch callback lets `Computer` go on.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Action {
+    Continue,
+    Stop,
+}
+
+type Callback = Box<dyn FnMut(&mut Computer, &Event) -> Action>;
+
+/// Why `Computer` stopped executing.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum RunOutcome {
+    // the next instruction is past the end of the program
+    EndOfProgram,
+    // the program jumps back to itself without writing memory, e.g. (END) @END 0;JMP
+    Halted,
+    // run_for executed all of its cycles
+    CycleLimit,
+    // run_until's predicate returned true
+    Breakpoint,
+    // a watch callback returned Action::Stop
+    Stopped,
 }
 
 pub struct Computer {
-    rom: ROM32K,
-    cpu: CPU,
-    memory: Memory,
-    // println
-    debug: bool,
+    machine: Box<dyn Machine>,
+    instruction_num: Word,
+    reset: bit,
+    // (pc, A, D) before each step since memory was last written
+    history: Vec<([bit; 15], Word, Word)>,
+    watches: Vec<(usize, Watch, Callback)>,
+    // ids removed while their callbacks are called
+    unwatched: Vec<usize>,
+    next_watch: usize,
+    tracer: Option<TraceWriter>,
+    // the instructions executed since load
+    cycles: u64,
+    keys: Option<KeyScript>,
 }
 
 impl Computer {
     pub fn new(channel: Option<(Sender<String>, Receiver<String>)>, debug: bool) -> Self {
+        Computer::with_backend(channel, debug, Backend::Gate)
+    }
+
+    pub fn with_backend(
+        channel: Option<(Sender<String>, Receiver<String>)>,
+        debug: bool,
+        backend: Backend,
+    ) -> Self {
+        let machine: Box<dyn Machine> = match backend {
+            Backend::Gate => Box::new(GateMachine::new(channel, debug)),
+            Backend::Fast => Box::new(FastMachine::new(channel, debug)),
+        };
+        Computer::with_machine(machine)
+    }
+
+    // e.g. a CPU written in HDL
+    pub fn with_machine(machine: Box<dyn Machine>) -> Self {
         Self {
-            rom: ROM32K::new(),
-            cpu: CPU::new(),
-            memory: Memory::new(channel),
-            debug,
+            machine,
+            instruction_num: Word::new([O; 16]),
+            reset: O,
+            history: Vec::new(),
+            watches: Vec::new(),
+            unwatched: Vec::new(),
+            next_watch: 0,
+            tracer: None,
+            cycles: 0,
+            keys: None,
         }
     }
 
-    pub fn memory_out(&self, address_str: &str) -> Word {
-        let address: [bit; 15] =
-            str_to_binary(address_str)
-                .try_into()
-                .unwrap_or_else(|v: Vec<bit>| {
-                    panic!("Expected a Vec of length {} but it was {}", 15, v.len())
-                });
-        let mut clock = Clock::new();
-        clock.next();
-        self.memory.output(&clock, address)
+    pub fn pc(&self) -> [bit; 15] {
+        self.machine.pc()
+    }
+
+    pub fn a(&self) -> Word {
+        self.machine.a()
+    }
+
+    pub fn d(&self) -> Word {
+        self.machine.d()
+    }
+
+    pub fn memory_out(&self, address_str: &str) -> Result<Word, HardwareError> {
+        let address: [bit; 15] = str_to_binary(address_str)?
+            .try_into()
+            .map_err(|_| HardwareError::at(0, address_str, "expected an address of 15 bits"))?;
+        Ok(self.machine.memory_out(address))
+    }
+
+    pub fn memory(&self, address: u16) -> Word {
+        self.machine.memory_out(from_address(address))
+    }
+
+    // e.g. to save what a program drew without the display
+    pub fn framebuffer(&self) -> Framebuffer {
+        self.machine.framebuffer()
+    }
+
+    // frames per second sent to the display, or 0 for every change
+    pub fn set_frame_rate(&mut self, frame_rate: u32) {
+        self.machine.set_frame_rate(frame_rate);
+    }
+
+    // the display gets the rest when a run ends, or now
+    pub fn flush_display(&mut self) {
+        self.machine.flush_display();
+    }
+
+    // e.g. while paused, so that the display is still answered
+    pub fn poll_display(&mut self) {
+        self.machine.poll_display();
+    }
+
+    // run, pause, reset and load from the display since the last call, for the runner to do
+    pub fn take_controls(&mut self) -> Vec<Control> {
+        self.machine.take_controls()
+    }
+
+    // e.g. from watch callbacks
+    pub fn set_memory(&mut self, address: u16, value: Word) {
+        self.machine.set_memory(from_address(address), value);
+        self.history.clear();
+    }
+
+    pub fn set_a(&mut self, value: Word) {
+        self.machine.set_a(value);
+        self.history.clear();
+    }
+
+    pub fn set_d(&mut self, value: Word) {
+        self.machine.set_d(value);
+        self.history.clear();
+    }
+
+    pub fn set_pc(&mut self, pc: u16) {
+        self.machine.set_pc(from_address(pc));
+        self.history.clear();
     }
 
     pub fn get_memory_info(&self, from: i32, to: i32) -> String {
@@ -175,8 +574,7 @@ impl Computer {
             if i - from == (to - from) * 3 / 4 {
                 println!("75%");
             }
-            let binary_str: &str = &format!("{:015b}", i);
-            let output = self.memory_out(binary_str);
+            let output = self.memory(i as u16);
             if output != Word::new([O; 16]) {
                 let with_info = format!("{}: {}", i, output);
                 info = if info == "" {
@@ -189,129 +587,832 @@ impl Computer {
         info
     }
 
-    pub fn run(&mut self, filename: &str, reset: bool) {
-        let instruction_num = self.rom.load(&filename);
-        let reset_bit = match reset {
+    // the program running before is kept on an error
+    pub fn load(&mut self, filename: &str) -> Result<(), HardwareError> {
+        self.instruction_num = self.machine.load(filename)?;
+        self.history.clear();
+        self.cycles = 0;
+        Ok(())
+    }
+
+    // a program from memory, e.g. just assembled
+    pub fn load_words(&mut self, words: &[u16]) -> Result<(), HardwareError> {
+        self.instruction_num = self.machine.load_words(words)?;
+        self.history.clear();
+        self.cycles = 0;
+        Ok(())
+    }
+
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    // Presses and releases keys at the cycles of the script, counted from load,
+    // and waiting for a key isn't taken as a halt until the script ends.
+    pub fn script_keys(&mut self, script: KeyScript) {
+        self.keys = Some(script);
+    }
+
+    pub fn run(&mut self, filename: &str, reset: bool) -> Result<RunOutcome, HardwareError> {
+        self.load(filename)?;
+        self.reset = match reset {
             true => I,
             false => O,
         };
 
-        let mut in_m = Word::new([O; 16]);
-        let mut pc = [O; 15];
-
         loop {
-            let res = self.execute(pc, in_m, reset_bit);
-
-            if Computer::is_last(instruction_num, pc) == Word::new([I; 16]) {
-                break;
+            if let Some(outcome) = self.step() {
+                return Ok(outcome);
             }
+        }
+    }
+
+    // Calls the callback on the events which match, until it's unwatched.
+    // The callback may change the state of the computer, and stop it with Action::Stop.
+    pub fn watch<F>(&mut self, watch: Watch, callback: F) -> usize
+    where
+        F: FnMut(&mut Computer, &Event) -> Action + 'static,
+    {
+        self.next_watch += 1;
+        self.watches
+            .push((self.next_watch, watch, Box::new(callback)));
+        self.next_watch
+    }
+
+    pub fn unwatch(&mut self, id: usize) {
+        self.watches.retain(|(watch, _, _)| *watch != id);
+        self.unwatched.push(id);
+    }
 
-            pc = res.0;
-            in_m = res.1;
+    // Writes a record of every following cycle until untrace.
+    pub fn trace<W: Write + 'static>(&mut self, writer: W, format: Format) {
+        self.tracer = Some(TraceWriter::new(Box::new(writer), format));
+    }
+
+    // -> the first error of the writer
+    pub fn untrace(&mut self) -> io::Result<()> {
+        match self.tracer.take() {
+            Some(tracer) => tracer.finish(),
+            None => Ok(()),
         }
     }
 
-    fn execute(&mut self, pc: [bit; 15], in_m: Word, reset: bit) -> ([bit; 15], Word) {
-        let mut clock = Clock::new();
+    // Writes the CPU signals of every following clock phase as a VCD until finish_vcd.
+    // Only the gate-level machine has clock phases.
+    pub fn dump_vcd<W: Write + 'static>(&mut self, writer: W) -> io::Result<()> {
+        self.machine.dump_vcd(Some(Box::new(writer)))
+    }
 
-        // ROM
-        let instruction = self.rom.output(&clock, pc);
+    // -> the first error of the writer
+    pub fn finish_vcd(&mut self) -> io::Result<()> {
+        self.machine.dump_vcd(None)
+    }
 
-        // CPU
-        if self.debug {
-            println!("----------------------- CPU input -----------------------");
-            println!("in_memory: {}", in_m);
-            println!("instruction: {}", instruction);
-            println!("reset: {}", reset);
+    // execute one instruction
+    pub fn step(&mut self) -> Option<RunOutcome> {
+        let watching = !self.watches.is_empty();
+        self.machine.record_events(watching);
+        self.machine.record_cycles(self.tracer.is_some());
+        self.press_keys();
+        let outcome = self.execute();
+        self.cycles += 1;
+        if outcome.is_some() {
+            self.machine.flush_display();
+        }
+        if let Some(tracer) = self.tracer.as_mut() {
+            if let Some(record) = self.machine.take_record() {
+                tracer.write(&record);
+            }
+        }
+        if !watching {
+            return outcome;
         }
 
-        let (out_m, write_m, address_m, pc) = self.cpu.run(&clock, in_m, instruction, reset);
+        let mut events = self.machine.take_events();
+        events.push(Event::Pc(to_address(self.machine.pc())));
+        match (outcome, self.dispatch(&events)) {
+            (None, Action::Stop) => Some(RunOutcome::Stopped),
+            (outcome, _) => outcome,
+        }
+    }
 
-        if self.debug {
-            println!("----------------------- CPU output -----------------------");
-            println!("out_m: {}", out_m);
-            println!("write_m: {}", write_m);
-            println!("address_m: {:?}", address_m);
-            println!("pc: {:?}", pc);
+    fn dispatch(&mut self, events: &[Event]) -> Action {
+        let mut watches = mem::take(&mut self.watches);
+        let mut action = Action::Continue;
+        for event in events {
+            for (id, watch, callback) in watches.iter_mut() {
+                if watch.matches(event)
+                    && !self.unwatched.contains(id)
+                    && callback(self, event) == Action::Stop
+                {
+                    action = Action::Stop;
+                }
+            }
         }
+        // the callbacks may have watched and unwatched
+        watches.retain(|(id, _, _)| !self.unwatched.contains(id));
+        watches.append(&mut self.watches);
+        self.watches = watches;
+        self.unwatched.clear();
+        action
+    }
 
-        // Memory
-        self.memory.input(&clock, out_m, address_m, write_m);
-        if self.debug {
-            println!(
-                "memory.input(input: {}, addr: {:?}, load: {})",
-                out_m, address_m, write_m
-            );
+    fn execute(&mut self) -> Option<RunOutcome> {
+        let pc = self.machine.pc();
+        let state = (pc, self.machine.a(), self.machine.d());
+        let write_m = self.machine.execute(self.reset);
+
+        // past the program, while a last 0;JMP back, e.g. to the loop of Fill, goes on
+        if to_address(self.machine.pc()) >= u16::from(self.instruction_num) {
+            return Some(RunOutcome::EndOfProgram);
+        }
+        if write_m == I {
+            self.history.clear();
+            return None;
         }
 
-        clock.next();
-        let in_m = self.memory.output(&clock, address_m);
-        if self.debug {
-            println!("{} = memory.output(addr: {:?})", in_m, address_m);
+        // a loop which neither writes memory nor changes registers never ends,
+        // unless it waits for a scripted key
+        if self.keys.as_ref().is_some_and(|keys| !keys.is_done()) {
+            self.history.clear();
+            return None;
+        }
+        self.history.push(state);
+        if self.history.len() > 2 {
+            self.history.remove(0);
         }
+        let next = (self.machine.pc(), self.machine.a(), self.machine.d());
+        if self.history.contains(&next) {
+            return Some(RunOutcome::Halted);
+        }
+        None
+    }
 
-        if self.debug {
-            println!("");
+    pub fn run_for(&mut self, cycles: usize) -> RunOutcome {
+        for _ in 0..cycles {
+            if let Some(outcome) = self.step() {
+                return outcome;
+            }
         }
+        RunOutcome::CycleLimit
+    }
 
-        (pc, in_m)
+    // the predicate is checked after every instruction
+    pub fn run_until<F: FnMut(&Computer) -> bool>(&mut self, mut predicate: F) -> RunOutcome {
+        loop {
+            if let Some(outcome) = self.step() {
+                return outcome;
+            }
+            if predicate(self) {
+                return RunOutcome::Breakpoint;
+            }
+        }
     }
 
-    fn is_last(instruction_num: Word, pc: [bit; 15]) -> Word {
-        let pc_16 = Word::new([
-            O, pc[0], pc[1], pc[2], pc[3], pc[4], pc[5], pc[6], pc[7], pc[8], pc[9], pc[10],
-            pc[11], pc[12], pc[13], pc[14],
-        ]);
-        let pc_incr = add16(
-            pc_16,
-            Word::new([O, O, O, O, O, O, O, O, O, O, O, O, O, O, O, I]),
-        );
-        Word::new([
-            not(xor(instruction_num[0], pc_incr[0])),
-            not(xor(instruction_num[1], pc_incr[1])),
-            not(xor(instruction_num[2], pc_incr[2])),
-            not(xor(instruction_num[3], pc_incr[3])),
-            not(xor(instruction_num[4], pc_incr[4])),
-            not(xor(instruction_num[5], pc_incr[5])),
-            not(xor(instruction_num[6], pc_incr[6])),
-            not(xor(instruction_num[7], pc_incr[7])),
-            not(xor(instruction_num[8], pc_incr[8])),
-            not(xor(instruction_num[9], pc_incr[9])),
-            not(xor(instruction_num[10], pc_incr[10])),
-            not(xor(instruction_num[11], pc_incr[11])),
-            not(xor(instruction_num[12], pc_incr[12])),
-            not(xor(instruction_num[13], pc_incr[13])),
-            not(xor(instruction_num[14], pc_incr[14])),
-            not(xor(instruction_num[15], pc_incr[15])),
-        ])
+    fn press_keys(&mut self) {
+        if let Some(keys) = self.keys.as_mut() {
+            for event in keys.due(self.cycles) {
+                let code = Word::from(event.key.unwrap_or(0));
+                self.machine.set_memory(from_address(KBD), code);
+            }
+        }
     }
 }
 
+pub(crate) fn to_address(address: [bit; 15]) -> u16 {
+    address
+        .iter()
+        .fold(0, |sum, b| (sum << 1) | if *b == I { 1 } else { 0 })
+}
+
+pub(crate) fn from_address(address: u16) -> [bit; 15] {
+    let mut bits = [O; 15];
+    for (i, b) in bits.iter_mut().enumerate() {
+        if address & (1 << (14 - i)) != 0 {
+            *b = I;
+        }
+    }
+    bits
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{computer::trace::Trace, hdl::script::Target, profile};
+    use std::{
+        cell::RefCell,
+        env,
+        fs::{self, File},
+        path::Path,
+        process,
+        rc::Rc,
+        sync::mpsc,
+        thread,
+    };
 
     #[test]
     fn for_computer_max() {
         let mut computer = Computer::new(None, false);
-        computer.run("src/program/max.txt", false);
-        let r0 = computer.memory_out("000000000000010");
+        computer.run("src/program/max.txt", false).unwrap();
+        let r0 = computer.memory_out("000000000000010").unwrap();
         assert_eq!(r0, Word::from("0000000001000011"));
     }
 
     #[test]
     fn for_computer_max2() {
         let mut computer = Computer::new(None, false);
-        computer.run("src/program/max2.txt", false);
-        let r0 = computer.memory_out("000000000000010");
+        computer.run("src/program/max2.txt", false).unwrap();
+        let r0 = computer.memory_out("000000000000010").unwrap();
         assert_eq!(r0, Word::from("0000000011000011"));
     }
 
     #[test]
     fn for_computer_add() {
         let mut computer = Computer::new(None, false);
-        computer.run("src/program/add.txt", false);
-        let r0 = computer.memory_out("000000000000000");
+        computer.run("src/program/add.txt", false).unwrap();
+        let r0 = computer.memory_out("000000000000000").unwrap();
         assert_eq!(r0, Word::from("0000000000000101"));
     }
+
+    #[test]
+    fn for_computer_fast_max() {
+        let mut computer = Computer::with_backend(None, false, Backend::Fast);
+        computer.run("src/program/max.txt", false).unwrap();
+        let r0 = computer.memory_out("000000000000010").unwrap();
+        assert_eq!(r0, Word::from("0000000001000011"));
+    }
+
+    #[test]
+    fn for_computer_fast_max2() {
+        let mut computer = Computer::with_backend(None, false, Backend::Fast);
+        computer.run("src/program/max2.txt", false).unwrap();
+        let r0 = computer.memory_out("000000000000010").unwrap();
+        assert_eq!(r0, Word::from("0000000011000011"));
+    }
+
+    #[test]
+    fn for_computer_fast_add() {
+        let mut computer = Computer::with_backend(None, false, Backend::Fast);
+        computer.run("src/program/add.txt", false).unwrap();
+        let r0 = computer.memory_out("000000000000000").unwrap();
+        assert_eq!(r0, Word::from("0000000000000101"));
+    }
+
+    #[test]
+    fn for_computer_step() {
+        let mut computer = Computer::new(None, false);
+        computer.load("src/program/add.txt").unwrap();
+
+        assert_eq!(computer.step(), None);
+        assert_eq!(computer.a(), Word::from("0000000000000010"));
+        assert_eq!(computer.pc(), [O, O, O, O, O, O, O, O, O, O, O, O, O, O, I]);
+        assert_eq!(computer.step(), None);
+        assert_eq!(computer.d(), Word::from("0000000000000010"));
+        assert_eq!(computer.step(), None);
+        assert_eq!(computer.step(), None);
+        assert_eq!(computer.d(), Word::from("0000000000000101"));
+        assert_eq!(computer.step(), None);
+        assert_eq!(computer.step(), Some(RunOutcome::EndOfProgram));
+        assert_eq!(computer.pc(), [O, O, O, O, O, O, O, O, O, O, O, O, I, I, O]);
+    }
+
+    #[test]
+    fn for_computer_profile() {
+        let mut computer = Computer::new(None, false);
+        computer.load("src/program/add.txt").unwrap();
+
+        profile::start();
+        computer.run_for(2);
+        let profile = profile::stop();
+        // @2, D=A
+        assert_eq!(profile.cycles(), 2);
+        assert_eq!(profile.instructions[&2].calls, 1);
+        assert_eq!(profile.instructions[&0b1110110000010000].calls, 1);
+        assert_eq!(profile.chips["CPU.run"].calls, 2);
+        assert_eq!(profile.chips["alu"].calls, 2);
+        assert!(profile.to_string().contains("cycles: 2"));
+    }
+
+    #[test]
+    fn for_computer_fast_step() {
+        let mut computer = Computer::with_backend(None, false, Backend::Fast);
+        computer.load("src/program/add.txt").unwrap();
+
+        assert_eq!(computer.run_for(3), RunOutcome::CycleLimit);
+        assert_eq!(computer.a(), Word::from("0000000000000011"));
+        assert_eq!(computer.d(), Word::from("0000000000000010"));
+        assert_eq!(computer.run_for(10), RunOutcome::EndOfProgram);
+        assert_eq!(
+            computer.memory_out("000000000000000").unwrap(),
+            Word::from("0000000000000101")
+        );
+    }
+
+    #[test]
+    fn for_computer_watch() {
+        for backend in [Backend::Fast, Backend::Gate].iter() {
+            let mut computer = Computer::with_backend(None, false, *backend);
+            computer.load("src/program/add.txt").unwrap();
+            let events = Rc::new(RefCell::new(Vec::new()));
+            let log = events.clone();
+            computer.watch(Watch::Write(0..16), move |_, event| {
+                log.borrow_mut().push(*event);
+                Action::Continue
+            });
+            let stop = computer.watch(Watch::Pc(4), |_, _| Action::Stop);
+
+            // @2, D=A, @3, D=D+A
+            assert_eq!(computer.run_for(10), RunOutcome::Stopped, "{:?}", backend);
+            assert_eq!(computer.d(), Word::from(5u16));
+            computer.unwatch(stop);
+            // @0, M=D
+            assert_eq!(computer.run_for(10), RunOutcome::EndOfProgram);
+            assert_eq!(
+                *events.borrow(),
+                vec![Event::Write {
+                    pc: 5,
+                    address: 0,
+                    value: Word::from(5u16)
+                }]
+            );
+        }
+    }
+
+    #[test]
+    fn for_computer_trace() {
+        for backend in [Backend::Fast, Backend::Gate].iter() {
+            for format in [Format::Json, Format::Binary].iter() {
+                let path = env::temp_dir().join(format!(
+                    "for_computer_trace-{}-{:?}-{:?}",
+                    process::id(),
+                    backend,
+                    format
+                ));
+                let mut computer = Computer::with_backend(None, false, *backend);
+                computer.trace(File::create(&path).unwrap(), *format);
+                assert_eq!(
+                    computer.run("src/program/add.txt", false).unwrap(),
+                    RunOutcome::EndOfProgram
+                );
+                computer.untrace().unwrap();
+
+                let trace = Trace::read(&path).unwrap();
+                std::fs::remove_file(&path).unwrap();
+                assert_eq!(trace.records().len(), 6, "{:?} {:?}", backend, format);
+                assert_eq!(
+                    trace.pc(1..4).map(|r| r.d).collect::<Vec<_>>(),
+                    vec![Word::from(2u16), Word::from(2u16), Word::from(5u16)]
+                );
+                // M=D
+                assert_eq!(
+                    trace.writes(0).collect::<Vec<_>>(),
+                    vec![&Record {
+                        pc: 5,
+                        instruction: Word::from(0b1110001100001000u16),
+                        a: Word::from(0u16),
+                        d: Word::from(5u16),
+                        out_m: Word::from(5u16),
+                        write_m: true,
+                        address_m: 0,
+                        in_m: Word::from(0u16),
+                    }]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn for_computer_vcd() {
+        let path = env::temp_dir().join(format!("for_computer_vcd-{}.vcd", process::id()));
+        let mut computer = Computer::with_backend(None, false, Backend::Gate);
+        computer.dump_vcd(File::create(&path).unwrap()).unwrap();
+        assert_eq!(
+            computer.run("src/program/add.txt", false).unwrap(),
+            RunOutcome::EndOfProgram
+        );
+        computer.finish_vcd().unwrap();
+
+        let dump = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let lines: Vec<&str> = dump.lines().collect();
+        assert!(lines.contains(&"$var wire 16 & D $end"));
+        // 6 instructions of 2 phases
+        assert_eq!(lines.iter().filter(|l| l.starts_with('#')).count(), 12);
+        // D=D+A loads D on the tick of #6, which shows up on the tock
+        let tock = lines.iter().position(|l| *l == "#7").unwrap();
+        assert!(lines[tock..].contains(&"b101 &"));
+        assert!(!lines[..tock].contains(&"b101 &"));
+
+        let mut computer = Computer::with_backend(None, false, Backend::Fast);
+        assert!(computer.dump_vcd(Vec::new()).is_err());
+        assert!(computer.finish_vcd().is_ok());
+    }
+
+    #[test]
+    fn for_computer_snapshot() {
+        let mut snapshots = Vec::new();
+        for backend in [Backend::Fast, Backend::Gate].iter() {
+            let mut computer = Computer::with_backend(None, false, *backend);
+            Target::load(&mut computer, Path::new("src/program/Mult.asm")).unwrap();
+            computer.set_memory(0, Word::from(7u16));
+            computer.set_memory(1, Word::from(1u16));
+            // in the middle of the loop
+            assert_eq!(computer.run_for(10), RunOutcome::CycleLimit);
+            let mut snapshot = Vec::new();
+            computer.save(&mut snapshot).unwrap();
+
+            let mut resumed = Computer::with_backend(None, false, *backend);
+            resumed.restore(snapshot.as_slice()).unwrap();
+            assert_eq!(resumed.pc(), computer.pc(), "{:?}", backend);
+            assert_eq!(resumed.a(), computer.a());
+            assert_eq!(resumed.d(), computer.d());
+            let outcome = computer.run_until(|_| false);
+            assert_eq!(resumed.run_until(|_| false), outcome);
+            assert_eq!(resumed.memory(2), Word::from(7u16));
+            assert_eq!(computer.memory(2), Word::from(7u16));
+            snapshots.push(snapshot);
+        }
+
+        // from another backend
+        let mut computer = Computer::with_backend(None, false, Backend::Gate);
+        assert!(computer.restore(snapshots[0].as_slice()).is_err());
+    }
+
+    // stores twice the word written
+    struct Doubler(Word);
+
+    impl Device for Doubler {
+        fn read(&self, _address: u16) -> Word {
+            self.0
+        }
+
+        fn write(&mut self, _address: u16, input: Word, load: bit) {
+            if load == I {
+                self.0 = Word::from(u16::from(input).wrapping_mul(2));
+            }
+        }
+    }
+
+    #[test]
+    fn for_memory() {
+        let mut memory = Memory::new(None);
+        memory.tick(Word::from(7u16), from_address(SCREEN + 1), I);
+        memory.tock();
+        memory.set_keyboard(Word::from(65u16));
+        assert_eq!(memory.output(from_address(SCREEN + 1)), Word::from(7u16));
+        assert_eq!(memory.output(from_address(1)), Word::from(0u16));
+        assert_eq!(memory.output(from_address(KBD)), Word::from(65u16));
+        // and so do the addresses above it
+        assert_eq!(memory.output(from_address(KBD + 1)), Word::from(65u16));
+        assert_eq!(memory.output(from_address(0x7fff)), Word::from(65u16));
+        let state = serde_json::to_value(&memory).unwrap();
+        assert_eq!(state["keyboard"]["code"], 65);
+    }
+
+    #[test]
+    fn for_memory_attach() {
+        // the Hack map with a single keyboard word, which leaves room for the doubler
+        let mut memory = Memory::empty();
+        memory.attach("ram", 0..SCREEN, Box::new(RAM16K::new()));
+        memory.attach("screen", SCREEN..KBD, Box::new(Screen::new(None)));
+        memory.attach("keyboard", KBD..KBD + 1, Box::new(Keyboard::new(None)));
+        memory.attach(
+            "doubler",
+            KBD + 1..KBD + 2,
+            Box::new(Doubler(Word::from(0u16))),
+        );
+        let mut computer =
+            Computer::with_machine(Box::new(GateMachine::with_memory(memory, false)));
+        let path = env::temp_dir().join(format!("for_memory_attach-{}", process::id()));
+        // RAM[0] = double(21) through the doubler at 24577
+        std::fs::write(
+            &path,
+            [
+                "0000000000010101",
+                "1110110000010000",
+                "0110000000000001",
+                "1110001100001000",
+                "1111110000010000",
+                "0000000000000000",
+                "1110001100001000",
+            ]
+            .join("\n"),
+        )
+        .unwrap();
+        assert_eq!(
+            computer.run(path.to_str().unwrap(), false).unwrap(),
+            RunOutcome::EndOfProgram
+        );
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(computer.memory(0), Word::from(42u16));
+        computer.set_memory(KBD + 1, Word::from(5u16));
+        assert_eq!(computer.memory(KBD + 1), Word::from(10u16));
+    }
+
+    #[test]
+    #[should_panic]
+    fn for_memory_attach_overlap() {
+        let mut memory = Memory::new(None);
+        memory.attach("doubler", KBD - 1..KBD, Box::new(Doubler(Word::from(0u16))));
+    }
+
+    #[test]
+    fn for_computer_watch_keyboard() {
+        let mut computer = Computer::with_backend(None, false, Backend::Fast);
+        Target::load(&mut computer, Path::new("src/program/Fill.asm")).unwrap();
+        let events = Rc::new(RefCell::new(Vec::new()));
+
+        // press a key once the program has looked at the keyboard
+        let log = events.clone();
+        computer.watch(Watch::Read(KBD..KBD + 1), move |computer, event| {
+            log.borrow_mut().push(*event);
+            computer.set_memory(KBD, Word::from(65u16));
+            Action::Continue
+        });
+        let log = events.clone();
+        computer.watch(Watch::Write(SCREEN..KBD), move |_, event| {
+            log.borrow_mut().push(*event);
+            match event {
+                Event::Write { value, .. } if i16::from(*value) == -1 => Action::Stop,
+                _ => Action::Continue,
+            }
+        });
+
+        // the last instruction 0;JMP goes back to the loop
+        assert_eq!(computer.run_for(1_000_000), RunOutcome::Stopped);
+        let events = events.borrow();
+        // the screen is cleared, and then blackened after the second read
+        assert_eq!(
+            events[0],
+            Event::Read {
+                pc: 3,
+                address: KBD
+            }
+        );
+        assert_eq!(
+            events[1],
+            Event::Write {
+                pc: 22,
+                address: SCREEN,
+                value: Word::from(0u16)
+            }
+        );
+        assert_eq!(events.len(), 8192 + 3);
+        assert_eq!(
+            events[8192 + 2],
+            Event::Write {
+                pc: 22,
+                address: SCREEN,
+                value: Word::from(-1i16)
+            }
+        );
+        assert_eq!(computer.memory(SCREEN), Word::from(-1i16));
+    }
+
+    #[test]
+    fn for_computer_framebuffer() {
+        for backend in [Backend::Fast, Backend::Gate].iter() {
+            let mut computer = Computer::with_backend(None, false, *backend);
+            // the rectangle program draws 3 rows of 16 pixels and ends in (END) @END 0;JMP
+            assert_eq!(
+                computer.run("src/program/rect.txt", false).unwrap(),
+                RunOutcome::Halted
+            );
+            let framebuffer = computer.framebuffer();
+            assert!(framebuffer.pixel(0, 0) && framebuffer.pixel(15, 2));
+            assert!(!framebuffer.pixel(16, 0) && !framebuffer.pixel(0, 3));
+            assert_eq!(
+                framebuffer.to_ascii().matches('#').count(),
+                48,
+                "{:?}",
+                backend
+            );
+        }
+    }
+
+    #[test]
+    fn for_computer_address_m() {
+        // @32767, D=A, A=D+1, D=M, @2, M=D reads M at A = 0x8000, i.e. RAM[0]
+        let words = [0x7fff, 0xec10, 0xe7e0, 0xfc10, 0x0002, 0xe308];
+        for backend in [Backend::Fast, Backend::Gate].iter() {
+            let mut computer = Computer::with_backend(None, false, *backend);
+            computer.load_words(&words).unwrap();
+            computer.set_memory(0, Word::from(7u16));
+            computer.set_memory(KBD, Word::from(65u16));
+            let events = Rc::new(RefCell::new(Vec::new()));
+            let log = events.clone();
+            computer.watch(Watch::Read(0..0x8000), move |_, event| {
+                log.borrow_mut().push(*event);
+                Action::Continue
+            });
+            assert_eq!(computer.run_for(10), RunOutcome::EndOfProgram);
+            assert_eq!(computer.memory(2), Word::from(7u16), "{:?}", backend);
+            assert_eq!(
+                *events.borrow(),
+                vec![Event::Read { pc: 3, address: 0 }],
+                "{:?}",
+                backend
+            );
+        }
+    }
+
+    #[test]
+    fn for_computer_load_words() {
+        let words = image::read("src/program/add.txt").unwrap();
+        let path = env::temp_dir().join(format!("for_computer_load_words-{}.bin", process::id()));
+        image::write(&path, &words, image::Format::BinaryBe).unwrap();
+        for backend in [Backend::Fast, Backend::Gate].iter() {
+            let mut computer = Computer::with_backend(None, false, *backend);
+            computer.load_words(&words).unwrap();
+            assert_eq!(computer.run_for(10), RunOutcome::EndOfProgram);
+            assert_eq!(computer.memory(0), Word::from(5u16), "{:?}", backend);
+
+            let mut computer = Computer::with_backend(None, false, *backend);
+            computer.load(&path.to_string_lossy()).unwrap();
+            assert_eq!(computer.run_for(10), RunOutcome::EndOfProgram);
+            assert_eq!(computer.memory(0), Word::from(5u16), "{:?}", backend);
+        }
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn for_computer_small_stack() {
+        // the gate-level memory is on the heap
+        let r0 = thread::Builder::new()
+            .stack_size(256 * 1024)
+            .spawn(|| {
+                let mut computer = Computer::with_backend(None, false, Backend::Gate);
+                computer.run("src/program/add.txt", false).unwrap();
+                computer.memory(0)
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!(r0, Word::from(5u16));
+    }
+
+    #[test]
+    fn for_computer_load_error() {
+        let path = env::temp_dir().join(format!("for_computer_load_error-{}.hack", process::id()));
+        fs::write(&path, "0000000000000010\n111011000001000\n").unwrap();
+        let mut computer = Computer::new(None, false);
+        computer.load("src/program/add.txt").unwrap();
+
+        // the line and the text, and the program before still runs
+        let e = computer.load(&path.to_string_lossy()).unwrap_err();
+        assert_eq!((e.line, e.text.as_deref()), (2, Some("111011000001000")));
+        assert!(e
+            .to_string()
+            .ends_with("line 2: expected 16 bits: \"111011000001000\""));
+        assert_eq!(computer.run_for(10), RunOutcome::EndOfProgram);
+        assert_eq!(computer.memory(0), Word::from(5u16));
+        let _ = fs::remove_file(&path);
+
+        assert!(computer.load_words(&vec![0; 32769]).is_err());
+        assert!(computer.run("src/program/missing.txt", false).is_err());
+        assert!(computer.memory_out("00000000000000").is_err());
+        assert!(computer.memory_out("00000000000000x").is_err());
+    }
+
+    #[test]
+    fn for_computer_frames() {
+        let (to_display, from_computer) = mpsc::channel();
+        let (_to_computer, from_display) = mpsc::channel();
+        let mut computer =
+            Computer::with_backend(Some((to_display, from_display)), false, Backend::Fast);
+        let path = env::temp_dir().join(format!("for_computer_frames-{}", process::id()));
+        // blacken the screen word by word, then halt
+        std::fs::write(
+            &path,
+            [
+                "0100000000000000",
+                "1110110000010000",
+                "0000000000010000",
+                "1110001100001000",
+                "0000000000010000",
+                "1111110000100000",
+                "1110111010001000",
+                "0000000000010000",
+                "1111110111011000",
+                "0110000000000000",
+                "1110010011010000",
+                "0000000000000100",
+                "1110001100000100",
+                "0000000000001101",
+                "1110101010000111",
+            ]
+            .join("\n"),
+        )
+        .unwrap();
+        computer.run(path.to_str().unwrap(), false).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        // the display puts the runs of the frames together
+        let mut words = vec![0; 8192];
+        let frames: Vec<String> = from_computer.try_iter().collect();
+        assert!(frames.len() < 100, "{} frames", frames.len());
+        for frame in frames.iter() {
+            let frame: Value = serde_json::from_str(frame).unwrap();
+            for run in frame["runs"].as_array().unwrap() {
+                let (start, count) = (run[0].as_u64().unwrap(), run[1].as_u64().unwrap());
+                for i in start..start + count {
+                    words[i as usize] = run[2].as_u64().unwrap() as u16;
+                }
+            }
+        }
+        assert_eq!(computer.framebuffer().words(), &words[..]);
+        assert!(words.iter().all(|word| *word == 0xffff));
+    }
+
+    #[test]
+    fn for_computer_display() {
+        for backend in [Backend::Fast, Backend::Gate].iter() {
+            let (to_display, from_computer) = mpsc::channel();
+            let (to_computer, from_display) = mpsc::channel();
+            let mut computer =
+                Computer::with_backend(Some((to_display, from_display)), false, *backend);
+            computer.set_frame_rate(0);
+            let messages = [
+                r#"{"type":"hello","version":1}"#,
+                r#"{"type":"key","key":65}"#,
+                r#"{"type":"key","key":65,"down":true}"#,
+                r#"{"type":"control","command":"pause"}"#,
+            ];
+            // a message is read on each poll
+            for message in messages.iter() {
+                to_computer.send(message.to_string()).unwrap();
+                computer.poll_display();
+            }
+            computer.poll_display();
+
+            let replies: Vec<Value> = from_computer
+                .try_iter()
+                .map(|reply| serde_json::from_str(&reply).unwrap())
+                .collect();
+            let types: Vec<&str> = replies
+                .iter()
+                .map(|r| r["type"].as_str().unwrap())
+                .collect();
+            // the welcome is followed by a keyframe, and the key without down is an error
+            let welcome = types.iter().position(|t| *t == "welcome").unwrap();
+            assert_eq!(replies[welcome + 1]["keyframe"], true, "{:?}", backend);
+            assert_eq!(types.last(), Some(&"error"));
+            assert_eq!(computer.memory(KBD), Word::from(65u16));
+            assert_eq!(computer.take_controls(), vec![Control::Pause]);
+            assert!(computer.take_controls().is_empty());
+        }
+    }
+
+    #[test]
+    fn for_computer_script_keys() {
+        let mut computer = Computer::with_backend(None, false, Backend::Fast);
+        Target::load(&mut computer, Path::new("src/program/Fill.asm")).unwrap();
+        computer.script_keys(KeyScript::parse("1000 press a\n300000 release").unwrap());
+        let run_to = |computer: &mut Computer, cycle: u64| {
+            while computer.cycles() < cycle {
+                assert_eq!(computer.step(), None);
+            }
+        };
+
+        run_to(&mut computer, 1000);
+        assert_eq!(computer.memory(KBD), Word::from(0u16));
+        run_to(&mut computer, 299_999);
+        assert_eq!(computer.memory(KBD), Word::from(97u16));
+        assert!(computer.framebuffer().words().iter().all(|w| *w == 0xffff));
+        run_to(&mut computer, 600_000);
+        assert_eq!(computer.memory(KBD), Word::from(0u16));
+        assert!(computer.framebuffer().words().iter().all(|w| *w == 0));
+    }
+
+    #[test]
+    fn for_computer_halt() {
+        let mut computer = Computer::with_backend(None, false, Backend::Fast);
+        assert_eq!(
+            computer.run("src/program/halt.txt", false).unwrap(),
+            RunOutcome::Halted
+        );
+        assert_eq!(computer.pc(), [O, O, O, O, O, O, O, O, O, O, O, O, I, I, I]);
+        assert_eq!(
+            computer.memory_out("000000000000000").unwrap(),
+            Word::from("0000000000000101")
+        );
+
+        // @0, M=M+1, @0, 0;JMP counts forever
+        let mut computer = Computer::with_backend(None, false, Backend::Fast);
+        computer
+            .load_words(&[0x0000, 0xfdc8, 0x0000, 0xea87])
+            .unwrap();
+        assert_eq!(computer.run_for(100), RunOutcome::CycleLimit);
+        assert_eq!(computer.memory(0), Word::from(25u16));
+    }
+
+    #[test]
+    fn for_computer_run_until() {
+        let mut computer = Computer::with_backend(None, false, Backend::Fast);
+        computer.load("src/program/halt.txt").unwrap();
+        let outcome = computer.run_until(|c| c.d() == Word::from("0000000000000101"));
+        assert_eq!(outcome, RunOutcome::Breakpoint);
+        assert_eq!(computer.pc(), [O, O, O, O, O, O, O, O, O, O, O, O, I, O, O]);
+        assert_eq!(computer.run_until(|_| false), RunOutcome::Halted);
+    }
 }
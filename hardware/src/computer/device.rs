@@ -0,0 +1,139 @@
+// Memory-mapped devices of Memory: RAM16K, the screen and the keyboard of the Hack map,
+// and any peripheral attached to a free range.
+use std::convert::TryInto;
+
+use serde_json::Value;
+
+use crate::{
+    base::{
+        keyboard::Keyboard,
+        logic::bit::{self, I},
+        logic::Word,
+        ram::RAM16K,
+        screen::{Framebuffer, Screen},
+    },
+    computer::from_address,
+};
+
+/// A peripheral which `Memory` maps to a range of addresses.
+/// The addresses it gets are relative to the start of its range.
+pub trait Device {
+    fn read(&self, address: u16) -> Word;
+    // the rising edge of every cycle, like the input of a chip, and load is O unless the CPU writes the device
+    fn write(&mut self, address: u16, input: Word, load: bit);
+    // the falling edge of every cycle, from which read shows what was written
+    fn tock(&mut self) {}
+    // after every write, e.g. to take a key from a channel, and while the machine waits
+    fn poll(&mut self) {}
+    // overwrite a word from outside the program, e.g. by test scripts
+    fn set(&mut self, address: u16, input: Word) {
+        self.write(address, input, I);
+        self.tock();
+    }
+    // the pixels of a device which displays them, like the screen
+    fn framebuffer(&self) -> Option<Framebuffer> {
+        None
+    }
+    // for a device which sends frames to a display
+    fn set_frame_rate(&mut self, _frame_rate: u32) {}
+    // send what is buffered, e.g. at the end of a program
+    fn flush(&mut self) {}
+    // the state for Computer::save, and back
+    fn snapshot(&self) -> Result<Value, serde_json::Error> {
+        Ok(Value::Null)
+    }
+    fn restore(&mut self, _state: Value) -> Result<(), serde_json::Error> {
+        Ok(())
+    }
+}
+
+// the lower N bits of the address, MSB first
+fn address_bits<const N: usize>(address: u16) -> [bit; N] {
+    from_address(address)[15 - N..].try_into().unwrap()
+}
+
+impl Device for RAM16K {
+    fn read(&self, address: u16) -> Word {
+        self.output(address_bits(address))
+    }
+
+    fn write(&mut self, address: u16, input: Word, load: bit) {
+        RAM16K::tick(self, input, address_bits(address), load);
+    }
+
+    fn tock(&mut self) {
+        RAM16K::tock(self);
+    }
+
+    fn snapshot(&self) -> Result<Value, serde_json::Error> {
+        serde_json::to_value(self)
+    }
+
+    fn restore(&mut self, state: Value) -> Result<(), serde_json::Error> {
+        RAM16K::restore(self, state)
+    }
+}
+
+impl Device for Screen {
+    fn read(&self, address: u16) -> Word {
+        self.output(address_bits(address))
+    }
+
+    fn write(&mut self, address: u16, input: Word, load: bit) {
+        Screen::tick(self, input, address_bits(address), load);
+    }
+
+    fn tock(&mut self) {
+        Screen::tock(self);
+    }
+
+    fn poll(&mut self) {
+        Screen::poll(self);
+    }
+
+    fn framebuffer(&self) -> Option<Framebuffer> {
+        Some(Screen::framebuffer(self))
+    }
+
+    fn set_frame_rate(&mut self, frame_rate: u32) {
+        Screen::set_frame_rate(self, frame_rate);
+    }
+
+    fn flush(&mut self) {
+        Screen::flush(self);
+    }
+
+    fn snapshot(&self) -> Result<Value, serde_json::Error> {
+        serde_json::to_value(self)
+    }
+
+    fn restore(&mut self, state: Value) -> Result<(), serde_json::Error> {
+        Screen::restore(self, state)
+    }
+}
+
+// read-only for the CPU, the key comes from the channel or set
+impl Device for Keyboard {
+    fn read(&self, _address: u16) -> Word {
+        self.output()
+    }
+
+    fn write(&mut self, _address: u16, _input: Word, _load: bit) {}
+
+    fn poll(&mut self) {
+        self.input();
+    }
+
+    fn set(&mut self, _address: u16, input: Word) {
+        Keyboard::set(self, input);
+    }
+
+    fn snapshot(&self) -> Result<Value, serde_json::Error> {
+        serde_json::to_value(self)
+    }
+
+    fn restore(&mut self, state: Value) -> Result<(), serde_json::Error> {
+        Keyboard::restore(self, serde_json::from_value(state)?);
+        Ok(())
+    }
+}
@@ -0,0 +1,349 @@
+#![allow(dead_code)]
+use std::sync::mpsc::{Receiver, Sender};
+
+use crate::{
+    base::{
+        keyboard::Keyboard,
+        logic::bit::{I, O},
+        logic::{bit, Word},
+        rom,
+        screen::Framebuffer,
+    },
+    computer::{
+        from_address, snapshot::SnapshotError, to_address, trace::Record, Event, Events, Machine,
+        KBD, SCREEN,
+    },
+    display::Frames,
+    error::HardwareError,
+    protocol::Control,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+const RAM_SIZE: usize = 16384;
+const SCREEN_SIZE: usize = 8192;
+
+// The Hack machine modelled on native u16 registers and memory.
+// It follows the gate-level CPU, which drives addressM from the updated A register.
+#[derive(Serialize, Deserialize)]
+pub struct FastMachine {
+    rom: Vec<u16>,
+    ram: Vec<u16>,
+    screen: Vec<u16>,
+    keyboard: Keyboard,
+    #[serde(skip)]
+    frames: Frames,
+    a: u16,
+    d: u16,
+    pc: u16,
+    #[serde(skip)]
+    events: Events,
+    // the last cycle while recording cycles
+    #[serde(skip)]
+    tracing: bool,
+    #[serde(skip)]
+    record: Option<Record>,
+    // println
+    #[serde(skip)]
+    debug: bool,
+}
+
+impl FastMachine {
+    pub fn new(channel: Option<(Sender<String>, Receiver<String>)>, debug: bool) -> Self {
+        let (tx, rx) = match channel {
+            Some(tuple) => (Some(tuple.0), Some(tuple.1)),
+            None => (None, None),
+        };
+        let frames = Frames::new(tx);
+        Self {
+            rom: Vec::new(),
+            ram: vec![0; RAM_SIZE],
+            screen: vec![0; SCREEN_SIZE],
+            keyboard: Keyboard::with_link(rx, frames.link()),
+            frames,
+            a: 0,
+            d: 0,
+            pc: 0,
+            events: Events::default(),
+            tracing: false,
+            record: None,
+            debug,
+        }
+    }
+
+    pub(super) fn fetch(&self, pc: u16) -> u16 {
+        self.rom.get(pc as usize).copied().unwrap_or(0)
+    }
+
+    pub(super) fn read(&self, address: u16) -> u16 {
+        if address < SCREEN {
+            self.ram[address as usize]
+        } else if address < KBD {
+            self.screen[(address - SCREEN) as usize]
+        } else {
+            u16::from(self.keyboard.output())
+        }
+    }
+
+    pub(super) fn write(&mut self, address: u16, input: u16) {
+        if address < SCREEN {
+            self.ram[address as usize] = input;
+        } else if address < KBD {
+            let register_index = (address - SCREEN) as usize;
+            self.screen[register_index] = input;
+            self.frames.write(register_index, input);
+        }
+    }
+
+    // once a cycle, like the chips of Memory
+    pub(super) fn poll_devices(&mut self) {
+        self.keyboard.input();
+        self.frames.tick();
+    }
+
+    // -> (f(x,y), zr, ng)
+    fn alu(x: u16, y: u16, comp: u16) -> (u16, bool, bool) {
+        let x = if comp & 0b100000 != 0 { 0 } else { x };
+        let x = if comp & 0b010000 != 0 { !x } else { x };
+        let y = if comp & 0b001000 != 0 { 0 } else { y };
+        let y = if comp & 0b000100 != 0 { !y } else { y };
+        let out = if comp & 0b000010 != 0 {
+            x.wrapping_add(y)
+        } else {
+            x & y
+        };
+        let out = if comp & 0b000001 != 0 { !out } else { out };
+        (out, out == 0, out & 0x8000 != 0)
+    }
+}
+
+impl Machine for FastMachine {
+    fn load_words(&mut self, words: &[u16]) -> Result<Word, HardwareError> {
+        rom::check_size(words)?;
+        self.rom = words.to_vec();
+        Ok(Word::from(self.rom.len() as u16))
+    }
+
+    fn execute(&mut self, reset: bit) -> bit {
+        let instruction = self.fetch(self.pc);
+        // addressM is the low 15 bits of A, as in the CPU
+        let in_m = self.read(self.a & 0x7fff);
+
+        if self.debug {
+            println!("----------------------- CPU input -----------------------");
+            println!("in_memory: {}", Word::from(in_m));
+            println!("instruction: {}", Word::from(instruction));
+            println!("reset: {}", reset);
+        }
+
+        if instruction & 0x9000 == 0x9000 {
+            self.events.push(Event::Read {
+                pc: self.pc,
+                address: self.a & 0x7fff,
+            });
+        }
+
+        let current_pc = self.pc;
+        let current_a = self.a;
+        let (out_m, write_m, jump) = if instruction & 0x8000 == 0 {
+            // A命令
+            self.a = instruction;
+            (0, false, false)
+        } else {
+            // C命令
+            let y = if instruction & 0x1000 != 0 {
+                in_m
+            } else {
+                self.a
+            };
+            let (out, zr, ng) = FastMachine::alu(self.d, y, (instruction >> 6) & 0b111111);
+            if instruction & 0b100000 != 0 {
+                self.a = out;
+            }
+            if instruction & 0b010000 != 0 {
+                self.d = out;
+            }
+            let jump = (instruction & 0b100 != 0 && ng)
+                || (instruction & 0b010 != 0 && zr)
+                || (instruction & 0b001 != 0 && !zr && !ng);
+            (out, instruction & 0b001000 != 0, jump)
+        };
+
+        self.pc = if reset == I {
+            0
+        } else if jump {
+            current_a
+        } else {
+            self.pc.wrapping_add(1)
+        } & 0x7fff;
+
+        // Memory
+        let address_m = self.a & 0x7fff;
+        if write_m {
+            self.write(address_m, out_m);
+            self.events.push(Event::Write {
+                pc: current_pc,
+                address: address_m,
+                value: Word::from(out_m),
+            });
+        }
+        self.poll_devices();
+
+        if self.tracing {
+            self.record = Some(Record {
+                pc: current_pc,
+                instruction: Word::from(instruction),
+                a: Word::from(self.a),
+                d: Word::from(self.d),
+                out_m: Word::from(out_m),
+                write_m,
+                address_m,
+                in_m: Word::from(in_m),
+            });
+        }
+
+        if self.debug {
+            println!("----------------------- CPU output -----------------------");
+            println!("out_m: {}", Word::from(out_m));
+            println!("write_m: {}", write_m);
+            println!("address_m: {}", address_m);
+            println!("pc: {}", self.pc);
+            println!();
+        }
+
+        if write_m {
+            I
+        } else {
+            O
+        }
+    }
+
+    fn pc(&self) -> [bit; 15] {
+        from_address(self.pc)
+    }
+
+    fn a(&self) -> Word {
+        Word::from(self.a)
+    }
+
+    fn d(&self) -> Word {
+        Word::from(self.d)
+    }
+
+    fn memory_out(&self, address: [bit; 15]) -> Word {
+        Word::from(self.read(to_address(address)))
+    }
+
+    fn framebuffer(&self) -> Framebuffer {
+        Framebuffer::new(self.screen.clone())
+    }
+
+    fn set_frame_rate(&mut self, frame_rate: u32) {
+        self.frames.set_frame_rate(frame_rate);
+    }
+
+    fn flush_display(&mut self) {
+        self.frames.flush();
+    }
+
+    fn poll_display(&mut self) {
+        self.poll_devices();
+    }
+
+    fn take_controls(&mut self) -> Vec<Control> {
+        self.frames.link().take_controls()
+    }
+
+    fn set_memory(&mut self, address: [bit; 15], input: Word) {
+        let address = to_address(address);
+        if address >= KBD {
+            self.keyboard.set(input);
+        } else {
+            self.write(address, u16::from(input));
+        }
+    }
+
+    fn set_pc(&mut self, pc: [bit; 15]) {
+        self.pc = to_address(pc);
+    }
+
+    fn set_a(&mut self, input: Word) {
+        self.a = u16::from(input);
+    }
+
+    fn set_d(&mut self, input: Word) {
+        self.d = u16::from(input);
+    }
+
+    fn record_events(&mut self, on: bool) {
+        self.events.record(on);
+    }
+
+    fn take_events(&mut self) -> Vec<Event> {
+        self.events.take()
+    }
+
+    fn snapshot(&self) -> Result<Value, SnapshotError> {
+        Ok(serde_json::to_value(self)?)
+    }
+
+    // the display is redrawn from the screen of the snapshot
+    fn restore(&mut self, state: Value) -> Result<(), SnapshotError> {
+        let state: FastMachine = serde_json::from_value(state)?;
+        if state.ram.len() != RAM_SIZE || state.screen.len() != SCREEN_SIZE {
+            return Err(SnapshotError(format!(
+                "expected {} words of RAM and {} of screen",
+                RAM_SIZE, SCREEN_SIZE
+            )));
+        }
+        self.rom = state.rom;
+        self.ram = state.ram;
+        self.screen = state.screen;
+        self.keyboard.restore(state.keyboard);
+        self.a = state.a;
+        self.d = state.d;
+        self.pc = state.pc;
+        self.frames.write_all(&self.screen);
+        Ok(())
+    }
+
+    fn record_cycles(&mut self, on: bool) {
+        self.tracing = on;
+        if !on {
+            self.record = None;
+        }
+    }
+
+    fn take_record(&mut self) -> Option<Record> {
+        self.record.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn for_fast_alu() {
+        // 0, 1, -1
+        assert_eq!(FastMachine::alu(3, 5, 0b101010), (0, true, false));
+        assert_eq!(FastMachine::alu(3, 5, 0b111111), (1, false, false));
+        assert_eq!(FastMachine::alu(3, 5, 0b111010), (0xffff, false, true));
+        // D+A, D-A, D&A, D|A
+        assert_eq!(FastMachine::alu(3, 5, 0b000010), (8, false, false));
+        assert_eq!(FastMachine::alu(3, 5, 0b010011), (0xfffe, false, true));
+        assert_eq!(FastMachine::alu(3, 5, 0b000000), (1, false, false));
+        assert_eq!(FastMachine::alu(3, 5, 0b010101), (7, false, false));
+    }
+
+    #[test]
+    fn for_fast_memory_map() {
+        let mut machine = FastMachine::new(None, false);
+        machine.write(2, 7);
+        machine.write(SCREEN + 1, 9);
+        machine.write(KBD, 1);
+        assert_eq!(machine.read(2), 7);
+        assert_eq!(machine.read(SCREEN + 1), 9);
+        assert_eq!(machine.read(KBD), 0);
+    }
+}
@@ -0,0 +1,349 @@
+#![allow(dead_code)]
+use std::{
+    io::{self, Write},
+    sync::mpsc::{Receiver, Sender},
+};
+
+use crate::{
+    base::{
+        cpu::{CPUOutput, CPU},
+        logic::bit::{I, O},
+        logic::{bit, Word},
+        rom::ROM32K,
+        scheduler::{ChipId, Scheduler},
+        screen::Framebuffer,
+    },
+    computer::{
+        snapshot::SnapshotError, to_address, trace::Record, Event, Events, Machine, Memory,
+    },
+    error::HardwareError,
+    profile,
+    protocol::Control,
+    vcd::{self, Vcd},
+};
+use serde::{ser::SerializeStruct, Serialize, Serializer};
+use serde_json::Value;
+
+// the signals of dump_vcd
+const SIGNALS: &[(&str, usize)] = &[
+    ("clock", 1),
+    ("instruction", 16),
+    ("inM", 16),
+    ("reset", 1),
+    ("A", 16),
+    ("D", 16),
+    ("PC", 15),
+    ("zr", 1),
+    ("ng", 1),
+    ("outM", 16),
+    ("writeM", 1),
+    ("addressM", 15),
+];
+
+// Every instruction is evaluated through the gate-level CPU, ROM32K and RAM16K.
+// The CPU and the memory are clocked by the scheduler, which rests at Tock between instructions.
+// Only the state of the chips is serialized for snapshots.
+pub struct GateMachine {
+    rom: ROM32K,
+    scheduler: Scheduler,
+    cpu: ChipId,
+    memory: ChipId,
+    pc: [bit; 15],
+    in_m: Word,
+    events: Events,
+    // the last cycle while recording cycles
+    tracing: bool,
+    record: Option<Record>,
+    vcd: Option<Vcd>,
+    // println
+    debug: bool,
+}
+
+impl GateMachine {
+    pub fn new(channel: Option<(Sender<String>, Receiver<String>)>, debug: bool) -> Self {
+        GateMachine::with_memory(Memory::new(channel), debug)
+    }
+
+    // e.g. the Hack map with more devices attached
+    pub fn with_memory(memory: Memory, debug: bool) -> Self {
+        let mut scheduler = Scheduler::new();
+        let cpu = scheduler.attach(scheduler.main(), Box::new(CPU::new()));
+        let memory = scheduler.attach(scheduler.main(), Box::new(memory));
+        Self {
+            rom: ROM32K::new(),
+            scheduler,
+            cpu,
+            memory,
+            pc: [O; 15],
+            in_m: Word::new([O; 16]),
+            events: Events::default(),
+            tracing: false,
+            record: None,
+            vcd: None,
+            debug,
+        }
+    }
+}
+
+impl Machine for GateMachine {
+    fn load_words(&mut self, words: &[u16]) -> Result<Word, HardwareError> {
+        self.rom.load_words(words)
+    }
+
+    fn execute(&mut self, reset: bit) -> bit {
+        // A of the last instruction, which addresses M
+        let address = u16::from(self.a()) & 0x7fff;
+
+        // ROM
+        let instruction = self.rom.output(self.pc);
+        let _profile = profile::instruction(u16::from(instruction));
+        let pc = to_address(self.pc);
+        if self.events.is_recording() && instruction[0] == I && instruction[3] == I {
+            self.events.push(Event::Read { pc, address });
+        }
+
+        // CPU
+        if self.debug {
+            println!("----------------------- CPU input -----------------------");
+            println!("in_memory: {}", self.in_m);
+            println!("instruction: {}", instruction);
+            println!("reset: {}", reset);
+        }
+
+        let in_m = self.in_m;
+        self.cpu_mut().set_pins(in_m, instruction, reset);
+        self.scheduler.eval();
+        let CPUOutput {
+            out_m,
+            write_m,
+            address_m,
+            pc: next_pc,
+        } = self.cpu().settled();
+        self.memory_mut().set_pins(out_m, address_m, write_m);
+
+        // rising edge
+        self.scheduler.step();
+        let inputs = (instruction, self.in_m, reset);
+        let outputs = (out_m, write_m, address_m);
+        self.sample_vcd(inputs, outputs);
+
+        if self.debug {
+            println!("----------------------- CPU output -----------------------");
+            println!("out_m: {}", out_m);
+            println!("write_m: {}", write_m);
+            println!("address_m: {:?}", address_m);
+            println!("pc: {:?}", next_pc);
+        }
+
+        // Memory
+        if write_m == I {
+            self.events.push(Event::Write {
+                pc,
+                address: to_address(address_m),
+                value: out_m,
+            });
+        }
+        if self.debug {
+            println!(
+                "memory.tick(input: {}, addr: {:?}, load: {})",
+                out_m, address_m, write_m
+            );
+        }
+
+        // falling edge
+        self.scheduler.step();
+        let in_m = self.memory().output(address_m);
+        self.sample_vcd((instruction, in_m, reset), outputs);
+        if self.debug {
+            println!("{} = memory.output(addr: {:?})", in_m, address_m);
+        }
+
+        if self.debug {
+            println!("");
+        }
+
+        if self.tracing {
+            self.record = Some(Record {
+                pc,
+                instruction,
+                a: self.a(),
+                d: self.d(),
+                out_m,
+                write_m: write_m == I,
+                address_m: to_address(address_m),
+                in_m: self.in_m,
+            });
+        }
+
+        self.pc = next_pc;
+        self.in_m = in_m;
+        write_m
+    }
+
+    fn pc(&self) -> [bit; 15] {
+        self.pc
+    }
+
+    fn a(&self) -> Word {
+        self.cpu().a_output()
+    }
+
+    fn d(&self) -> Word {
+        self.cpu().d_output()
+    }
+
+    fn memory_out(&self, address: [bit; 15]) -> Word {
+        self.memory().output(address)
+    }
+
+    fn framebuffer(&self) -> Framebuffer {
+        self.memory().framebuffer()
+    }
+
+    fn set_frame_rate(&mut self, frame_rate: u32) {
+        self.memory_mut().set_frame_rate(frame_rate);
+    }
+
+    fn flush_display(&mut self) {
+        self.memory_mut().flush();
+    }
+
+    fn poll_display(&mut self) {
+        self.memory_mut().poll();
+    }
+
+    fn take_controls(&mut self) -> Vec<Control> {
+        self.memory_mut().take_controls()
+    }
+
+    fn set_memory(&mut self, address: [bit; 15], input: Word) {
+        self.memory_mut().set(to_address(address), input);
+        self.update_in_m();
+    }
+
+    fn set_pc(&mut self, pc: [bit; 15]) {
+        let mut input = [O; 16];
+        input[1..].copy_from_slice(&pc);
+        self.cpu_mut().set_pc(Word::new(input));
+        self.pc = pc;
+    }
+
+    fn set_a(&mut self, input: Word) {
+        self.cpu_mut().set_a(input);
+        self.update_in_m();
+    }
+
+    fn set_d(&mut self, input: Word) {
+        self.cpu_mut().set_d(input);
+    }
+
+    fn record_events(&mut self, on: bool) {
+        self.events.record(on);
+    }
+
+    fn take_events(&mut self) -> Vec<Event> {
+        self.events.take()
+    }
+
+    fn record_cycles(&mut self, on: bool) {
+        self.tracing = on;
+        if !on {
+            self.record = None;
+        }
+    }
+
+    fn take_record(&mut self) -> Option<Record> {
+        self.record.take()
+    }
+
+    fn snapshot(&self) -> Result<Value, SnapshotError> {
+        Ok(serde_json::to_value(self)?)
+    }
+
+    // chip by chip in place, e.g. to keep the channels of the memory
+    fn restore(&mut self, mut state: Value) -> Result<(), SnapshotError> {
+        self.rom.restore(state["rom"].take())?;
+        *self.cpu_mut() = serde_json::from_value(state["cpu"].take())?;
+        self.memory_mut().restore(state["memory"].take())?;
+        self.pc = serde_json::from_value(state["pc"].take())?;
+        self.in_m = serde_json::from_value(state["in_m"].take())?;
+        Ok(())
+    }
+
+    fn dump_vcd(&mut self, writer: Option<Box<dyn Write>>) -> io::Result<()> {
+        if let Some(vcd) = self.vcd.take() {
+            vcd.finish()?;
+        }
+        if let Some(writer) = writer {
+            self.vcd = Some(Vcd::new(writer, "CPU", SIGNALS)?);
+        }
+        Ok(())
+    }
+}
+
+impl GateMachine {
+    fn cpu(&self) -> &CPU {
+        self.scheduler.get(self.cpu)
+    }
+
+    fn cpu_mut(&mut self) -> &mut CPU {
+        self.scheduler.get_mut(self.cpu)
+    }
+
+    fn memory(&self) -> &Memory {
+        self.scheduler.get(self.memory)
+    }
+
+    fn memory_mut(&mut self) -> &mut Memory {
+        self.scheduler.get_mut(self.memory)
+    }
+
+    // inM is read at the end of execute, so it follows A and memory set from outside
+    fn update_in_m(&mut self) {
+        let a = self.a();
+        let mut address = [O; 15];
+        for (i, b) in address.iter_mut().enumerate() {
+            *b = a[i + 1];
+        }
+        self.in_m = self.memory_out(address);
+    }
+
+    // the registers as they are in the phase of the clock
+    fn sample_vcd(
+        &mut self,
+        (instruction, in_m, reset): (Word, Word, bit),
+        (out_m, write_m, address_m): (Word, bit, [bit; 15]),
+    ) {
+        if let Some(dump) = self.vcd.as_mut() {
+            let cpu: &CPU = self.scheduler.get(self.cpu);
+            let (zr, ng) = cpu.flags();
+            dump.sample(&[
+                vcd::clock(self.scheduler.clock()),
+                u16::from(instruction),
+                u16::from(in_m),
+                vcd::value(reset),
+                u16::from(cpu.a_output()),
+                u16::from(cpu.d_output()),
+                u16::from(cpu.pc_output()),
+                vcd::value(zr),
+                vcd::value(ng),
+                u16::from(out_m),
+                vcd::value(write_m),
+                to_address(address_m),
+            ]);
+        }
+    }
+}
+
+// the chips, as they were fields of the machine
+impl Serialize for GateMachine {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("GateMachine", 5)?;
+        state.serialize_field("rom", &self.rom)?;
+        state.serialize_field("cpu", self.cpu())?;
+        state.serialize_field("memory", self.memory())?;
+        state.serialize_field("pc", &self.pc)?;
+        state.serialize_field("in_m", &self.in_m)?;
+        state.end()
+    }
+}
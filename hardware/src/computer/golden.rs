@@ -0,0 +1,160 @@
+// Golden images: the screen a program drew, compared with a stored PBM,
+// so that tests of drawing programs see more than single RAM words.
+use std::{
+    env,
+    fmt::{self, Formatter},
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    base::screen::{Framebuffer, HEIGHT, WIDTH},
+    computer::Computer,
+};
+
+// set to write the actual screens as the golden images instead of comparing them
+pub const UPDATE: &str = "UPDATE_GOLDEN";
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct GoldenError(pub String);
+
+impl fmt::Display for GoldenError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl From<io::Error> for GoldenError {
+    fn from(e: io::Error) -> Self {
+        GoldenError(e.to_string())
+    }
+}
+
+// On a mismatch the diff image is written to golden.diff.ppm.
+pub fn check(actual: &Framebuffer, golden: &Path) -> Result<(), GoldenError> {
+    if env::var_os(UPDATE).is_some() {
+        return actual
+            .save(golden)
+            .map_err(|e| GoldenError(format!("{}: {}", golden.display(), e)));
+    }
+    let expected = File::open(golden)
+        .and_then(Framebuffer::read_pbm)
+        .map_err(|e| GoldenError(format!("{}: {}", golden.display(), e)))?;
+    let differences = pixels()
+        .filter(|(x, y)| actual.pixel(*x, *y) != expected.pixel(*x, *y))
+        .count();
+    if differences == 0 {
+        return Ok(());
+    }
+    let diff = diff_path(golden);
+    write_diff(actual, &expected, File::create(&diff)?)?;
+    Err(GoldenError(format!(
+        "{} pixels differ from {}, see {}",
+        differences,
+        golden.display(),
+        diff.display()
+    )))
+}
+
+pub fn check_screen<P: AsRef<Path>>(computer: &Computer, golden: P) -> Result<(), GoldenError> {
+    check(&computer.framebuffer(), golden.as_ref())
+}
+
+pub fn assert_screen<P: AsRef<Path>>(computer: &Computer, golden: P) {
+    if let Err(e) = check_screen(computer, golden) {
+        panic!("{}", e);
+    }
+}
+
+// Runs the program for at most cycles, e.g. a game which never ends, then checks the screen.
+pub fn assert_program<P: AsRef<Path>>(
+    computer: &mut Computer,
+    program: &str,
+    cycles: usize,
+    golden: P,
+) {
+    if let Err(e) = computer.load(program) {
+        panic!("{}", e);
+    }
+    computer.run_for(cycles);
+    assert_screen(computer, golden);
+}
+
+// binary PPM: the same pixels in black and white,
+// red where only the golden image is black and blue where only the screen is
+pub fn write_diff<W: Write>(
+    actual: &Framebuffer,
+    expected: &Framebuffer,
+    writer: W,
+) -> io::Result<()> {
+    let mut writer = BufWriter::new(writer);
+    write!(writer, "P6\n{} {}\n255\n", WIDTH, HEIGHT)?;
+    for (x, y) in pixels() {
+        let color = match (actual.pixel(x, y), expected.pixel(x, y)) {
+            (true, true) => [0, 0, 0],
+            (false, false) => [255, 255, 255],
+            (false, true) => [255, 0, 0],
+            (true, false) => [0, 0, 255],
+        };
+        writer.write_all(&color)?;
+    }
+    writer.flush()
+}
+
+fn diff_path(golden: &Path) -> PathBuf {
+    let mut path = golden.as_os_str().to_owned();
+    path.push(".diff.ppm");
+    PathBuf::from(path)
+}
+
+// row by row
+fn pixels() -> impl Iterator<Item = (usize, usize)> {
+    (0..HEIGHT).flat_map(|y| (0..WIDTH).map(move |x| (x, y)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        base::logic::Word,
+        computer::{Backend, SCREEN},
+    };
+    use std::{fs, process};
+
+    #[test]
+    fn for_golden() {
+        // the rectangle program draws 3 rows of 16 pixels
+        let mut computer = Computer::with_backend(None, false, Backend::Fast);
+        assert_program(
+            &mut computer,
+            "src/program/rect.txt",
+            1000,
+            "src/program/rect.pbm",
+        );
+
+        let golden = env::temp_dir().join(format!("for_golden-{}.pbm", process::id()));
+        fs::copy("src/program/rect.pbm", &golden).unwrap();
+        // a row less, and a pixel at (64, 3)
+        computer.set_memory(SCREEN + 64, Word::from(0u16));
+        computer.set_memory(SCREEN + 100, Word::from(1u16));
+        let error = check_screen(&computer, &golden).unwrap_err();
+        assert!(error.0.starts_with("17 pixels differ"), "{}", error);
+
+        let diff = diff_path(&golden);
+        let bytes = fs::read(&diff).unwrap();
+        fs::remove_file(&golden).unwrap();
+        fs::remove_file(&diff).unwrap();
+        let header = format!("P6\n{} {}\n255\n", WIDTH, HEIGHT);
+        assert!(bytes.starts_with(header.as_bytes()));
+        let color = |x: usize, y: usize| {
+            let i = header.len() + (y * WIDTH + x) * 3;
+            bytes[i..i + 3].to_vec()
+        };
+        // the same rows are kept, the missing row is red and the extra pixel blue
+        assert_eq!(color(0, 0), vec![0, 0, 0]);
+        assert_eq!(color(0, 2), vec![255, 0, 0]);
+        assert_eq!(color(64, 3), vec![0, 0, 255]);
+        assert_eq!(color(16, 0), vec![255, 255, 255]);
+    }
+}
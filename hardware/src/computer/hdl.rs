@@ -0,0 +1,322 @@
+#![allow(dead_code)]
+use std::sync::mpsc::{Receiver, Sender};
+
+use crate::{
+    base::{
+        logic::bit::{I, O},
+        logic::{bit, Word},
+    },
+    computer::{fast::FastMachine, trace::Record, Event, Events, Machine},
+    error::HardwareError,
+    hdl::{builtin::pins, chip::Chip, from_word, to_word, HdlError},
+    protocol::Control,
+};
+
+// A CPU chip simulated from HDL, with native ROM and memory.
+// The CPU reads and writes memory through addressM before the clock, as in CPU.hdl of the course.
+pub struct HdlMachine {
+    cpu: Chip,
+    memory: FastMachine,
+    events: Events,
+    // the last cycle while recording cycles
+    tracing: bool,
+    record: Option<Record>,
+    // println
+    debug: bool,
+}
+
+impl HdlMachine {
+    pub fn new(
+        mut cpu: Chip,
+        channel: Option<(Sender<String>, Receiver<String>)>,
+        debug: bool,
+    ) -> Result<Self, HdlError> {
+        if cpu.inputs() != pins(&[("inM", 16), ("instruction", 16), ("reset", 1)]).as_slice()
+            || cpu.outputs()
+                != pins(&[("outM", 16), ("writeM", 1), ("addressM", 15), ("pc", 15)]).as_slice()
+        {
+            return Err(HdlError::new(
+                0,
+                &format!("{} doesn't have the pins of CPU", cpu.name()),
+            ));
+        }
+        // the registers are read and set through these parts, which start at 0 anyway
+        for part in REGISTERS {
+            if !cpu.set_part(part, &[O; 16]) {
+                return Err(HdlError::new(
+                    0,
+                    &format!("{} has no builtin {}", cpu.name(), part),
+                ));
+            }
+        }
+        Ok(Self {
+            cpu,
+            memory: FastMachine::new(channel, debug),
+            events: Events::default(),
+            tracing: false,
+            record: None,
+            debug,
+        })
+    }
+
+    fn output(&self, name: &str) -> u16 {
+        to_u16(&self.cpu.get(name).unwrap_or_default())
+    }
+
+    // one of REGISTERS, checked by new
+    fn register(&self, part: &str) -> Vec<bit> {
+        self.cpu
+            .part_output(part, "out")
+            .expect("the CPU was checked for its registers")
+    }
+
+    fn set_register(&mut self, part: &str, bits: &[bit]) {
+        let loaded = self.cpu.set_part(part, bits);
+        assert!(loaded, "the CPU was checked for its registers");
+    }
+}
+
+// the parts of the CPU for the A/D registers and the program counter
+const REGISTERS: [&str; 3] = ["ARegister", "DRegister", "PC"];
+
+impl Machine for HdlMachine {
+    fn load_words(&mut self, words: &[u16]) -> Result<Word, HardwareError> {
+        self.memory.load_words(words)
+    }
+
+    fn execute(&mut self, reset: bit) -> bit {
+        let pc = to_u16(&self.pc_bits());
+        let instruction = self.memory.fetch(pc);
+        self.cpu
+            .set("instruction", &to_bits(instruction, 16))
+            .unwrap();
+        self.cpu.set("reset", &[reset]).unwrap();
+        self.cpu.eval();
+
+        // addressM only depends on the A register
+        let in_m = self.memory.read(self.output("addressM"));
+        if instruction & 0x9000 == 0x9000 {
+            self.events.push(Event::Read {
+                pc,
+                address: self.output("addressM"),
+            });
+        }
+        self.cpu.set("inM", &to_bits(in_m, 16)).unwrap();
+        self.cpu.eval();
+
+        let out_m = self.output("outM");
+        let write_m = self.output("writeM");
+        let address_m = self.output("addressM");
+        if self.debug {
+            println!("----------------------- CPU -----------------------");
+            println!("in_memory: {}", to_word(&to_bits(in_m, 16)));
+            println!("instruction: {}", to_word(&to_bits(instruction, 16)));
+            println!("out_m: {}", to_word(&to_bits(out_m, 16)));
+            println!("write_m: {}", write_m);
+            println!("address_m: {}", address_m);
+        }
+
+        if write_m == 1 {
+            self.memory.write(address_m, out_m);
+            self.events.push(Event::Write {
+                pc,
+                address: address_m,
+                value: Word::from(out_m),
+            });
+        }
+        self.memory.poll_devices();
+
+        self.cpu.tick();
+        self.cpu.tock();
+        if self.tracing {
+            self.record = Some(Record {
+                pc,
+                instruction: Word::from(instruction),
+                a: self.a(),
+                d: self.d(),
+                out_m: Word::from(out_m),
+                write_m: write_m == 1,
+                address_m,
+                in_m: Word::from(in_m),
+            });
+        }
+        if write_m == 1 {
+            I
+        } else {
+            O
+        }
+    }
+
+    fn pc(&self) -> [bit; 15] {
+        let mut pc = [O; 15];
+        for (i, b) in self.pc_bits().iter().enumerate() {
+            pc[14 - i] = *b;
+        }
+        pc
+    }
+
+    fn a(&self) -> Word {
+        to_word(&self.register("ARegister"))
+    }
+
+    fn d(&self) -> Word {
+        to_word(&self.register("DRegister"))
+    }
+
+    fn memory_out(&self, address: [bit; 15]) -> Word {
+        self.memory.memory_out(address)
+    }
+
+    fn set_memory(&mut self, address: [bit; 15], input: Word) {
+        self.memory.set_memory(address, input)
+    }
+
+    fn set_pc(&mut self, pc: [bit; 15]) {
+        let bits: Vec<bit> = pc.iter().rev().copied().collect();
+        self.set_register("PC", &bits);
+    }
+
+    fn set_a(&mut self, input: Word) {
+        self.set_register("ARegister", &from_word(input));
+    }
+
+    fn set_d(&mut self, input: Word) {
+        self.set_register("DRegister", &from_word(input));
+    }
+
+    fn record_events(&mut self, on: bool) {
+        self.events.record(on);
+    }
+
+    fn take_events(&mut self) -> Vec<Event> {
+        self.events.take()
+    }
+
+    fn record_cycles(&mut self, on: bool) {
+        self.tracing = on;
+        if !on {
+            self.record = None;
+        }
+    }
+
+    fn take_record(&mut self) -> Option<Record> {
+        self.record.take()
+    }
+
+    fn set_frame_rate(&mut self, frame_rate: u32) {
+        self.memory.set_frame_rate(frame_rate);
+    }
+
+    fn flush_display(&mut self) {
+        self.memory.flush_display();
+    }
+
+    fn poll_display(&mut self) {
+        self.memory.poll_display();
+    }
+
+    fn take_controls(&mut self) -> Vec<Control> {
+        self.memory.take_controls()
+    }
+}
+
+impl HdlMachine {
+    fn pc_bits(&self) -> Vec<bit> {
+        self.cpu.get("pc").unwrap_or_default()
+    }
+}
+
+// LSB first
+fn to_bits(value: u16, width: usize) -> Vec<bit> {
+    (0..width)
+        .map(|i| if value & (1 << i) != 0 { I } else { O })
+        .collect()
+}
+
+fn to_u16(bits: &[bit]) -> u16 {
+    bits.iter()
+        .enumerate()
+        .fold(0, |sum, (i, b)| if *b == I { sum | (1 << i) } else { sum })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        computer::Computer,
+        hdl::{
+            load,
+            script::{in_temp, run_with_output},
+        },
+    };
+
+    #[test]
+    fn for_hdl_machine() {
+        let cpu = load("src/hdl/tests/CPU.hdl").unwrap();
+        let machine = HdlMachine::new(cpu, None, false).unwrap();
+        let mut computer = Computer::with_machine(Box::new(machine));
+        computer.run("src/program/max.txt", false).unwrap();
+        let r0 = computer.memory_out("000000000000010").unwrap();
+        assert_eq!(r0, Word::from("0000000001000011"));
+    }
+
+    #[test]
+    fn for_hdl_machine_registers() {
+        let cpu = load("src/hdl/tests/CPU.hdl").unwrap();
+        let mut machine = HdlMachine::new(cpu, None, false).unwrap();
+        machine.load("src/program/add.txt").unwrap();
+        machine.execute(O);
+        machine.execute(O);
+        assert_eq!(machine.a(), Word::from("0000000000000010"));
+        assert_eq!(machine.d(), Word::from("0000000000000010"));
+        assert_eq!(machine.pc(), [O, O, O, O, O, O, O, O, O, O, O, O, O, I, O]);
+    }
+
+    #[test]
+    fn for_hdl_machine_events() {
+        let cpu = load("src/hdl/tests/CPU.hdl").unwrap();
+        let mut machine = HdlMachine::new(cpu, None, false).unwrap();
+        machine.load("src/program/add.txt").unwrap();
+        machine.record_events(true);
+        for _ in 0..6 {
+            machine.execute(O);
+        }
+        // M=D
+        assert_eq!(
+            machine.take_events(),
+            vec![Event::Write {
+                pc: 5,
+                address: 0,
+                value: Word::from(5u16)
+            }]
+        );
+        assert_eq!(machine.take_events(), Vec::new());
+    }
+
+    #[test]
+    fn for_hdl_machine_script() {
+        let cpu = load("src/hdl/tests/CPU.hdl").unwrap();
+        let machine = HdlMachine::new(cpu, None, false).unwrap();
+        let report = in_temp("for_hdl_machine_script", |dir| {
+            run_with_output(
+                "src/program/Max.tst",
+                Computer::with_machine(Box::new(machine)),
+                dir,
+            )
+        });
+        assert!(report.unwrap().passed());
+    }
+
+    #[test]
+    fn for_hdl_machine_pins() {
+        let and = load("src/hdl/tests/And.hdl").unwrap();
+        assert!(HdlMachine::new(and, None, false).is_err());
+
+        // the pins of CPU without its registers
+        let builtin = Chip::builtin("CPU").unwrap();
+        assert_eq!(
+            HdlMachine::new(builtin, None, false).err().unwrap().message,
+            "CPU has no builtin ARegister"
+        );
+    }
+}
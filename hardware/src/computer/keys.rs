@@ -0,0 +1,163 @@
+// Key presses at given cycles instead of from a display, so that interactive programs
+// run the same way every time. A script has an event per line:
+//
+//   # cycle press|release [key]
+//   10000 press a
+//   12000 release
+//   15000 press ArrowLeft
+use std::{fs, path::Path};
+
+use crate::{base::keyboard, error::HardwareError};
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct KeyEvent {
+    // before the instruction of the cycle, counted from load
+    pub cycle: u64,
+    // None releases the key
+    pub key: Option<u16>,
+}
+
+/// The events of `Computer::script_keys`, in the order of their cycles.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct KeyScript {
+    events: Vec<KeyEvent>,
+    // the first event which hasn't happened
+    next: usize,
+}
+
+// A comment starts with a # at the start of a line or after whitespace,
+// except the key of press, so that # can be pressed.
+fn words(line: &str) -> Vec<&str> {
+    let mut words = Vec::new();
+    for word in line.split_whitespace() {
+        if word.starts_with('#') && words.last() != Some(&"press") {
+            break;
+        }
+        words.push(word);
+    }
+    words
+}
+
+impl KeyScript {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self, HardwareError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|e| HardwareError::new(&e.to_string()).in_file(path))?;
+        KeyScript::parse(&text).map_err(|e| e.in_file(path))
+    }
+
+    pub fn parse(text: &str) -> Result<Self, HardwareError> {
+        let mut script = KeyScript::new();
+        for (i, line) in text.lines().enumerate() {
+            let words = words(line);
+            if words.is_empty() {
+                continue;
+            }
+            let error = |message: &str| HardwareError::at(i + 1, line, message);
+            let cycle = words[0]
+                .parse::<u64>()
+                .map_err(|_| error(&format!("expected a cycle but it was {}", words[0])))?;
+            match words[1..] {
+                ["press", key] => {
+                    let code = keyboard::code(key)
+                        .ok_or_else(|| error(&format!("unknown key {}", key)))?;
+                    script.press(cycle, code);
+                }
+                ["release"] => script.release(cycle),
+                _ => {
+                    return Err(error(&format!(
+                        "expected press <key> or release but it was {}",
+                        words[1..].join(" ")
+                    )))
+                }
+            }
+        }
+        Ok(script)
+    }
+
+    pub fn press(&mut self, cycle: u64, key: u16) {
+        self.insert(KeyEvent {
+            cycle,
+            key: Some(key),
+        });
+    }
+
+    pub fn release(&mut self, cycle: u64) {
+        self.insert(KeyEvent { cycle, key: None });
+    }
+
+    pub fn events(&self) -> &[KeyEvent] {
+        &self.events
+    }
+
+    // the events up to the cycle, once each
+    pub(crate) fn due(&mut self, cycle: u64) -> &[KeyEvent] {
+        let start = self.next;
+        while self.next < self.events.len() && self.events[self.next].cycle <= cycle {
+            self.next += 1;
+        }
+        &self.events[start..self.next]
+    }
+
+    pub(crate) fn is_done(&self) -> bool {
+        self.next == self.events.len()
+    }
+
+    // after the events of the same cycle
+    fn insert(&mut self, event: KeyEvent) {
+        let i = self.events.partition_point(|e| e.cycle <= event.cycle);
+        self.events.insert(i, event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn for_key_script() {
+        let script = KeyScript::parse(
+            "# Pong
+             12000 release
+             10000 press a  # serve
+             15000 press ArrowLeft
+             16000 press F12
+             17000 press Space
+             18000 press #  # hash",
+        )
+        .unwrap();
+        assert_eq!(
+            script
+                .events()
+                .iter()
+                .map(|e| (e.cycle, e.key))
+                .collect::<Vec<_>>(),
+            vec![
+                (10000, Some(97)),
+                (12000, None),
+                (15000, Some(130)),
+                (16000, Some(152)),
+                (17000, Some(32)),
+                (18000, Some(35)),
+            ]
+        );
+
+        let mut script = script;
+        assert!(script.due(9999).is_empty());
+        assert_eq!(script.due(12000).len(), 2);
+        assert!(script.due(12000).is_empty());
+        assert!(!script.is_done());
+
+        assert_eq!(
+            KeyScript::parse("10 press Tab"),
+            Err(HardwareError::at(1, "10 press Tab", "unknown key Tab"))
+        );
+        assert!(KeyScript::parse("\nten press a").is_err());
+        assert!(KeyScript::parse("10 press").is_err());
+        assert!(KeyScript::parse("10 release a").is_err());
+    }
+}
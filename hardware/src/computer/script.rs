@@ -0,0 +1,155 @@
+#![allow(dead_code)]
+// CPUEmulator test scripts: the pins are RAM[n], PC, A and D, and ticktock executes an instruction.
+use std::path::Path;
+
+use assembler::parser::Parser;
+
+use crate::{
+    base::image,
+    base::logic::bit,
+    base::logic::bit::{I, O},
+    computer::{Backend, Computer},
+    hdl::{
+        from_word, msb_first,
+        script::{run_with, run_with_output, Report, Target},
+        to_word, HdlError,
+    },
+};
+
+// run a CPUEmulator .tst file on a new computer
+pub fn run(filename: &str, backend: Backend) -> Result<Report, HdlError> {
+    run_with(filename, Computer::with_backend(None, false, backend))
+}
+
+// with the output file in output_dir
+pub fn run_to(filename: &str, backend: Backend, output_dir: &Path) -> Result<Report, HdlError> {
+    run_with_output(
+        filename,
+        Computer::with_backend(None, false, backend),
+        output_dir,
+    )
+}
+
+impl Target for Computer {
+    // .asm is assembled before loading, like CPUEmulator
+    fn load(&mut self, path: &Path) -> Result<(), HdlError> {
+        if !path.exists() {
+            return Err(HdlError::new(
+                0,
+                &format!("Fail to open {}", path.display()),
+            ));
+        }
+        if path.extension().is_some_and(|extension| extension == "asm") {
+            let parsed = Parser::new().run(&path.to_string_lossy());
+            image::parse_hack(&parsed.to_string())
+                .and_then(|words| self.load_words(&words))
+                .map_err(|e| HdlError::new(0, &e.in_file(path).to_string()))
+        } else {
+            Computer::load(self, &path.to_string_lossy())
+                .map_err(|e| HdlError::new(0, &e.to_string()))
+        }
+    }
+
+    fn get(&self, pin: &str) -> Result<Vec<bit>, HdlError> {
+        Ok(match pin {
+            "PC" => self.machine.pc().iter().rev().copied().collect(),
+            "A" => from_word(self.machine.a()),
+            "D" => from_word(self.machine.d()),
+            _ => from_word(self.machine.memory_out(ram_address(pin)?)),
+        })
+    }
+
+    fn set(&mut self, pin: &str, bits: &[bit]) -> Result<(), HdlError> {
+        match pin {
+            "PC" => self.machine.set_pc(msb_first(bits)),
+            "A" => self.machine.set_a(to_word(bits)),
+            "D" => self.machine.set_d(to_word(bits)),
+            _ => {
+                let address = ram_address(pin)?;
+                self.machine.set_memory(address, to_word(bits));
+            }
+        }
+        self.history.clear();
+        Ok(())
+    }
+
+    fn eval(&mut self) -> Result<(), HdlError> {
+        Err(HdlError::new(0, "eval is for chips, use ticktock"))
+    }
+
+    // an instruction is executed at once on tick
+    fn tick(&mut self) -> Result<(), HdlError> {
+        self.machine.execute(self.reset);
+        Ok(())
+    }
+
+    fn tock(&mut self) -> Result<(), HdlError> {
+        Ok(())
+    }
+}
+
+// RAM[n] -> address
+fn ram_address(pin: &str) -> Result<[bit; 15], HdlError> {
+    let address = pin
+        .strip_prefix("RAM[")
+        .and_then(|pin| pin.strip_suffix(']'))
+        .and_then(|n| n.parse::<u16>().ok())
+        .filter(|n| *n <= 24576)
+        .ok_or_else(|| HdlError::new(0, &format!("computer has no pin {}", pin)))?;
+    let bits: Vec<bit> = (0..15)
+        .map(|i| if address & (1 << i) != 0 { I } else { O })
+        .collect();
+    Ok(msb_first(&bits))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{base::logic::Word, hdl::script::in_temp};
+
+    // the output files go to a temp dir, not next to the scripts
+    fn run_in_temp(filename: &str, backend: Backend) -> Report {
+        let name = Path::new(filename).file_stem().unwrap().to_string_lossy();
+        in_temp(&format!("computer-script-{}-{:?}", name, backend), |dir| {
+            run_to(filename, backend, dir).unwrap()
+        })
+    }
+
+    #[test]
+    fn for_script_mult() {
+        let report = run_in_temp("src/program/Mult.tst", Backend::Fast);
+        assert!(report.passed(), "{:?}", report.mismatches);
+    }
+
+    #[test]
+    fn for_script_max() {
+        for backend in [Backend::Fast, Backend::Gate].iter() {
+            let report = run_in_temp("src/program/Max.tst", *backend);
+            assert!(report.passed(), "{:?}: {:?}", backend, report.mismatches);
+        }
+        let report = run_in_temp("src/program/Max.tst", Backend::Fast);
+        assert_eq!(
+            report.output[1],
+            "|       3  |       5  |       5  |  14|     14 |      5 |"
+        );
+    }
+
+    #[test]
+    fn for_script_fill() {
+        let report = run_in_temp("src/program/FillAutomatic.tst", Backend::Fast);
+        assert!(report.passed(), "{:?}", report.mismatches);
+    }
+
+    #[test]
+    fn for_script_pins() {
+        let mut computer = Computer::with_backend(None, false, Backend::Fast);
+        assert_eq!(
+            computer.set("RAM[24577]", &[O; 16]).err(),
+            Some(HdlError::new(0, "computer has no pin RAM[24577]"))
+        );
+        computer
+            .set("A", &from_word(Word::from("0000000000000111")))
+            .unwrap();
+        assert_eq!(computer.a(), Word::from("0000000000000111"));
+    }
+}
@@ -0,0 +1,69 @@
+// Snapshots of a whole computer: the machine with its ROM, memory and registers,
+// and the loaded program, saved as JSON.
+use std::{
+    fmt::{self, Formatter},
+    io::{self, BufReader, BufWriter, Read, Write},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    base::logic::{bit, Word},
+    computer::Computer,
+};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct SnapshotError(pub String);
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl From<io::Error> for SnapshotError {
+    fn from(e: io::Error) -> Self {
+        SnapshotError(e.to_string())
+    }
+}
+
+impl From<serde_json::Error> for SnapshotError {
+    fn from(e: serde_json::Error) -> Self {
+        SnapshotError(e.to_string())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Snapshot {
+    instruction_num: Word,
+    reset: bit,
+    // Machine::snapshot
+    machine: Value,
+}
+
+impl Computer {
+    // e.g. to resume a long program later with restore
+    pub fn save<W: Write>(&self, writer: W) -> Result<(), SnapshotError> {
+        let snapshot = Snapshot {
+            instruction_num: self.instruction_num,
+            reset: self.reset,
+            machine: self.machine.snapshot()?,
+        };
+        let mut writer = BufWriter::new(writer);
+        serde_json::to_writer(&mut writer, &snapshot)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    // The snapshot must come from the same backend.
+    // Watches, traces and the channel of the display are kept.
+    pub fn restore<R: Read>(&mut self, reader: R) -> Result<(), SnapshotError> {
+        let snapshot: Snapshot = serde_json::from_reader(BufReader::new(reader))?;
+        self.machine.restore(snapshot.machine)?;
+        self.instruction_num = snapshot.instruction_num;
+        self.reset = snapshot.reset;
+        self.history.clear();
+        Ok(())
+    }
+}
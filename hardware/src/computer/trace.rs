@@ -0,0 +1,259 @@
+use std::{
+    fmt::{self, Formatter},
+    fs,
+    io::{self, BufWriter, Write},
+    ops::Range,
+    path::Path,
+};
+
+use serde_json::{json, Value};
+
+use crate::base::logic::Word;
+
+// the first bytes of a binary trace, followed by RECORD_SIZE bytes per cycle
+const MAGIC: &[u8; 8] = b"HACKTRC1";
+const RECORD_SIZE: usize = 15;
+
+/// The CPU pins of one cycle, as printed by `debug: true`.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Record {
+    // the address of the instruction
+    pub pc: u16,
+    pub instruction: Word,
+    // the registers after the cycle
+    pub a: Word,
+    pub d: Word,
+    pub out_m: Word,
+    pub write_m: bool,
+    pub address_m: u16,
+    // the memory value the CPU read
+    pub in_m: Word,
+}
+
+impl Record {
+    fn to_json(self) -> String {
+        json!({
+            "pc": self.pc,
+            "instruction": u16::from(self.instruction),
+            "A": u16::from(self.a),
+            "D": u16::from(self.d),
+            "outM": u16::from(self.out_m),
+            "writeM": self.write_m,
+            "addressM": self.address_m,
+            "inM": u16::from(self.in_m),
+        })
+        .to_string()
+    }
+
+    fn from_json(line: &str) -> Result<Self, TraceError> {
+        let v: Value =
+            serde_json::from_str(line).map_err(|e| TraceError(format!("{}: {}", e, line)))?;
+        let number = |key: &str| -> Result<u16, TraceError> {
+            v[key]
+                .as_u64()
+                .filter(|n| *n <= u64::from(u16::MAX))
+                .map(|n| n as u16)
+                .ok_or_else(|| TraceError(format!("expected a 16 bit {} in {}", key, line)))
+        };
+        Ok(Self {
+            pc: number("pc")?,
+            instruction: Word::from(number("instruction")?),
+            a: Word::from(number("A")?),
+            d: Word::from(number("D")?),
+            out_m: Word::from(number("outM")?),
+            write_m: v["writeM"]
+                .as_bool()
+                .ok_or_else(|| TraceError(format!("expected a bool writeM in {}", line)))?,
+            address_m: number("addressM")?,
+            in_m: Word::from(number("inM")?),
+        })
+    }
+
+    // little endian u16s, then writeM
+    fn to_bytes(self) -> [u8; RECORD_SIZE] {
+        let mut bytes = [0; RECORD_SIZE];
+        let values = [
+            self.pc,
+            u16::from(self.instruction),
+            u16::from(self.a),
+            u16::from(self.d),
+            u16::from(self.out_m),
+            self.address_m,
+            u16::from(self.in_m),
+        ];
+        for (i, value) in values.iter().enumerate() {
+            bytes[i * 2..i * 2 + 2].copy_from_slice(&value.to_le_bytes());
+        }
+        bytes[RECORD_SIZE - 1] = self.write_m as u8;
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Self {
+        let value = |i: usize| u16::from_le_bytes([bytes[i * 2], bytes[i * 2 + 1]]);
+        Self {
+            pc: value(0),
+            instruction: Word::from(value(1)),
+            a: Word::from(value(2)),
+            d: Word::from(value(3)),
+            out_m: Word::from(value(4)),
+            address_m: value(5),
+            in_m: Word::from(value(6)),
+            write_m: bytes[RECORD_SIZE - 1] != 0,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Format {
+    // a JSON object per line
+    Json,
+    // RECORD_SIZE bytes per cycle
+    Binary,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct TraceError(pub String);
+
+impl fmt::Display for TraceError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl From<io::Error> for TraceError {
+    fn from(e: io::Error) -> Self {
+        TraceError(e.to_string())
+    }
+}
+
+// Writes the records of Computer::trace. The first error is kept for finish,
+// so that a failing writer doesn't stop the computer.
+pub(crate) struct TraceWriter {
+    writer: BufWriter<Box<dyn Write>>,
+    format: Format,
+    error: Option<io::Error>,
+}
+
+impl TraceWriter {
+    pub(crate) fn new(writer: Box<dyn Write>, format: Format) -> Self {
+        let mut trace = Self {
+            writer: BufWriter::new(writer),
+            format,
+            error: None,
+        };
+        if format == Format::Binary {
+            let result = trace.writer.write_all(MAGIC);
+            trace.keep(result);
+        }
+        trace
+    }
+
+    pub(crate) fn write(&mut self, record: &Record) {
+        if self.error.is_some() {
+            return;
+        }
+        let result = match self.format {
+            Format::Json => writeln!(self.writer, "{}", record.to_json()),
+            Format::Binary => self.writer.write_all(&record.to_bytes()),
+        };
+        self.keep(result);
+    }
+
+    pub(crate) fn finish(mut self) -> io::Result<()> {
+        let result = self.writer.flush();
+        self.keep(result);
+        match self.error {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    fn keep(&mut self, result: io::Result<()>) {
+        if let Err(e) = result {
+            self.error.get_or_insert(e);
+        }
+    }
+}
+
+/// A trace written by `Computer::trace`, in either format.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Trace {
+    records: Vec<Record>,
+}
+
+impl Trace {
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self, TraceError> {
+        Trace::from_bytes(&fs::read(path)?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TraceError> {
+        let records = if let Some(body) = bytes.strip_prefix(MAGIC.as_ref()) {
+            if body.len() % RECORD_SIZE != 0 {
+                return Err(TraceError(format!(
+                    "expected records of {} bytes but the trace has {} bytes",
+                    RECORD_SIZE,
+                    body.len()
+                )));
+            }
+            body.chunks(RECORD_SIZE).map(Record::from_bytes).collect()
+        } else {
+            let text = std::str::from_utf8(bytes).map_err(|e| TraceError(e.to_string()))?;
+            text.lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(Record::from_json)
+                .collect::<Result<_, _>>()?
+        };
+        Ok(Self { records })
+    }
+
+    pub fn records(&self) -> &[Record] {
+        &self.records
+    }
+
+    // the cycles which executed an instruction in the range
+    pub fn pc(&self, range: Range<u16>) -> impl Iterator<Item = &Record> {
+        self.records.iter().filter(move |r| range.contains(&r.pc))
+    }
+
+    // the cycles which wrote the address
+    pub fn writes(&self, address: u16) -> impl Iterator<Item = &Record> {
+        self.records
+            .iter()
+            .filter(move |r| r.write_m && r.address_m == address)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn for_trace() {
+        let record = Record {
+            pc: 7,
+            instruction: Word::from(0b1111110000010000u16),
+            a: Word::from(24576u16),
+            d: Word::from(-1i16),
+            out_m: Word::from(-1i16),
+            write_m: false,
+            address_m: 24576,
+            in_m: Word::from(-1i16),
+        };
+        assert_eq!(Record::from_json(&record.to_json()), Ok(record));
+        assert_eq!(Record::from_bytes(&record.to_bytes()), record);
+        assert_eq!(
+            record.to_json(),
+            r#"{"A":24576,"D":65535,"addressM":24576,"inM":65535,"instruction":64528,"outM":65535,"pc":7,"writeM":false}"#
+        );
+
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&record.to_bytes());
+        assert_eq!(
+            Trace::from_bytes(&bytes).map(|trace| trace.records),
+            Ok(vec![record])
+        );
+        bytes.pop();
+        assert!(Trace::from_bytes(&bytes).is_err());
+        assert!(Trace::from_bytes(br#"{"pc":70000}"#).is_err());
+    }
+}
@@ -0,0 +1,518 @@
+#![allow(dead_code)]
+// Logic depth of combinational chips in nand levels, when built with the profile feature.
+// The chip is evaluated once to record its nands, and then once for every input bit and nand
+// with that one flipped and the other nands forced to their recorded outputs,
+// so the nands whose inputs change are the ones wired to it.
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    fmt::{self, Formatter},
+    sync::atomic::Ordering,
+    time::Duration,
+};
+
+use crate::{
+    base::{
+        chip::part,
+        cpu::CPU,
+        logic::bit,
+        logic::bit::{I, O},
+    },
+    hdl::{
+        builtin::{builtin, pins},
+        from_word,
+        parser::PinDecl,
+        to_word,
+    },
+    profile::PROFILING,
+};
+
+#[derive(Debug, PartialEq)]
+pub struct DepthError(pub String);
+
+impl fmt::Display for DepthError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+struct Call {
+    a: bit,
+    b: bit,
+    out: bit,
+    // the part of the chip which evaluated it, None for the gates of the chip itself
+    chip: Option<Cow<'static, str>>,
+}
+
+enum Mode {
+    Record,
+    // flip the output of a nand
+    Force(Option<usize>),
+}
+
+struct Trace {
+    mode: Mode,
+    calls: Vec<Call>,
+    count: usize,
+    // nands whose inputs differ from the record
+    changed: Vec<usize>,
+    chips: Vec<Cow<'static, str>>,
+}
+
+thread_local! {
+    static TRACE: RefCell<Option<Trace>> = const { RefCell::new(None) };
+}
+
+// true if the chip is pushed, then leave() has to be called
+pub(crate) fn enter(name: Cow<'static, str>) -> bool {
+    TRACE.with(|t| match t.borrow_mut().as_mut() {
+        Some(trace) if matches!(trace.mode, Mode::Record) => {
+            trace.chips.push(name);
+            true
+        }
+        _ => false,
+    })
+}
+
+pub(crate) fn leave() {
+    TRACE.with(|t| {
+        if let Some(trace) = t.borrow_mut().as_mut() {
+            trace.chips.pop();
+        }
+    });
+}
+
+pub(crate) fn nand(a: bit, b: bit, out: bit) -> bit {
+    TRACE.with(|t| {
+        let mut t = t.borrow_mut();
+        let trace = match t.as_mut() {
+            Some(trace) => trace,
+            None => return out,
+        };
+        let index = trace.count;
+        trace.count += 1;
+        match trace.mode {
+            Mode::Record => {
+                // the part of the chip under analysis, or the chip itself for its own gates
+                let chip = trace.chips.get(1).or_else(|| trace.chips.first()).cloned();
+                trace.calls.push(Call { a, b, out, chip });
+                out
+            }
+            Mode::Force(flip) => match trace.calls.get(index) {
+                Some(call) => {
+                    if call.a != a || call.b != b {
+                        trace.changed.push(index);
+                    }
+                    match (flip == Some(index), call.out) {
+                        (true, O) => I,
+                        (true, I) => O,
+                        (false, out) => out,
+                    }
+                }
+                None => out,
+            },
+        }
+    })
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Path {
+    // e.g. x[0] or zr, and - for registers and constants
+    pub from: String,
+    pub to: String,
+    pub nands: usize,
+    // the parts it goes through and its nands in each
+    pub chips: Vec<(String, usize)>,
+}
+
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let chips: Vec<String> = self
+            .chips
+            .iter()
+            .map(|(chip, nands)| format!("{}({})", chip, nands))
+            .collect();
+        write!(
+            f,
+            "{} -> {}: {} nands, {}",
+            self.from,
+            self.to,
+            self.nands,
+            chips.join(" > ")
+        )
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Depth {
+    pub name: String,
+    // nands evaluated by the chip
+    pub nands: usize,
+    // the longest path from every input pin to every output pin which depends on it
+    pub paths: Vec<Path>,
+    // the longest path in the chip, which may also start or end at its registers
+    pub critical: Path,
+}
+
+impl Depth {
+    pub fn path(&self, from: &str, to: &str) -> Option<&Path> {
+        self.paths
+            .iter()
+            .find(|path| pin(&path.from) == from && pin(&path.to) == to)
+    }
+
+    // Hz, when every nand takes gate_delay
+    pub fn max_frequency(&self, gate_delay: Duration) -> f64 {
+        1.0 / (self.critical.nands as f64 * gate_delay.as_secs_f64())
+    }
+}
+
+impl fmt::Display for Depth {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}: {} nands", self.name, self.nands)?;
+        for path in &self.paths {
+            writeln!(f, "{}", path)?;
+        }
+        writeln!(f, "critical path {}", self.critical)
+    }
+}
+
+// x[0] -> x
+fn pin(name: &str) -> &str {
+    name.split('[').next().unwrap_or(name)
+}
+
+fn bit_names(pins: &[PinDecl]) -> Vec<String> {
+    pins.iter()
+        .flat_map(|pin| {
+            (0..pin.width).map(move |i| {
+                if pin.width == 1 {
+                    pin.name.clone()
+                } else {
+                    format!("{}[{}]", pin.name, i)
+                }
+            })
+        })
+        .collect()
+}
+
+// the nands and output bits wired to an input bit or a nand
+#[derive(Default)]
+struct Wires {
+    nands: Vec<usize>,
+    outputs: Vec<usize>,
+}
+
+fn run<F>(chip: &mut F, mode: Mode, calls: Vec<Call>, inputs: &[Vec<bit>]) -> (Trace, Vec<bit>)
+where
+    F: FnMut(&[Vec<bit>]) -> Vec<Vec<bit>>,
+{
+    TRACE.with(|t| {
+        *t.borrow_mut() = Some(Trace {
+            mode,
+            calls,
+            count: 0,
+            changed: Vec::new(),
+            chips: Vec::new(),
+        })
+    });
+    let outputs = chip(inputs).concat();
+    let trace = TRACE.with(|t| t.borrow_mut().take()).unwrap();
+    (trace, outputs)
+}
+
+// Buses are LSB first like the builtin chips.
+pub fn analyze<F>(
+    name: &str,
+    inputs: &[PinDecl],
+    outputs: &[PinDecl],
+    mut chip: F,
+) -> Result<Depth, DepthError>
+where
+    F: FnMut(&[Vec<bit>]) -> Vec<Vec<bit>>,
+{
+    if !cfg!(feature = "profile") {
+        return Err(DepthError(
+            "nands are traced with the profile feature".to_string(),
+        ));
+    }
+    PROFILING.fetch_add(1, Ordering::Relaxed);
+    let wires = trace(name, inputs, &mut chip);
+    PROFILING.fetch_sub(1, Ordering::Relaxed);
+    let (from_inputs, from_nands, chips) = wires?;
+    Ok(longest_paths(
+        name,
+        &bit_names(inputs),
+        &bit_names(outputs),
+        &from_inputs,
+        &from_nands,
+        &chips,
+    ))
+}
+
+type Wiring = (Vec<Wires>, Vec<Wires>, Vec<Option<Cow<'static, str>>>);
+
+fn trace<F>(name: &str, inputs: &[PinDecl], chip: &mut F) -> Result<Wiring, DepthError>
+where
+    F: FnMut(&[Vec<bit>]) -> Vec<Vec<bit>>,
+{
+    let values: Vec<Vec<bit>> = inputs.iter().map(|pin| vec![O; pin.width]).collect();
+    let (mut recorded, outputs) = run(chip, Mode::Record, Vec::new(), &values);
+
+    // flip each of the sources, i.e. input bits and then nands
+    let input_bits: Vec<(usize, usize)> = inputs
+        .iter()
+        .enumerate()
+        .flat_map(|(i, pin)| (0..pin.width).map(move |j| (i, j)))
+        .collect();
+    let mut wires = Vec::new();
+    for source in 0..input_bits.len() + recorded.calls.len() {
+        let mut flipped = values.clone();
+        let flip = match input_bits.get(source) {
+            Some(&(i, j)) => {
+                flipped[i][j] = if flipped[i][j] == O { I } else { O };
+                None
+            }
+            None => Some(source - input_bits.len()),
+        };
+        let (trace, forced) = run(chip, Mode::Force(flip), recorded.calls, &flipped);
+        if trace.count != trace.calls.len() {
+            return Err(DepthError(format!(
+                "{} doesn't evaluate the same nands every time",
+                name
+            )));
+        }
+        recorded.calls = trace.calls;
+        wires.push(Wires {
+            nands: trace.changed,
+            outputs: (0..outputs.len())
+                .filter(|&i| forced[i] != outputs[i])
+                .collect(),
+        });
+    }
+
+    let from_nands = wires.split_off(input_bits.len());
+    let chips = recorded.calls.into_iter().map(|call| call.chip).collect();
+    Ok((wires, from_nands, chips))
+}
+
+fn longest_paths(
+    name: &str,
+    input_names: &[String],
+    output_names: &[String],
+    from_inputs: &[Wires],
+    from_nands: &[Wires],
+    chips: &[Option<Cow<'static, str>>],
+) -> Depth {
+    // the nands which drive each output bit
+    let mut drivers = vec![Vec::new(); output_names.len()];
+    for (k, wires) in from_nands.iter().enumerate() {
+        for &output in &wires.outputs {
+            drivers[output].push(k);
+        }
+    }
+    // nands are evaluated after the ones they're wired from, so a single pass is enough
+    let longest = |starts: &[usize]| {
+        // (nands, previous nand)
+        let mut depth: Vec<Option<(usize, Option<usize>)>> = vec![None; from_nands.len()];
+        for &k in starts {
+            depth[k] = Some((1, None));
+        }
+        for k in 0..from_nands.len() {
+            if let Some((nands, _)) = depth[k] {
+                for &m in &from_nands[k].nands {
+                    if depth[m].is_none_or(|(longest, _)| nands + 1 > longest) {
+                        depth[m] = Some((nands + 1, Some(k)));
+                    }
+                }
+            }
+        }
+        depth
+    };
+    let route = |depth: &[Option<(usize, Option<usize>)>], end: usize| {
+        let mut route: Vec<(String, usize)> = Vec::new();
+        let mut k = Some(end);
+        let mut start = end;
+        while let Some(nand) = k {
+            let part = chips[nand].as_deref().unwrap_or(name);
+            match route.last_mut() {
+                Some((chip, nands)) if chip == part => *nands += 1,
+                _ => route.push((part.to_string(), 1)),
+            }
+            start = nand;
+            k = depth[nand].and_then(|(_, previous)| previous);
+        }
+        route.reverse();
+        (route, start)
+    };
+
+    let mut paths: Vec<Path> = Vec::new();
+    for (i, wires) in from_inputs.iter().enumerate() {
+        let depth = longest(&wires.nands);
+        for (j, output) in output_names.iter().enumerate() {
+            let end = drivers[j]
+                .iter()
+                .filter_map(|&k| depth[k].map(|(nands, _)| (nands, k)))
+                .max();
+            let path = match end {
+                Some((nands, k)) => Path {
+                    from: input_names[i].clone(),
+                    to: output.clone(),
+                    nands,
+                    chips: route(&depth, k).0,
+                },
+                None if wires.outputs.contains(&j) => Path {
+                    from: input_names[i].clone(),
+                    to: output.clone(),
+                    nands: 0,
+                    chips: Vec::new(),
+                },
+                None => continue,
+            };
+            match paths
+                .iter_mut()
+                .find(|p| pin(&p.from) == pin(&path.from) && pin(&p.to) == pin(&path.to))
+            {
+                Some(longest) if longest.nands < path.nands => *longest = path,
+                Some(_) => (),
+                None => paths.push(path),
+            }
+        }
+    }
+
+    let all: Vec<usize> = (0..from_nands.len()).collect();
+    let depth = longest(&all);
+    let critical = match depth
+        .iter()
+        .enumerate()
+        .filter_map(|(k, d)| d.map(|(nands, _)| (nands, k)))
+        .max()
+    {
+        Some((nands, end)) => {
+            let (chips, start) = route(&depth, end);
+            let from = from_inputs
+                .iter()
+                .position(|wires| wires.nands.contains(&start))
+                .map_or("-".to_string(), |i| input_names[i].clone());
+            let to = drivers
+                .iter()
+                .position(|drivers| drivers.contains(&end))
+                .map_or("-".to_string(), |j| output_names[j].clone());
+            Path {
+                from,
+                to,
+                nands,
+                chips,
+            }
+        }
+        None => Path {
+            from: "-".to_string(),
+            to: "-".to_string(),
+            nands: 0,
+            chips: Vec::new(),
+        },
+    };
+
+    Depth {
+        name: name.to_string(),
+        nands: from_nands.len(),
+        paths,
+        critical,
+    }
+}
+
+// a combinational builtin chip, e.g. ALU, Add16 or Mux8Way16, or the CPU
+pub fn chip(name: &str) -> Result<Depth, DepthError> {
+    if name == "CPU" {
+        return cpu();
+    }
+    match builtin(name) {
+        Some(decl) => analyze(name, &decl.inputs, &decl.outputs, decl.gate),
+        None if part(name).is_some() => Err(DepthError(format!("{} isn't combinational", name))),
+        None => Err(DepthError(format!("{} isn't a builtin chip", name))),
+    }
+}
+
+// One cycle of the CPU, from its inputs and registers to its outputs and registers.
+// The critical path bounds its clock.
+pub fn cpu() -> Result<Depth, DepthError> {
+    analyze(
+        "CPU",
+        &pins(&[("inM", 16), ("instruction", 16), ("reset", 1)]),
+        &pins(&[("outM", 16), ("writeM", 1), ("addressM", 15), ("pc", 15)]),
+        |i| {
+            let output = CPU::new().output(to_word(&i[0]), to_word(&i[1]), i[2][0]);
+            vec![
+                from_word(output.out_m),
+                vec![output.write_m],
+                output.address_m.iter().rev().copied().collect(),
+                output.pc.iter().rev().copied().collect(),
+            ]
+        },
+    )
+}
+
+#[cfg(all(test, feature = "profile"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn for_depth() {
+        // xor is and(or(a, b), nand(a, b)), and or and and are 2 nands deep
+        let depth = chip("Xor").unwrap();
+        assert_eq!(depth.nands, 6);
+        assert_eq!(
+            depth.path("a", "out").unwrap().to_string(),
+            "a -> out: 4 nands, Xor(4)"
+        );
+
+        // the carry ripples through a half adder (and) and 15 full adders (and, and, or)
+        let depth = chip("Add16").unwrap();
+        let path = depth.path("a", "out").unwrap();
+        assert_eq!((&path.from[..], &path.to[..]), ("a[0]", "out[15]"));
+        assert_eq!(path.nands, 2 + 15 * 8);
+        assert_eq!(
+            path.chips,
+            vec![
+                ("half_adder".to_string(), 2),
+                ("full_adder".to_string(), 120)
+            ]
+        );
+
+        // 3 levels of mux, which is xor(and(a, not(sel)), and(b, sel))
+        let depth = chip("Mux8Way16").unwrap();
+        assert_eq!(depth.path("a", "out").unwrap().nands, 3 * 6);
+        assert_eq!(depth.path("sel", "out").unwrap().nands, 1 + 3 * 6);
+        assert_eq!(depth.critical.nands, 19);
+
+        assert_eq!(
+            chip("DFF").err(),
+            Some(DepthError("DFF isn't combinational".to_string()))
+        );
+    }
+
+    #[test]
+    fn for_depth_alu() {
+        let depth = chip("ALU").unwrap();
+        let add16 = 2 + 15 * 8;
+        assert!(depth.path("x", "out").unwrap().nands > add16);
+        // zr waits for out
+        assert!(depth.path("x", "zr").unwrap().nands > depth.path("x", "out").unwrap().nands);
+        assert_eq!(depth.path("no", "out").unwrap().nands, 7);
+        assert_eq!(depth.critical.to, "zr");
+        assert!(depth.critical.chips.contains(&("add16".to_string(), add16)));
+    }
+
+    #[test]
+    fn for_depth_cpu() {
+        let depth = cpu().unwrap();
+        let alu = chip("ALU").unwrap();
+        assert!(depth.critical.nands > alu.critical.nands);
+        assert!(depth.critical.chips.iter().any(|(chip, _)| chip == "alu"));
+        // writeM = and(i, d3)
+        assert_eq!(depth.path("instruction", "writeM").unwrap().nands, 2);
+
+        let frequency = depth.max_frequency(Duration::from_nanos(1));
+        assert!((frequency * depth.critical.nands as f64 - 1e9).abs() < 1e-3);
+    }
+}
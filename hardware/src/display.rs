@@ -0,0 +1,302 @@
+// The screen as seen by the display at the other end of the channel:
+// the words written are collected and sent as a frame at most frame_rate times a second.
+use std::{
+    fmt::{self, Formatter},
+    mem,
+    sync::{mpsc::Sender, Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    base::screen::WORDS,
+    protocol::{Control, FromDisplay, Run, ToDisplay},
+};
+
+pub const FRAME_RATE: u32 = 30;
+
+/// Shared by the keyboard, which reads what the display sends, and the frames,
+/// which answer it. The controls wait for `Computer::take_controls`.
+#[derive(Debug, Clone, Default)]
+pub struct Link(Arc<Mutex<Inbox>>);
+
+#[derive(Debug, Default)]
+struct Inbox {
+    // a display said hello, so it gets the whole screen
+    redraw: bool,
+    replies: Vec<ToDisplay>,
+    controls: Vec<Control>,
+}
+
+impl Link {
+    // the messages besides keys, which the keyboard takes itself
+    pub fn receive(&self, message: FromDisplay) {
+        let mut inbox = self.0.lock().unwrap();
+        match message {
+            FromDisplay::Hello { version } => {
+                let reply = ToDisplay::welcome(version);
+                if let ToDisplay::Welcome { .. } = reply {
+                    inbox.redraw = true;
+                }
+                inbox.replies.push(reply);
+            }
+            FromDisplay::Control(control) => inbox.controls.push(control),
+            FromDisplay::Key { .. } => {}
+        }
+    }
+
+    pub fn reply(&self, message: ToDisplay) {
+        self.0.lock().unwrap().replies.push(message);
+    }
+
+    pub fn take_controls(&self) -> Vec<Control> {
+        mem::take(&mut self.0.lock().unwrap().controls)
+    }
+
+    fn take(&self) -> (bool, Vec<ToDisplay>) {
+        let mut inbox = self.0.lock().unwrap();
+        (
+            mem::replace(&mut inbox.redraw, false),
+            mem::take(&mut inbox.replies),
+        )
+    }
+}
+
+/// Sends the words of the screen to the display as frames.
+/// A frame has only the words changed since the last frame, or all of them for a keyframe.
+pub struct Frames {
+    sender: Option<Sender<String>>,
+    words: Vec<u16>,
+    // as the display has them
+    sent: Vec<u16>,
+    // the indices written since the last frame, once each
+    dirty: Vec<usize>,
+    is_dirty: Vec<bool>,
+    keyframe: bool,
+    link: Link,
+    interval: Duration,
+    last: Option<Instant>,
+}
+
+impl fmt::Debug for Frames {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Frames")
+            .field("connected", &self.sender.is_some())
+            .field("dirty", &self.dirty.len())
+            .field("interval", &self.interval)
+            .finish()
+    }
+}
+
+impl Frames {
+    pub fn new(sender: Option<Sender<String>>) -> Self {
+        let words = match sender {
+            Some(_) => WORDS,
+            None => 0,
+        };
+        Self {
+            sender,
+            words: vec![0; words],
+            sent: vec![0; words],
+            dirty: Vec::new(),
+            is_dirty: vec![false; words],
+            // the display starts in sync
+            keyframe: true,
+            link: Link::default(),
+            interval: Duration::from_secs(1) / FRAME_RATE,
+            last: None,
+        }
+    }
+
+    // for the keyboard, which reads what the display sends
+    pub fn link(&self) -> Link {
+        self.link.clone()
+    }
+
+    // 0 sends a frame on every tick with changes
+    pub fn set_frame_rate(&mut self, frame_rate: u32) {
+        self.interval = match frame_rate {
+            0 => Duration::from_secs(0),
+            n => Duration::from_secs(1) / n,
+        };
+    }
+
+    pub fn write(&mut self, register_index: usize, input: u16) {
+        if self.sender.is_none() {
+            return;
+        }
+        self.words[register_index] = input;
+        if !self.is_dirty[register_index] {
+            self.is_dirty[register_index] = true;
+            self.dirty.push(register_index);
+        }
+    }
+
+    // e.g. after a snapshot is restored
+    pub fn write_all(&mut self, words: &[u16]) {
+        if self.sender.is_none() {
+            return;
+        }
+        self.words.copy_from_slice(words);
+        self.keyframe = true;
+    }
+
+    // called once a cycle, to send a frame when one is due
+    pub fn tick(&mut self) {
+        if self.sender.is_none() {
+            return;
+        }
+        let (redraw, replies) = self.link.take();
+        if let Some(ref tx) = self.sender {
+            for reply in replies {
+                let _ = tx.send(reply.to_json());
+            }
+        }
+        if redraw {
+            self.keyframe = true;
+        }
+        if !self.keyframe && self.dirty.is_empty() {
+            return;
+        }
+        let now = Instant::now();
+        if let Some(last) = self.last {
+            if now.duration_since(last) < self.interval {
+                return;
+            }
+        }
+        self.last = Some(now);
+        self.flush();
+    }
+
+    // send the changes now, e.g. at the end of a program
+    pub fn flush(&mut self) {
+        let tx = match self.sender {
+            Some(ref tx) => tx,
+            None => return,
+        };
+        for i in self.dirty.iter() {
+            self.is_dirty[*i] = false;
+        }
+        let mut indices = if self.keyframe {
+            self.dirty.clear();
+            (0..WORDS).collect()
+        } else {
+            let (words, sent) = (&self.words, &self.sent);
+            self.dirty.retain(|i| words[*i] != sent[*i]);
+            self.dirty.sort_unstable();
+            mem::take(&mut self.dirty)
+        };
+        if indices.is_empty() {
+            return;
+        }
+        let runs = runs(&self.words, &indices);
+        for i in indices.drain(..) {
+            self.sent[i] = self.words[i];
+        }
+        let message = ToDisplay::Frame {
+            keyframe: self.keyframe,
+            runs,
+        }
+        .to_json();
+        self.keyframe = false;
+        // the display may have gone, and comes back with a redraw
+        let _ = tx.send(message);
+    }
+}
+
+// no display, e.g. for a deserialized machine
+impl Default for Frames {
+    fn default() -> Self {
+        Frames::new(None)
+    }
+}
+
+impl Drop for Frames {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+// a run for consecutive indices of the same value
+fn runs(words: &[u16], indices: &[usize]) -> Vec<Run> {
+    let mut runs: Vec<Run> = Vec::new();
+    for &i in indices {
+        let value = words[i];
+        match runs.last_mut() {
+            Some(Run(start, count, v)) if (*start + *count) as usize == i && *v == value => {
+                *count += 1
+            }
+            _ => runs.push(Run(i as u16, 1, value)),
+        }
+    }
+    runs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::VERSION;
+    use serde_json::{json, Value};
+    use std::sync::mpsc;
+
+    #[test]
+    fn for_frames() {
+        let (tx, rx) = mpsc::channel();
+        let mut frames = Frames::new(Some(tx));
+        frames.set_frame_rate(0);
+        let frame = |rx: &mpsc::Receiver<String>| -> Value {
+            serde_json::from_str(&rx.try_recv().unwrap()).unwrap()
+        };
+
+        // the first frame is the whole screen
+        frames.write(0, 1);
+        frames.tick();
+        let keyframe = frame(&rx);
+        assert_eq!(keyframe["type"], "frame");
+        assert_eq!(keyframe["keyframe"], true);
+        assert_eq!(keyframe["runs"], json!([[0, 1, 1], [1, WORDS - 1, 0]]));
+
+        // a filled row is a run, and a word written back is left out
+        for i in 32..64 {
+            frames.write(i, 0xffff);
+        }
+        frames.write(100, 7);
+        frames.write(100, 0);
+        frames.tick();
+        assert_eq!(
+            frame(&rx),
+            json!({ "type": "frame", "keyframe": false, "runs": [[32, 32, 0xffff]] })
+        );
+        frames.tick();
+        assert!(rx.try_recv().is_err());
+
+        // a display of another version gets an error, and no keyframe
+        frames.link().receive(FromDisplay::Hello { version: 0 });
+        frames.tick();
+        assert_eq!(frame(&rx)["type"], "error");
+        assert!(rx.try_recv().is_err());
+
+        // a display connects
+        frames
+            .link()
+            .receive(FromDisplay::Hello { version: VERSION });
+        frames.tick();
+        assert_eq!(frame(&rx)["type"], "welcome");
+        assert_eq!(
+            frame(&rx)["runs"],
+            json!([[0, 1, 1], [1, 31, 0], [32, 32, 0xffff], [64, WORDS - 64, 0]])
+        );
+
+        // at most a frame per interval, the rest on flush or when dropped
+        frames.set_frame_rate(1);
+        frames.write(5, 5);
+        frames.tick();
+        frames.write(6, 6);
+        frames.tick();
+        assert!(rx.try_recv().is_err());
+        frames.flush();
+        assert_eq!(frame(&rx)["runs"], json!([[5, 1, 5], [6, 1, 6]]));
+        frames.write(7, 7);
+        drop(frames);
+        assert_eq!(frame(&rx)["runs"], json!([[7, 1, 7]]));
+    }
+}
@@ -0,0 +1,113 @@
+use std::{
+    fmt::{self, Formatter},
+    path::Path,
+};
+
+use crate::{
+    base::logic::WordError,
+    computer::{golden::GoldenError, snapshot::SnapshotError, trace::TraceError},
+    depth::DepthError,
+    hdl::HdlError,
+    protocol::ProtocolError,
+};
+
+/// Why a program, an address or a message couldn't be read,
+/// with the place in the file when there is one.
+#[derive(Debug, PartialEq, Clone)]
+pub struct HardwareError {
+    pub message: String,
+    // the file read, if any
+    pub path: Option<String>,
+    // 0 when the error isn't tied to a line
+    pub line: usize,
+    // the offending text, e.g. the line
+    pub text: Option<String>,
+}
+
+impl HardwareError {
+    pub fn new(message: &str) -> Self {
+        Self {
+            message: message.to_string(),
+            path: None,
+            line: 0,
+            text: None,
+        }
+    }
+
+    pub fn at(line: usize, text: &str, message: &str) -> Self {
+        Self {
+            line,
+            text: Some(text.to_string()),
+            ..Self::new(message)
+        }
+    }
+
+    // the file the error is in, unless already known
+    pub fn in_file(mut self, path: &Path) -> Self {
+        if self.path.is_none() {
+            self.path = Some(path.display().to_string());
+        }
+        self
+    }
+}
+
+impl fmt::Display for HardwareError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if let Some(ref path) = self.path {
+            write!(f, "{}: ", path)?;
+        }
+        if self.line != 0 {
+            write!(f, "line {}: ", self.line)?;
+        }
+        write!(f, "{}", self.message)?;
+        if let Some(ref text) = self.text {
+            write!(f, ": {:?}", text)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<WordError> for HardwareError {
+    fn from(e: WordError) -> Self {
+        HardwareError::new(&e.0)
+    }
+}
+
+impl From<ProtocolError> for HardwareError {
+    fn from(e: ProtocolError) -> Self {
+        HardwareError::new(&e.0)
+    }
+}
+
+impl From<SnapshotError> for HardwareError {
+    fn from(e: SnapshotError) -> Self {
+        HardwareError::new(&e.0)
+    }
+}
+
+impl From<TraceError> for HardwareError {
+    fn from(e: TraceError) -> Self {
+        HardwareError::new(&e.0)
+    }
+}
+
+impl From<GoldenError> for HardwareError {
+    fn from(e: GoldenError) -> Self {
+        HardwareError::new(&e.0)
+    }
+}
+
+impl From<DepthError> for HardwareError {
+    fn from(e: DepthError) -> Self {
+        HardwareError::new(&e.0)
+    }
+}
+
+impl From<HdlError> for HardwareError {
+    fn from(e: HdlError) -> Self {
+        Self {
+            line: e.line,
+            ..HardwareError::new(&e.message)
+        }
+    }
+}
@@ -0,0 +1,231 @@
+#![allow(dead_code)]
+pub mod builtin;
+pub mod chip;
+pub mod parser;
+pub mod script;
+
+use std::{
+    collections::HashMap,
+    fmt::{self, Formatter},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    base::logic::bit::O,
+    base::logic::{bit, Word},
+    hdl::{
+        chip::{Chip, Resolver},
+        parser::{ChipBody, ChipDecl, HdlParser},
+    },
+};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct HdlError {
+    // 0 when the error isn't tied to a line
+    pub line: usize,
+    pub message: String,
+}
+
+impl HdlError {
+    pub fn new(line: usize, message: &str) -> Self {
+        Self {
+            line,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for HdlError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.line == 0 {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "line {}: {}", self.line, self.message)
+        }
+    }
+}
+
+// Looks up parts as <Name>.hdl next to the chip being loaded.
+// Parts without a file, or declared BUILTIN, are the chips in base.
+pub struct FileResolver {
+    dir: PathBuf,
+    decls: HashMap<String, Option<ChipDecl>>,
+}
+
+impl FileResolver {
+    pub fn new(dir: &Path) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+            decls: HashMap::new(),
+        }
+    }
+}
+
+impl Resolver for FileResolver {
+    fn resolve(&mut self, name: &str) -> Result<Option<ChipDecl>, HdlError> {
+        if let Some(decl) = self.decls.get(name) {
+            return Ok(decl.clone());
+        }
+        let path = self.dir.join(format!("{}.hdl", name));
+        let decl = match fs::read_to_string(&path) {
+            Ok(source) => {
+                let decl = parse(&source)
+                    .map_err(|e| HdlError::new(e.line, &format!("{}.hdl: {}", name, e.message)))?;
+                match decl.body {
+                    ChipBody::Builtin { .. } => None,
+                    ChipBody::Parts(_) => Some(decl),
+                }
+            }
+            Err(_) => None,
+        };
+        self.decls.insert(name.to_string(), decl.clone());
+        Ok(decl)
+    }
+}
+
+pub fn parse(source: &str) -> Result<ChipDecl, HdlError> {
+    HdlParser::new(source)?.run()
+}
+
+// load an .hdl file and the parts it uses
+pub fn load(filename: &str) -> Result<Chip, HdlError> {
+    let source = fs::read_to_string(filename)
+        .map_err(|e| HdlError::new(0, &format!("Fail to open {}: {}", filename, e)))?;
+    let decl = parse(&source)?;
+    let dir = Path::new(filename)
+        .parent()
+        .unwrap_or_else(|| Path::new("."));
+    Chip::new(&decl, &mut FileResolver::new(dir))
+}
+
+// LSB first bus -> Word
+pub fn to_word(bits: &[bit]) -> Word {
+    let mut word = [O; 16];
+    for (i, b) in bits.iter().enumerate() {
+        word[15 - i] = *b;
+    }
+    Word::new(word)
+}
+
+// Word -> LSB first bus
+pub fn from_word(word: Word) -> Vec<bit> {
+    (0..16).map(|i| word[15 - i]).collect()
+}
+
+// LSB first bus -> MSB first address like [bit; 3]
+pub fn msb_first<const N: usize>(bits: &[bit]) -> [bit; N] {
+    let mut address = [O; N];
+    for (i, b) in bits.iter().take(N).enumerate() {
+        address[N - 1 - i] = *b;
+    }
+    address
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::base::logic::bit::I;
+
+    #[test]
+    fn for_word_conversion() {
+        let word = Word::from("0000000000000110");
+        assert_eq!(from_word(word)[0..4], [O, I, I, O]);
+        assert_eq!(to_word(&from_word(word)), word);
+        assert_eq!(msb_first::<3>(&[I, O, O]), [O, O, I]);
+    }
+
+    #[test]
+    fn for_load_and() {
+        let mut chip = load("src/hdl/tests/And.hdl").unwrap();
+        for (a, b, out) in [(O, O, O), (O, I, O), (I, O, O), (I, I, I)].iter() {
+            chip.set("a", &[*a]).unwrap();
+            chip.set("b", &[*b]).unwrap();
+            chip.eval();
+            assert_eq!(chip.get("out"), Some(vec![*out]));
+        }
+    }
+
+    #[test]
+    fn for_load_bus() {
+        // Or16 built from Or and Not16, with sub buses and constants
+        let mut chip = load("src/hdl/tests/Bus.hdl").unwrap();
+        let mut input = vec![O; 16];
+        input[3] = I;
+        chip.set("in", &input).unwrap();
+        chip.eval();
+        assert_eq!(chip.get("any"), Some(vec![I]));
+        assert_eq!(chip.get("lo"), Some(vec![I, I, I, O]));
+        assert_eq!(chip.get("const"), Some(vec![I, O, I]));
+        input[3] = O;
+        chip.set("in", &input).unwrap();
+        chip.eval();
+        assert_eq!(chip.get("any"), Some(vec![O]));
+        assert_eq!(chip.get("lo"), Some(vec![I, I, I, I]));
+    }
+
+    #[test]
+    fn for_load_bit() {
+        // Bit from Mux and DFF, which feeds back its output
+        let mut chip = load("src/hdl/tests/Bit.hdl").unwrap();
+        assert!(chip.is_clocked());
+
+        chip.set("in", &[I]).unwrap();
+        chip.set("load", &[I]).unwrap();
+        chip.tick();
+        assert_eq!(chip.get("out"), Some(vec![O]));
+        chip.tock();
+        assert_eq!(chip.get("out"), Some(vec![I]));
+
+        chip.set("in", &[O]).unwrap();
+        chip.set("load", &[O]).unwrap();
+        chip.tick();
+        chip.tock();
+        assert_eq!(chip.get("out"), Some(vec![I]));
+
+        chip.set("load", &[I]).unwrap();
+        chip.tick();
+        chip.tock();
+        assert_eq!(chip.get("out"), Some(vec![O]));
+    }
+
+    #[test]
+    fn for_load_computer() {
+        // the pc of CPU feeds ROM32K, whose instruction feeds CPU back
+        let mut chip = load("src/hdl/tests/Computer.hdl").unwrap();
+        chip.set("reset", &[I]).unwrap();
+        chip.tick();
+        chip.tock();
+        chip.set("reset", &[O]).unwrap();
+        // the empty ROM is @0 all along
+        for pc in 1u16..=3 {
+            chip.tick();
+            chip.tock();
+            assert_eq!(
+                chip.part_output("CPU", "pc").map(|pc| to_word(&pc)),
+                Some(Word::from(pc))
+            );
+        }
+    }
+
+    #[test]
+    fn for_load_errors() {
+        assert_eq!(
+            load("src/hdl/tests/Loop.hdl").err(),
+            Some(HdlError::new(0, "combinational loop through And"))
+        );
+        assert_eq!(
+            load("src/hdl/tests/Unknown.hdl").err(),
+            Some(HdlError::new(6, "unknown chip Foo"))
+        );
+        assert_eq!(
+            load("src/hdl/tests/Width.hdl").err(),
+            Some(HdlError::new(7, "width of b and in differ"))
+        );
+        // out[0] and out[1] are driven once each, but x twice
+        assert_eq!(
+            load("src/hdl/tests/Drivers.hdl").err(),
+            Some(HdlError::new(8, "x is driven by more than one part"))
+        );
+    }
+}
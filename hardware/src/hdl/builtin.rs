@@ -0,0 +1,200 @@
+#![allow(dead_code)]
+use crate::{
+    base::{
+        alu::{self, alu},
+        arithmetic::{add16, full_adder, half_adder, inc16},
+        logic::{
+            and, and16, bit, dmux, dmux4way, dmux8way, mux, mux16, mux4way16, mux8way16, nand, not,
+            not16, or, or16, or8way, xor,
+        },
+    },
+    hdl::{from_word, msb_first, parser::PinDecl, to_word},
+};
+
+// Combinational chips implemented in Rust, from the values of their inputs to those of
+// their outputs. Buses are LSB first, i.e. bits[i] is pin[i].
+// The sequential chips are the chips of base, by base::chip::builtin.
+pub type Gate = fn(&[Vec<bit>]) -> Vec<Vec<bit>>;
+
+pub struct BuiltinDecl {
+    pub inputs: Vec<PinDecl>,
+    pub outputs: Vec<PinDecl>,
+    pub gate: Gate,
+}
+
+pub(crate) fn pins(pins: &[(&str, usize)]) -> Vec<PinDecl> {
+    pins.iter()
+        .map(|(name, width)| PinDecl {
+            name: name.to_string(),
+            width: *width,
+        })
+        .collect()
+}
+
+pub fn builtin(name: &str) -> Option<BuiltinDecl> {
+    let ab = [("a", 1), ("b", 1)];
+    let ab16 = [("a", 16), ("b", 16)];
+    let out = [("out", 1)];
+    let out16 = [("out", 16)];
+
+    let combinational =
+        |inputs: &[(&str, usize)], outputs: &[(&str, usize)], gate: Gate| BuiltinDecl {
+            inputs: pins(inputs),
+            outputs: pins(outputs),
+            gate,
+        };
+
+    let decl = match name {
+        "Nand" => combinational(&ab, &out, |i| vec![vec![nand(i[0][0], i[1][0])]]),
+        "Not" => combinational(&[("in", 1)], &out, |i| vec![vec![not(i[0][0])]]),
+        "And" => combinational(&ab, &out, |i| vec![vec![and(i[0][0], i[1][0])]]),
+        "Or" => combinational(&ab, &out, |i| vec![vec![or(i[0][0], i[1][0])]]),
+        "Xor" => combinational(&ab, &out, |i| vec![vec![xor(i[0][0], i[1][0])]]),
+        "Mux" => combinational(&[("a", 1), ("b", 1), ("sel", 1)], &out, |i| {
+            vec![vec![mux(i[0][0], i[1][0], i[2][0])]]
+        }),
+        "DMux" => combinational(&[("in", 1), ("sel", 1)], &ab, |i| {
+            let out = dmux(i[0][0], i[1][0]);
+            vec![vec![out[0]], vec![out[1]]]
+        }),
+        "Not16" => combinational(&[("in", 16)], &out16, |i| {
+            vec![from_word(not16(to_word(&i[0])))]
+        }),
+        "And16" => combinational(&ab16, &out16, |i| {
+            vec![from_word(and16(to_word(&i[0]), to_word(&i[1])))]
+        }),
+        "Or16" => combinational(&ab16, &out16, |i| {
+            vec![from_word(or16(to_word(&i[0]), to_word(&i[1])))]
+        }),
+        "Mux16" => combinational(&[("a", 16), ("b", 16), ("sel", 1)], &out16, |i| {
+            vec![from_word(mux16(to_word(&i[0]), to_word(&i[1]), i[2][0]))]
+        }),
+        "Or8Way" => combinational(&[("in", 8)], &out, |i| vec![vec![or8way(msb_first(&i[0]))]]),
+        "Mux4Way16" => combinational(
+            &[("a", 16), ("b", 16), ("c", 16), ("d", 16), ("sel", 2)],
+            &out16,
+            |i| {
+                vec![from_word(mux4way16(
+                    to_word(&i[0]),
+                    to_word(&i[1]),
+                    to_word(&i[2]),
+                    to_word(&i[3]),
+                    msb_first(&i[4]),
+                ))]
+            },
+        ),
+        "Mux8Way16" => combinational(
+            &[
+                ("a", 16),
+                ("b", 16),
+                ("c", 16),
+                ("d", 16),
+                ("e", 16),
+                ("f", 16),
+                ("g", 16),
+                ("h", 16),
+                ("sel", 3),
+            ],
+            &out16,
+            |i| {
+                vec![from_word(mux8way16(
+                    to_word(&i[0]),
+                    to_word(&i[1]),
+                    to_word(&i[2]),
+                    to_word(&i[3]),
+                    to_word(&i[4]),
+                    to_word(&i[5]),
+                    to_word(&i[6]),
+                    to_word(&i[7]),
+                    msb_first(&i[8]),
+                ))]
+            },
+        ),
+        "DMux4Way" => combinational(
+            &[("in", 1), ("sel", 2)],
+            &[("a", 1), ("b", 1), ("c", 1), ("d", 1)],
+            |i| {
+                dmux4way(i[0][0], msb_first(&i[1]))
+                    .iter()
+                    .map(|b| vec![*b])
+                    .collect()
+            },
+        ),
+        "DMux8Way" => combinational(
+            &[("in", 1), ("sel", 3)],
+            &[
+                ("a", 1),
+                ("b", 1),
+                ("c", 1),
+                ("d", 1),
+                ("e", 1),
+                ("f", 1),
+                ("g", 1),
+                ("h", 1),
+            ],
+            |i| {
+                dmux8way(i[0][0], msb_first(&i[1]))
+                    .iter()
+                    .map(|b| vec![*b])
+                    .collect()
+            },
+        ),
+        "HalfAdder" => combinational(&ab, &[("sum", 1), ("carry", 1)], |i| {
+            let added = half_adder(i[0][0], i[1][0]);
+            vec![vec![added[1]], vec![added[0]]]
+        }),
+        "FullAdder" => combinational(
+            &[("a", 1), ("b", 1), ("c", 1)],
+            &[("sum", 1), ("carry", 1)],
+            |i| {
+                let added = full_adder(i[0][0], i[1][0], i[2][0]);
+                vec![vec![added[1]], vec![added[0]]]
+            },
+        ),
+        "Add16" => combinational(&ab16, &out16, |i| {
+            vec![from_word(add16(to_word(&i[0]), to_word(&i[1])))]
+        }),
+        "Inc16" => combinational(&[("in", 16)], &out16, |i| {
+            vec![from_word(inc16(to_word(&i[0])))]
+        }),
+        "ALU" => combinational(alu::INPUTS, alu::OUTPUTS, |i| {
+            let (out, zr, ng) = alu(
+                to_word(&i[0]),
+                to_word(&i[1]),
+                i[2][0],
+                i[3][0],
+                i[4][0],
+                i[5][0],
+                i[6][0],
+                i[7][0],
+            );
+            vec![from_word(out), vec![zr], vec![ng]]
+        }),
+        _ => return None,
+    };
+    Some(decl)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::base::logic::bit::{I, O};
+
+    #[test]
+    fn for_builtin_combinational() {
+        let chip = builtin("Mux4Way16").unwrap().gate;
+        let zero = vec![O; 16];
+        let one = vec![I; 16];
+        let inputs = vec![
+            zero.clone(),
+            zero.clone(),
+            one.clone(),
+            zero.clone(),
+            vec![O, I],
+        ];
+        assert_eq!(chip(&inputs), vec![one]);
+
+        let adder = builtin("HalfAdder").unwrap().gate;
+        assert_eq!(adder(&[vec![I], vec![I]]), vec![vec![O], vec![I]]);
+    }
+}
@@ -0,0 +1,662 @@
+#![allow(dead_code)]
+use std::collections::HashMap;
+
+use crate::{
+    base::{
+        self,
+        logic::bit::{self, I, O},
+    },
+    hdl::{
+        builtin::{builtin, BuiltinDecl, Gate},
+        parser::{ChipBody, ChipDecl, PinDecl, PinRef},
+        HdlError,
+    },
+    profile,
+};
+
+// Finds the declaration of a part by its name.
+pub trait Resolver {
+    fn resolve(&mut self, name: &str) -> Result<Option<ChipDecl>, HdlError>;
+}
+
+enum Source {
+    Wire(usize, usize),
+    Const(bit),
+}
+
+// part pin[part_from..] <-> wire[wire_from..] for len bits
+struct Binding {
+    pin: usize,
+    part_from: usize,
+    len: usize,
+    source: Source,
+}
+
+struct Part {
+    chip: Chip,
+    inputs: Vec<Binding>,
+    outputs: Vec<Binding>,
+    // also evaluated before the others, for the register outputs read by parts sorted
+    // before it, e.g. the pc of CPU by ROM32K in Computer
+    early: bool,
+}
+
+struct Composite {
+    // chip inputs, chip outputs and then internal pins
+    wires: Vec<Vec<bit>>,
+    // sorted so that every part is evaluated after the parts it depends on
+    parts: Vec<Part>,
+}
+
+enum Kind {
+    Gate(Gate),
+    // a chip of base, e.g. a register or the CPU
+    Base(Box<dyn base::chip::Chip>),
+    Composite(Composite),
+}
+
+/// A chip built from an HDL declaration.
+/// Buses are LSB first: `bits[i]` is `pin[i]`.
+pub struct Chip {
+    name: String,
+    inputs: Vec<PinDecl>,
+    outputs: Vec<PinDecl>,
+    input_values: Vec<Vec<bit>>,
+    output_values: Vec<Vec<bit>>,
+    // depends[o][i]: output o changes combinationally with input i
+    depends: Vec<Vec<bool>>,
+    clocked: bool,
+    kind: Kind,
+}
+
+impl Chip {
+    pub fn new(decl: &ChipDecl, resolver: &mut dyn Resolver) -> Result<Self, HdlError> {
+        Chip::build(decl, resolver, &mut Vec::new())
+    }
+
+    pub fn builtin(name: &str) -> Option<Self> {
+        match builtin(name) {
+            Some(decl) => Some(Chip::from_gate(name, decl)),
+            None => base::chip::part(name).map(|chip| Chip::from_base(name, chip)),
+        }
+    }
+
+    fn from_gate(name: &str, decl: BuiltinDecl) -> Self {
+        Chip {
+            name: name.to_string(),
+            input_values: decl.inputs.iter().map(|pin| vec![O; pin.width]).collect(),
+            output_values: decl.outputs.iter().map(|pin| vec![O; pin.width]).collect(),
+            depends: vec![vec![true; decl.inputs.len()]; decl.outputs.len()],
+            inputs: decl.inputs,
+            outputs: decl.outputs,
+            clocked: false,
+            kind: Kind::Gate(decl.gate),
+        }
+    }
+
+    fn from_base(name: &str, chip: Box<dyn base::chip::Chip>) -> Self {
+        let depends = chip
+            .outputs()
+            .iter()
+            .map(|output| {
+                chip.inputs()
+                    .iter()
+                    .map(|input| chip.follows(&output.name, &input.name))
+                    .collect()
+            })
+            .collect();
+        let mut chip = Chip {
+            name: name.to_string(),
+            inputs: chip.inputs().to_vec(),
+            outputs: chip.outputs().to_vec(),
+            input_values: chip.inputs().iter().map(|pin| vec![O; pin.width]).collect(),
+            output_values: Vec::new(),
+            depends,
+            clocked: chip.is_clocked(),
+            kind: Kind::Base(chip),
+        };
+        chip.eval();
+        chip
+    }
+
+    fn build(
+        decl: &ChipDecl,
+        resolver: &mut dyn Resolver,
+        building: &mut Vec<String>,
+    ) -> Result<Self, HdlError> {
+        if building.contains(&decl.name) {
+            return Err(HdlError::new(0, &format!("{} contains itself", decl.name)));
+        }
+
+        let parts = match &decl.body {
+            ChipBody::Builtin { name, .. } => {
+                let mut chip = match builtin(name) {
+                    Some(gate) => Chip::from_gate(&decl.name, gate),
+                    None => base::chip::part(name)
+                        .map(|chip| Chip::from_base(&decl.name, chip))
+                        .ok_or_else(|| {
+                            HdlError::new(0, &format!("unknown builtin chip {}", name))
+                        })?,
+                };
+                chip.check_pins(decl)?;
+                return Ok(chip);
+            }
+            ChipBody::Parts(parts) => parts,
+        };
+
+        building.push(decl.name.clone());
+        let mut names: HashMap<String, usize> = HashMap::new();
+        let mut widths: Vec<usize> = Vec::new();
+        for pin in decl.inputs.iter().chain(decl.outputs.iter()) {
+            if names.insert(pin.name.clone(), widths.len()).is_some() {
+                return Err(HdlError::new(0, &format!("duplicate pin {}", pin.name)));
+            }
+            widths.push(pin.width);
+        }
+        let n_inputs = decl.inputs.len();
+
+        // instantiate parts
+        let mut chips = Vec::new();
+        for part in parts.iter() {
+            let chip = match resolver.resolve(&part.name)? {
+                Some(part_decl) => Chip::build(&part_decl, resolver, building),
+                None => Chip::builtin(&part.name).ok_or_else(|| {
+                    HdlError::new(part.line, &format!("unknown chip {}", part.name))
+                }),
+            }
+            .map_err(|e| {
+                if e.line == 0 {
+                    HdlError::new(part.line, &e.message)
+                } else {
+                    e
+                }
+            })?;
+            chips.push(chip);
+        }
+        building.pop();
+
+        // internal pins get their widths from the part outputs driving them
+        for (part, chip) in parts.iter().zip(chips.iter()) {
+            for connection in part.connections.iter() {
+                if let Some(pin) = chip.output_index(&connection.part_pin.name) {
+                    let name = &connection.chip_pin.name;
+                    if !names.contains_key(name) && name != "true" && name != "false" {
+                        let (from, to) = chip.range(false, pin, &connection.part_pin, part.line)?;
+                        names.insert(name.clone(), widths.len());
+                        widths.push(to - from + 1);
+                    }
+                }
+            }
+        }
+
+        // for every bit of every wire, whether a part drives it
+        let mut driven: Vec<Vec<bool>> = widths.iter().map(|width| vec![false; *width]).collect();
+        let mut bound = Vec::new();
+        for (part, chip) in parts.iter().zip(chips) {
+            let mut inputs = Vec::new();
+            let mut outputs = Vec::new();
+            for connection in part.connections.iter() {
+                let part_pin = &connection.part_pin;
+                let chip_pin = &connection.chip_pin;
+                let error = |message: String| HdlError::new(part.line, &message);
+
+                let (is_input, pin) = match (
+                    chip.input_index(&part_pin.name),
+                    chip.output_index(&part_pin.name),
+                ) {
+                    (Some(pin), _) => (true, pin),
+                    (None, Some(pin)) => (false, pin),
+                    (None, None) => {
+                        return Err(error(format!("{} has no pin {}", part.name, part_pin.name)))
+                    }
+                };
+                let (part_from, part_to) = chip.range(is_input, pin, part_pin, part.line)?;
+                let len = part_to - part_from + 1;
+
+                let source = if chip_pin.name == "true" || chip_pin.name == "false" {
+                    if !is_input {
+                        return Err(error(format!("{} cannot be an output", chip_pin.name)));
+                    }
+                    Source::Const(if chip_pin.name == "true" { I } else { O })
+                } else {
+                    let wire = *names
+                        .get(&chip_pin.name)
+                        .ok_or_else(|| error(format!("undefined pin {}", chip_pin.name)))?;
+                    let (from, to) = match chip_pin.range {
+                        Some((from, to)) => (from, to),
+                        None => (0, widths[wire] - 1),
+                    };
+                    if to >= widths[wire] {
+                        return Err(error(format!(
+                            "{}[{}..{}] is out of range",
+                            chip_pin.name, from, to
+                        )));
+                    }
+                    if to - from + 1 != len {
+                        return Err(error(format!(
+                            "width of {} and {} differ",
+                            part_pin.name, chip_pin.name
+                        )));
+                    }
+                    if !is_input {
+                        if wire < n_inputs {
+                            return Err(error(format!(
+                                "input {} cannot be driven by a part",
+                                chip_pin.name
+                            )));
+                        }
+                        if driven[wire][from..=to].iter().any(|d| *d) {
+                            return Err(error(format!(
+                                "{} is driven by more than one part",
+                                chip_pin.name
+                            )));
+                        }
+                        driven[wire][from..=to].fill(true);
+                    }
+                    Source::Wire(wire, from)
+                };
+
+                let binding = Binding {
+                    pin,
+                    part_from,
+                    len,
+                    source,
+                };
+                if is_input {
+                    inputs.push(binding);
+                } else {
+                    outputs.push(binding);
+                }
+            }
+            bound.push(Part {
+                chip,
+                inputs,
+                outputs,
+                early: false,
+            });
+        }
+
+        for (name, wire) in names.iter() {
+            if *wire >= n_inputs
+                && !driven[*wire].contains(&true)
+                && *wire < n_inputs + decl.outputs.len()
+            {
+                return Err(HdlError::new(
+                    0,
+                    &format!("output {} is not connected", name),
+                ));
+            }
+        }
+
+        let parts = Chip::sort(bound, widths.len())?;
+        let depends = Chip::dependencies(&parts, n_inputs, widths.len())
+            [n_inputs..n_inputs + decl.outputs.len()]
+            .to_vec();
+        let clocked = parts.iter().any(|part| part.chip.clocked);
+
+        Ok(Chip {
+            name: decl.name.clone(),
+            inputs: decl.inputs.clone(),
+            outputs: decl.outputs.clone(),
+            input_values: decl.inputs.iter().map(|pin| vec![O; pin.width]).collect(),
+            output_values: decl.outputs.iter().map(|pin| vec![O; pin.width]).collect(),
+            depends,
+            clocked,
+            kind: Kind::Composite(Composite {
+                wires: widths.iter().map(|width| vec![O; *width]).collect(),
+                parts,
+            }),
+        })
+    }
+
+    // a builtin declared in an HDL file has to keep the builtin's pins
+    fn check_pins(&mut self, decl: &ChipDecl) -> Result<(), HdlError> {
+        if self.inputs != decl.inputs || self.outputs != decl.outputs {
+            return Err(HdlError::new(
+                0,
+                &format!("pins of {} differ from the builtin chip", decl.name),
+            ));
+        }
+        Ok(())
+    }
+
+    // topological sort on combinational dependencies
+    fn sort(mut parts: Vec<Part>, n_wires: usize) -> Result<Vec<Part>, HdlError> {
+        let edges = Chip::edges(&parts, n_wires);
+        // a loop through register outputs is cut, and their parts are evaluated early
+        let order = match Chip::order(&parts, &edges, true) {
+            Ok(order) => order,
+            Err(_) => {
+                let order = Chip::order(&parts, &edges, false)?;
+                let mut position = vec![0; parts.len()];
+                for (n, i) in order.iter().enumerate() {
+                    position[*i] = n;
+                }
+                for (writer, reader, register) in edges {
+                    if register && position[reader] < position[writer] {
+                        parts[writer].early = true;
+                    }
+                }
+                order
+            }
+        };
+
+        let mut slots: Vec<Option<Part>> = parts.into_iter().map(Some).collect();
+        Ok(order
+            .into_iter()
+            .map(|i| slots[i].take().unwrap())
+            .collect())
+    }
+
+    // (writer, reader, whether from a register output) for every wire
+    // which a part reads combinationally
+    fn edges(parts: &[Part], n_wires: usize) -> Vec<(usize, usize, bool)> {
+        let mut writers: Vec<Vec<(usize, bool)>> = vec![Vec::new(); n_wires];
+        for (i, part) in parts.iter().enumerate() {
+            for binding in part.outputs.iter() {
+                if let Source::Wire(wire, _) = binding.source {
+                    writers[wire].push((i, part.chip.is_register_output(binding.pin)));
+                }
+            }
+        }
+        let mut edges = Vec::new();
+        for (i, part) in parts.iter().enumerate() {
+            for binding in part.inputs.iter() {
+                if !part.chip.is_combinational_input(binding.pin) {
+                    continue;
+                }
+                if let Source::Wire(wire, _) = binding.source {
+                    for (writer, register) in writers[wire].iter() {
+                        edges.push((*writer, i, *register));
+                    }
+                }
+            }
+        }
+        edges
+    }
+
+    fn order(
+        parts: &[Part],
+        edges: &[(usize, usize, bool)],
+        registers: bool,
+    ) -> Result<Vec<usize>, HdlError> {
+        let mut after: Vec<Vec<usize>> = vec![Vec::new(); parts.len()];
+        let mut waiting = vec![0; parts.len()];
+        for (writer, reader, register) in edges.iter() {
+            if registers || !register {
+                after[*writer].push(*reader);
+                waiting[*reader] += 1;
+            }
+        }
+
+        let mut order = Vec::new();
+        let mut ready: Vec<usize> = (0..parts.len()).filter(|i| waiting[*i] == 0).collect();
+        while let Some(i) = ready.pop() {
+            order.push(i);
+            for next in after[i].iter() {
+                waiting[*next] -= 1;
+                if waiting[*next] == 0 {
+                    ready.push(*next);
+                }
+            }
+        }
+        if order.len() < parts.len() {
+            let looped = (0..parts.len())
+                .find(|i| waiting[*i] > 0)
+                .map_or("".to_string(), |i| parts[i].chip.name.clone());
+            return Err(HdlError::new(
+                0,
+                &format!("combinational loop through {}", looped),
+            ));
+        }
+        Ok(order)
+    }
+
+    // for every wire, the chip inputs it changes combinationally with
+    fn dependencies(parts: &[Part], n_inputs: usize, n_wires: usize) -> Vec<Vec<bool>> {
+        let mut depends = vec![vec![false; n_inputs]; n_wires];
+        for (i, d) in depends.iter_mut().enumerate().take(n_inputs) {
+            d[i] = true;
+        }
+        for part in parts.iter() {
+            for output in part.outputs.iter() {
+                let wire = match output.source {
+                    Source::Wire(wire, _) => wire,
+                    Source::Const(_) => continue,
+                };
+                for input in part.inputs.iter() {
+                    if !part.chip.depends[output.pin][input.pin] {
+                        continue;
+                    }
+                    if let Source::Wire(source, _) = input.source {
+                        let from = depends[source].clone();
+                        for (to, from) in depends[wire].iter_mut().zip(from) {
+                            *to |= from;
+                        }
+                    }
+                }
+            }
+        }
+        depends
+    }
+
+    fn range(
+        &self,
+        is_input: bool,
+        pin: usize,
+        pin_ref: &PinRef,
+        line: usize,
+    ) -> Result<(usize, usize), HdlError> {
+        let width = if is_input {
+            self.inputs[pin].width
+        } else {
+            self.outputs[pin].width
+        };
+        match pin_ref.range {
+            None => Ok((0, width - 1)),
+            Some((from, to)) if to < width => Ok((from, to)),
+            Some((from, to)) => Err(HdlError::new(
+                line,
+                &format!("{}[{}..{}] is out of range", pin_ref.name, from, to),
+            )),
+        }
+    }
+
+    fn input_index(&self, name: &str) -> Option<usize> {
+        self.inputs.iter().position(|pin| pin.name == name)
+    }
+
+    fn output_index(&self, name: &str) -> Option<usize> {
+        self.outputs.iter().position(|pin| pin.name == name)
+    }
+
+    fn is_combinational_input(&self, pin: usize) -> bool {
+        self.depends.iter().any(|depends| depends[pin])
+    }
+
+    // an output which follows no input, as the registers of a clocked chip
+    fn is_register_output(&self, pin: usize) -> bool {
+        !self.depends[pin].contains(&true)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn inputs(&self) -> &[PinDecl] {
+        &self.inputs
+    }
+
+    pub fn outputs(&self) -> &[PinDecl] {
+        &self.outputs
+    }
+
+    // has sequential parts which change on tick/tock
+    pub fn is_clocked(&self) -> bool {
+        self.clocked
+    }
+
+    // whether the output changes combinationally with the input
+    pub fn follows(&self, output: &str, input: &str) -> bool {
+        match (self.output_index(output), self.input_index(input)) {
+            (Some(output), Some(input)) => self.depends[output][input],
+            _ => false,
+        }
+    }
+
+    pub fn set(&mut self, name: &str, bits: &[bit]) -> Result<(), HdlError> {
+        let pin = self
+            .input_index(name)
+            .ok_or_else(|| HdlError::new(0, &format!("{} has no input {}", self.name, name)))?;
+        if bits.len() != self.inputs[pin].width {
+            return Err(HdlError::new(
+                0,
+                &format!("{} is {} bits wide", name, self.inputs[pin].width),
+            ));
+        }
+        self.input_values[pin] = bits.to_vec();
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<Vec<bit>> {
+        if let Some(pin) = self.input_index(name) {
+            return Some(self.input_values[pin].clone());
+        }
+        self.output_index(name)
+            .map(|pin| self.output_values[pin].clone())
+    }
+
+    // output of the first part with the given name, searched depth first
+    pub fn part_output(&self, part: &str, pin: &str) -> Option<Vec<bit>> {
+        if let Kind::Composite(composite) = &self.kind {
+            for p in composite.parts.iter() {
+                if p.chip.name == part {
+                    return p.chip.get(pin);
+                }
+                if let Some(bits) = p.chip.part_output(part, pin) {
+                    return Some(bits);
+                }
+            }
+        }
+        None
+    }
+
+    // load the register part found first, e.g. PC of CPU
+    pub fn set_part(&mut self, part: &str, value: &[bit]) -> bool {
+        let found = match &mut self.kind {
+            Kind::Gate(_) => false,
+            Kind::Base(chip) => self.name == part && chip.set_register(value),
+            Kind::Composite(composite) => composite
+                .parts
+                .iter_mut()
+                .any(|p| p.chip.set_part(part, value)),
+        };
+        if found {
+            self.eval();
+        }
+        found
+    }
+
+    pub fn eval(&mut self) {
+        let _profile = match self.kind {
+            Kind::Composite(_) if profile::is_enabled() => Some(profile::chip(self.name.clone())),
+            _ => None,
+        };
+        match &mut self.kind {
+            Kind::Gate(gate) => self.output_values = gate(&self.input_values),
+            Kind::Base(chip) => {
+                for (pin, value) in self.inputs.iter().zip(self.input_values.iter()) {
+                    chip.set_input(&pin.name, value)
+                        .expect("the pins are those of the chip");
+                }
+                chip.eval();
+                self.output_values = self
+                    .outputs
+                    .iter()
+                    .map(|pin| chip.get(&pin.name).unwrap_or_default())
+                    .collect();
+            }
+            Kind::Composite(composite) => {
+                composite.load_inputs(&self.input_values);
+                for part in composite.parts.iter_mut().filter(|part| part.early) {
+                    part.chip.eval();
+                    Composite::get_outputs(&mut composite.wires, part);
+                }
+                for part in composite.parts.iter_mut() {
+                    Composite::set_inputs(&composite.wires, part);
+                    part.chip.eval();
+                    Composite::get_outputs(&mut composite.wires, part);
+                }
+                self.output_values = composite.outputs(self.inputs.len(), self.outputs.len());
+            }
+        }
+    }
+
+    // rising edge: clocked parts sample their inputs
+    pub fn tick(&mut self) {
+        self.eval();
+        match &mut self.kind {
+            Kind::Gate(_) => {}
+            Kind::Base(chip) => chip.tick(),
+            Kind::Composite(composite) => {
+                for part in composite.parts.iter_mut() {
+                    if part.chip.clocked {
+                        Composite::set_inputs(&composite.wires, part);
+                        part.chip.tick();
+                    }
+                }
+            }
+        }
+    }
+
+    // falling edge: clocked parts commit their new state
+    pub fn tock(&mut self) {
+        match &mut self.kind {
+            Kind::Gate(_) => {}
+            Kind::Base(chip) => chip.tock(),
+            Kind::Composite(composite) => {
+                for part in composite.parts.iter_mut() {
+                    if part.chip.clocked {
+                        part.chip.tock();
+                    }
+                }
+            }
+        }
+        self.eval();
+    }
+}
+
+impl Composite {
+    fn load_inputs(&mut self, inputs: &[Vec<bit>]) {
+        for (i, value) in inputs.iter().enumerate() {
+            self.wires[i] = value.clone();
+        }
+    }
+
+    fn set_inputs(wires: &[Vec<bit>], part: &mut Part) {
+        for binding in part.inputs.iter() {
+            let values = &mut part.chip.input_values[binding.pin];
+            for i in 0..binding.len {
+                values[binding.part_from + i] = match binding.source {
+                    Source::Wire(wire, from) => wires[wire][from + i],
+                    Source::Const(b) => b,
+                };
+            }
+        }
+    }
+
+    fn get_outputs(wires: &mut [Vec<bit>], part: &Part) {
+        for binding in part.outputs.iter() {
+            if let Source::Wire(wire, from) = binding.source {
+                let values = &part.chip.output_values[binding.pin];
+                for i in 0..binding.len {
+                    wires[wire][from + i] = values[binding.part_from + i];
+                }
+            }
+        }
+    }
+
+    fn outputs(&self, n_inputs: usize, n_outputs: usize) -> Vec<Vec<bit>> {
+        self.wires[n_inputs..n_inputs + n_outputs].to_vec()
+    }
+}
@@ -0,0 +1,439 @@
+#![allow(dead_code)]
+use crate::hdl::HdlError;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct PinDecl {
+    pub name: String,
+    pub width: usize,
+}
+
+// a[2], a[0..7] or a
+#[derive(Debug, PartialEq, Clone)]
+pub struct PinRef {
+    pub name: String,
+    // (from, to) inclusive
+    pub range: Option<(usize, usize)>,
+}
+
+// part_pin=chip_pin
+#[derive(Debug, PartialEq, Clone)]
+pub struct Connection {
+    pub part_pin: PinRef,
+    pub chip_pin: PinRef,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct PartDecl {
+    pub name: String,
+    pub connections: Vec<Connection>,
+    pub line: usize,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ChipBody {
+    Parts(Vec<PartDecl>),
+    Builtin { name: String, clocked: Vec<String> },
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ChipDecl {
+    pub name: String,
+    pub inputs: Vec<PinDecl>,
+    pub outputs: Vec<PinDecl>,
+    pub body: ChipBody,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum Token {
+    Ident(String),
+    Number(usize),
+    Symbol(char),
+    // ..
+    Range,
+}
+
+pub struct HdlParser {
+    tokens: Vec<(Token, usize)>,
+    index: usize,
+}
+
+impl HdlParser {
+    pub fn new(source: &str) -> Result<Self, HdlError> {
+        Ok(Self {
+            tokens: HdlParser::tokenize(source)?,
+            index: 0,
+        })
+    }
+
+    pub fn run(&mut self) -> Result<ChipDecl, HdlError> {
+        self.expect_keyword("CHIP")?;
+        let name = self.ident()?;
+        self.expect_symbol('{')?;
+
+        let mut inputs = Vec::new();
+        let mut outputs = Vec::new();
+        let body = loop {
+            let keyword = self.ident()?;
+            match keyword.as_str() {
+                "IN" => inputs.extend(self.pin_decls()?),
+                "OUT" => outputs.extend(self.pin_decls()?),
+                "PARTS" => {
+                    self.expect_symbol(':')?;
+                    let mut parts = Vec::new();
+                    while self.peek() != Some(&Token::Symbol('}')) {
+                        parts.push(self.part()?);
+                    }
+                    break ChipBody::Parts(parts);
+                }
+                "BUILTIN" => {
+                    let builtin = self.ident()?;
+                    self.expect_symbol(';')?;
+                    let mut clocked = Vec::new();
+                    if self.peek() == Some(&Token::Ident("CLOCKED".to_string())) {
+                        self.index += 1;
+                        loop {
+                            clocked.push(self.ident()?);
+                            if !self.eat_symbol(',') {
+                                break;
+                            }
+                        }
+                        self.expect_symbol(';')?;
+                    }
+                    break ChipBody::Builtin {
+                        name: builtin,
+                        clocked,
+                    };
+                }
+                _ => return Err(self.error(&format!("unexpected {:?}", keyword))),
+            }
+        };
+        self.expect_symbol('}')?;
+        if self.index < self.tokens.len() {
+            return Err(self.error("unexpected tokens after chip"));
+        }
+
+        Ok(ChipDecl {
+            name,
+            inputs,
+            outputs,
+            body,
+        })
+    }
+
+    fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, HdlError> {
+        let chars: Vec<char> = source.chars().collect();
+        let mut tokens = Vec::new();
+        let mut line = 1;
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            if c == '\n' {
+                line += 1;
+                i += 1;
+            } else if c.is_whitespace() {
+                i += 1;
+            } else if c == '/' && chars.get(i + 1) == Some(&'/') {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+            } else if c == '/' && chars.get(i + 1) == Some(&'*') {
+                i += 2;
+                while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                    if chars[i] == '\n' {
+                        line += 1;
+                    }
+                    i += 1;
+                }
+                if i >= chars.len() {
+                    return Err(HdlError::new(line, "unterminated comment"));
+                }
+                i += 2;
+            } else if c == '.' && chars.get(i + 1) == Some(&'.') {
+                tokens.push((Token::Range, line));
+                i += 2;
+            } else if c.is_ascii_digit() {
+                let start = i;
+                while i < chars.len() && chars[i].is_ascii_digit() {
+                    i += 1;
+                }
+                let number: String = chars[start..i].iter().collect();
+                let number = number
+                    .parse()
+                    .map_err(|_| HdlError::new(line, &format!("invalid number {}", number)))?;
+                tokens.push((Token::Number(number), line));
+            } else if c.is_alphabetic() || c == '_' {
+                let start = i;
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                tokens.push((Token::Ident(chars[start..i].iter().collect()), line));
+            } else if "{}()[];:,=".contains(c) {
+                tokens.push((Token::Symbol(c), line));
+                i += 1;
+            } else {
+                return Err(HdlError::new(
+                    line,
+                    &format!("unexpected character {:?}", c),
+                ));
+            }
+        }
+        Ok(tokens)
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.index).map(|(token, _)| token)
+    }
+
+    fn line(&self) -> usize {
+        match self.tokens.get(self.index) {
+            Some((_, line)) => *line,
+            None => self.tokens.last().map_or(1, |(_, line)| *line),
+        }
+    }
+
+    fn error(&self, message: &str) -> HdlError {
+        HdlError::new(self.line(), message)
+    }
+
+    fn next(&mut self) -> Result<Token, HdlError> {
+        let token = self
+            .peek()
+            .cloned()
+            .ok_or_else(|| self.error("unexpected end of file"))?;
+        self.index += 1;
+        Ok(token)
+    }
+
+    fn ident(&mut self) -> Result<String, HdlError> {
+        match self.next()? {
+            Token::Ident(name) => Ok(name),
+            token => {
+                self.index -= 1;
+                Err(self.error(&format!("expected a name but found {:?}", token)))
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<usize, HdlError> {
+        match self.next()? {
+            Token::Number(number) => Ok(number),
+            token => {
+                self.index -= 1;
+                Err(self.error(&format!("expected a number but found {:?}", token)))
+            }
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), HdlError> {
+        let name = self.ident()?;
+        if name != keyword {
+            self.index -= 1;
+            return Err(self.error(&format!("expected {} but found {}", keyword, name)));
+        }
+        Ok(())
+    }
+
+    fn expect_symbol(&mut self, symbol: char) -> Result<(), HdlError> {
+        if !self.eat_symbol(symbol) {
+            return Err(self.error(&format!("expected {:?}", symbol)));
+        }
+        Ok(())
+    }
+
+    fn eat_symbol(&mut self, symbol: char) -> bool {
+        if self.peek() == Some(&Token::Symbol(symbol)) {
+            self.index += 1;
+            return true;
+        }
+        false
+    }
+
+    // a, b[16], c;
+    fn pin_decls(&mut self) -> Result<Vec<PinDecl>, HdlError> {
+        let mut pins = Vec::new();
+        loop {
+            let name = self.ident()?;
+            let width = if self.eat_symbol('[') {
+                let width = self.number()?;
+                self.expect_symbol(']')?;
+                width
+            } else {
+                1
+            };
+            if width == 0 || width > 16 {
+                return Err(self.error(&format!("invalid width of {}: {}", name, width)));
+            }
+            pins.push(PinDecl { name, width });
+            if !self.eat_symbol(',') {
+                break;
+            }
+        }
+        self.expect_symbol(';')?;
+        Ok(pins)
+    }
+
+    // Name(a=b, c[0..7]=d);
+    fn part(&mut self) -> Result<PartDecl, HdlError> {
+        let line = self.line();
+        let name = self.ident()?;
+        self.expect_symbol('(')?;
+        let mut connections = Vec::new();
+        loop {
+            let part_pin = self.pin_ref()?;
+            self.expect_symbol('=')?;
+            let chip_pin = self.pin_ref()?;
+            connections.push(Connection { part_pin, chip_pin });
+            if !self.eat_symbol(',') {
+                break;
+            }
+        }
+        self.expect_symbol(')')?;
+        self.expect_symbol(';')?;
+        Ok(PartDecl {
+            name,
+            connections,
+            line,
+        })
+    }
+
+    fn pin_ref(&mut self) -> Result<PinRef, HdlError> {
+        let name = self.ident()?;
+        let range = if self.eat_symbol('[') {
+            let from = self.number()?;
+            let to = if self.peek() == Some(&Token::Range) {
+                self.index += 1;
+                self.number()?
+            } else {
+                from
+            };
+            self.expect_symbol(']')?;
+            if from > to {
+                return Err(self.error(&format!("invalid range {}[{}..{}]", name, from, to)));
+            }
+            Some((from, to))
+        } else {
+            None
+        };
+        Ok(PinRef { name, range })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn for_hdl_parser() {
+        let mut parser = HdlParser::new(
+            "/** And gate */
+            CHIP And {
+                IN a, b;
+                OUT out;
+
+                PARTS:
+                // out = not(nand(a, b))
+                Nand(a=a, b=b, out=x);
+                Not(in=x, out=out);
+            }",
+        )
+        .unwrap();
+        let pin = |name: &str| PinRef {
+            name: name.to_string(),
+            range: None,
+        };
+        let connection = |part_pin: &str, chip_pin: &str| Connection {
+            part_pin: pin(part_pin),
+            chip_pin: pin(chip_pin),
+        };
+        assert_eq!(
+            parser.run(),
+            Ok(ChipDecl {
+                name: "And".to_string(),
+                inputs: vec![
+                    PinDecl {
+                        name: "a".to_string(),
+                        width: 1
+                    },
+                    PinDecl {
+                        name: "b".to_string(),
+                        width: 1
+                    }
+                ],
+                outputs: vec![PinDecl {
+                    name: "out".to_string(),
+                    width: 1
+                }],
+                body: ChipBody::Parts(vec![
+                    PartDecl {
+                        name: "Nand".to_string(),
+                        connections: vec![
+                            connection("a", "a"),
+                            connection("b", "b"),
+                            connection("out", "x")
+                        ],
+                        line: 8,
+                    },
+                    PartDecl {
+                        name: "Not".to_string(),
+                        connections: vec![connection("in", "x"), connection("out", "out")],
+                        line: 9,
+                    }
+                ])
+            })
+        );
+    }
+
+    #[test]
+    fn for_hdl_parser_builtin() {
+        let mut parser = HdlParser::new(
+            "CHIP Bit {
+                IN in, load, address[3];
+                OUT out[16];
+                BUILTIN Bit;
+                CLOCKED in, load;
+            }",
+        )
+        .unwrap();
+        let decl = parser.run().unwrap();
+        assert_eq!(decl.inputs[2].width, 3);
+        assert_eq!(decl.outputs[0].width, 16);
+        assert_eq!(
+            decl.body,
+            ChipBody::Builtin {
+                name: "Bit".to_string(),
+                clocked: vec!["in".to_string(), "load".to_string()]
+            }
+        );
+    }
+
+    #[test]
+    fn for_hdl_parser_bus() {
+        let mut parser = HdlParser::new(
+            "CHIP Split {
+                IN in[16];
+                OUT lo[8], msb;
+                PARTS:
+                Or8Way(in=in[0..7], out=lo[3]);
+            }",
+        )
+        .unwrap();
+        let decl = parser.run().unwrap();
+        match decl.body {
+            ChipBody::Parts(parts) => {
+                assert_eq!(parts[0].connections[0].chip_pin.range, Some((0, 7)));
+                assert_eq!(parts[0].connections[1].chip_pin.range, Some((3, 3)));
+            }
+            _ => panic!("expected parts"),
+        }
+    }
+
+    #[test]
+    fn for_hdl_parser_error() {
+        let mut parser = HdlParser::new("CHIP And {\n IN a, b;\n OUT out\n PARTS: }").unwrap();
+        assert_eq!(parser.run(), Err(HdlError::new(4, "expected ';'")));
+        assert_eq!(
+            HdlParser::new("CHIP And { IN a$ }").err(),
+            Some(HdlError::new(1, "unexpected character '$'"))
+        );
+    }
+}
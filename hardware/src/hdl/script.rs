@@ -0,0 +1,652 @@
+#![allow(dead_code)]
+// Runs nand2tetris test scripts (.tst) against chips and compares the output with .cmp files.
+use std::{
+    fmt::{self, Formatter},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    base::chip::{self, Chip},
+    base::logic::bit,
+    base::logic::bit::{I, O},
+    hdl::{load, HdlError},
+};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Mismatch {
+    // line in the .cmp file
+    pub line: usize,
+    pub expected: String,
+    pub actual: String,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}:\n  expected: {}\n  actual:   {}",
+            self.line, self.expected, self.actual
+        )
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Report {
+    pub output: Vec<String>,
+    // empty when passed or without compare-to
+    pub mismatches: Vec<Mismatch>,
+}
+
+impl Report {
+    pub fn passed(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+// a%B3.1.3
+#[derive(Debug, PartialEq, Clone)]
+struct Column {
+    name: String,
+    format: char,
+    left: usize,
+    width: usize,
+    right: usize,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum Command {
+    Load(String),
+    OutputFile(String),
+    CompareTo(String),
+    OutputList(Vec<Column>),
+    Set(String, String),
+    Eval,
+    Tick,
+    Tock,
+    TickTock,
+    Output,
+    Echo(String),
+    Repeat(usize, Vec<(Command, usize)>),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum Token {
+    Word(String),
+    Symbol(char),
+}
+
+/// What a test script drives, e.g. a chip or the whole computer.
+/// Pins are LSB first.
+pub trait Target {
+    fn load(&mut self, path: &Path) -> Result<(), HdlError>;
+    fn get(&self, pin: &str) -> Result<Vec<bit>, HdlError>;
+    fn set(&mut self, pin: &str, bits: &[bit]) -> Result<(), HdlError>;
+    fn eval(&mut self) -> Result<(), HdlError>;
+    fn tick(&mut self) -> Result<(), HdlError>;
+    fn tock(&mut self) -> Result<(), HdlError>;
+}
+
+// load X.hdl uses the chip in base if there is no X.hdl
+#[derive(Default)]
+pub struct ChipTarget {
+    chip: Option<Box<dyn Chip>>,
+}
+
+impl ChipTarget {
+    // drive a chip which is already built
+    pub fn new(chip: Box<dyn Chip>) -> Self {
+        Self { chip: Some(chip) }
+    }
+
+    fn chip(&self) -> Result<&dyn Chip, HdlError> {
+        self.chip
+            .as_deref()
+            .ok_or_else(|| HdlError::new(0, "no chip is loaded"))
+    }
+
+    fn chip_mut(&mut self) -> Result<&mut (dyn Chip + 'static), HdlError> {
+        self.chip
+            .as_deref_mut()
+            .ok_or_else(|| HdlError::new(0, "no chip is loaded"))
+    }
+}
+
+impl Target for ChipTarget {
+    fn load(&mut self, path: &Path) -> Result<(), HdlError> {
+        let chip: Box<dyn Chip> = if path.exists() {
+            Box::new(load(&path.to_string_lossy())?)
+        } else {
+            let name = path
+                .file_stem()
+                .map_or(String::new(), |name| name.to_string_lossy().to_string());
+            chip::builtin(&name)
+                .ok_or_else(|| HdlError::new(0, &format!("unknown chip {}", name)))?
+        };
+        self.chip = Some(chip);
+        Ok(())
+    }
+
+    fn get(&self, pin: &str) -> Result<Vec<bit>, HdlError> {
+        let chip = self.chip()?;
+        chip.get(pin)
+            .ok_or_else(|| HdlError::new(0, &format!("{} has no pin {}", chip.name(), pin)))
+    }
+
+    fn set(&mut self, pin: &str, bits: &[bit]) -> Result<(), HdlError> {
+        self.chip_mut()?
+            .set_input(pin, bits)
+            .map_err(|e| HdlError::new(e.line, &e.message))
+    }
+
+    fn eval(&mut self) -> Result<(), HdlError> {
+        self.chip_mut()?.eval();
+        Ok(())
+    }
+
+    fn tick(&mut self) -> Result<(), HdlError> {
+        self.chip_mut()?.tick();
+        Ok(())
+    }
+
+    fn tock(&mut self) -> Result<(), HdlError> {
+        self.chip_mut()?.tock();
+        Ok(())
+    }
+}
+
+pub struct TestScript<T: Target> {
+    dir: PathBuf,
+    // where output-file writes, the dir of the .tst file unless given
+    output_dir: PathBuf,
+    target: T,
+    output_file: Option<PathBuf>,
+    compare_to: Option<PathBuf>,
+    columns: Vec<Column>,
+    output: Vec<String>,
+    // 3+ is between tick and tock of the 4th cycle
+    time: usize,
+    tocked: bool,
+}
+
+// run a .tst file for chips; files in it are relative to the .tst file
+pub fn run(filename: &str) -> Result<Report, HdlError> {
+    run_with(filename, ChipTarget::default())
+}
+
+pub fn run_with<T: Target>(filename: &str, target: T) -> Result<Report, HdlError> {
+    let dir = Path::new(filename)
+        .parent()
+        .unwrap_or_else(|| Path::new("."));
+    run_with_output(filename, target, dir)
+}
+
+// the output file is written to output_dir, e.g. a temp dir instead of the sources
+pub fn run_with_output<T: Target>(
+    filename: &str,
+    target: T,
+    output_dir: &Path,
+) -> Result<Report, HdlError> {
+    let source = fs::read_to_string(filename)
+        .map_err(|e| HdlError::new(0, &format!("Fail to open {}: {}", filename, e)))?;
+    let dir = Path::new(filename)
+        .parent()
+        .unwrap_or_else(|| Path::new("."));
+    TestScript::new(dir, target)
+        .output_to(output_dir)
+        .run(&source)
+}
+
+impl<T: Target> TestScript<T> {
+    pub fn new(dir: &Path, target: T) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+            output_dir: dir.to_path_buf(),
+            target,
+            output_file: None,
+            compare_to: None,
+            columns: Vec::new(),
+            output: Vec::new(),
+            time: 0,
+            tocked: true,
+        }
+    }
+
+    pub fn output_to(mut self, dir: &Path) -> Self {
+        self.output_dir = dir.to_path_buf();
+        self
+    }
+
+    pub fn run(&mut self, source: &str) -> Result<Report, HdlError> {
+        let tokens = Self::tokenize(source)?;
+        let mut index = 0;
+        let commands = Self::parse(&tokens, &mut index, false)?;
+        self.execute(&commands)?;
+
+        if let Some(path) = &self.output_file {
+            let mut text = self.output.join("\n");
+            text.push('\n');
+            fs::write(path, text).map_err(|e| {
+                HdlError::new(0, &format!("Fail to write {}: {}", path.display(), e))
+            })?;
+        }
+        let mismatches = match &self.compare_to {
+            Some(path) => {
+                let expected = fs::read_to_string(path).map_err(|e| {
+                    HdlError::new(0, &format!("Fail to open {}: {}", path.display(), e))
+                })?;
+                compare(&expected, &self.output)
+            }
+            None => Vec::new(),
+        };
+        Ok(Report {
+            output: self.output.clone(),
+            mismatches,
+        })
+    }
+
+    fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, HdlError> {
+        let chars: Vec<char> = source.chars().collect();
+        let mut tokens = Vec::new();
+        let mut line = 1;
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            if c == '\n' {
+                line += 1;
+                i += 1;
+            } else if c.is_whitespace() {
+                i += 1;
+            } else if c == '/' && chars.get(i + 1) == Some(&'/') {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+            } else if c == '/' && chars.get(i + 1) == Some(&'*') {
+                i += 2;
+                while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                    if chars[i] == '\n' {
+                        line += 1;
+                    }
+                    i += 1;
+                }
+                if i >= chars.len() {
+                    return Err(HdlError::new(line, "unterminated comment"));
+                }
+                i += 2;
+            } else if ",;{}".contains(c) {
+                tokens.push((Token::Symbol(c), line));
+                i += 1;
+            } else if c == '"' {
+                let start = i + 1;
+                i = start;
+                while i < chars.len() && chars[i] != '"' && chars[i] != '\n' {
+                    i += 1;
+                }
+                if chars.get(i) != Some(&'"') {
+                    return Err(HdlError::new(line, "unterminated string"));
+                }
+                tokens.push((Token::Word(chars[start..i].iter().collect()), line));
+                i += 1;
+            } else {
+                let start = i;
+                while i < chars.len() && !chars[i].is_whitespace() && !",;{}".contains(chars[i]) {
+                    i += 1;
+                }
+                tokens.push((Token::Word(chars[start..i].iter().collect()), line));
+            }
+        }
+        Ok(tokens)
+    }
+
+    // commands until the end, or '}' in a block
+    fn parse(
+        tokens: &[(Token, usize)],
+        index: &mut usize,
+        block: bool,
+    ) -> Result<Vec<(Command, usize)>, HdlError> {
+        let mut commands = Vec::new();
+        loop {
+            let (words, line) = match tokens.get(*index) {
+                None if block => {
+                    let line = tokens.last().map_or(1, |(_, line)| *line);
+                    return Err(HdlError::new(line, "expected '}'"));
+                }
+                None => return Ok(commands),
+                Some((Token::Symbol('}'), _)) if block => {
+                    *index += 1;
+                    return Ok(commands);
+                }
+                Some((_, line)) => {
+                    let mut words = Vec::new();
+                    while let Some((Token::Word(word), _)) = tokens.get(*index) {
+                        words.push(word.as_str());
+                        *index += 1;
+                    }
+                    (words, *line)
+                }
+            };
+            let error = |message: &str| HdlError::new(line, message);
+
+            let command = match words.as_slice() {
+                [] => return Err(error("expected a command")),
+                ["repeat", n] => {
+                    let n = n
+                        .parse()
+                        .map_err(|_| error(&format!("invalid repeat count {}", n)))?;
+                    if tokens.get(*index).map(|(token, _)| token) != Some(&Token::Symbol('{')) {
+                        return Err(error("expected '{'"));
+                    }
+                    *index += 1;
+                    commands.push((Command::Repeat(n, Self::parse(tokens, index, true)?), line));
+                    continue;
+                }
+                ["load", file] => Command::Load(file.to_string()),
+                ["output-file", file] => Command::OutputFile(file.to_string()),
+                ["compare-to", file] => Command::CompareTo(file.to_string()),
+                ["output-list", columns @ ..] => Command::OutputList(
+                    columns
+                        .iter()
+                        .map(|column| Self::column(column).map_err(|e| error(&e)))
+                        .collect::<Result<_, _>>()?,
+                ),
+                ["set", pin, value] => Command::Set(pin.to_string(), value.to_string()),
+                ["eval"] => Command::Eval,
+                ["tick"] => Command::Tick,
+                ["tock"] => Command::Tock,
+                ["ticktock"] => Command::TickTock,
+                ["echo", message] => Command::Echo(message.to_string()),
+                ["output"] => Command::Output,
+                words => return Err(error(&format!("unknown command {}", words.join(" ")))),
+            };
+            commands.push((command, line));
+
+            match tokens.get(*index) {
+                Some((Token::Symbol(','), _)) | Some((Token::Symbol(';'), _)) => *index += 1,
+                Some((Token::Symbol('}'), _)) if block => {}
+                _ => return Err(error("expected ',' or ';'")),
+            }
+        }
+    }
+
+    // name%B1.16.1, or name for %B1.<width>.1
+    fn column(column: &str) -> Result<Column, String> {
+        let invalid = || format!("invalid output format {}", column);
+        let (name, format) = match column.find('%') {
+            Some(i) => (&column[..i], &column[i + 1..]),
+            None => {
+                return Ok(Column {
+                    name: column.to_string(),
+                    format: 'B',
+                    left: 1,
+                    width: 0,
+                    right: 1,
+                })
+            }
+        };
+        let mut chars = format.chars();
+        let kind = chars
+            .next()
+            .filter(|c| "BDXS".contains(*c))
+            .ok_or_else(invalid)?;
+        let numbers = chars
+            .as_str()
+            .split('.')
+            .map(|n| n.parse().map_err(|_| invalid()))
+            .collect::<Result<Vec<usize>, _>>()?;
+        match numbers.as_slice() {
+            [left, width, right] => Ok(Column {
+                name: name.to_string(),
+                format: kind,
+                left: *left,
+                width: *width,
+                right: *right,
+            }),
+            _ => Err(invalid()),
+        }
+    }
+
+    fn execute(&mut self, commands: &[(Command, usize)]) -> Result<(), HdlError> {
+        for (command, line) in commands.iter() {
+            self.command(command)
+                .map_err(|e| HdlError::new(*line, &e.message))?;
+        }
+        Ok(())
+    }
+
+    fn command(&mut self, command: &Command) -> Result<(), HdlError> {
+        match command {
+            Command::Load(file) => {
+                self.target.load(&self.dir.join(file))?;
+                self.time = 0;
+                self.tocked = true;
+            }
+            Command::OutputFile(file) => self.output_file = Some(self.output_dir.join(file)),
+            Command::CompareTo(file) => self.compare_to = Some(self.dir.join(file)),
+            Command::OutputList(columns) => {
+                let mut columns = columns.clone();
+                for column in columns.iter_mut().filter(|column| column.width == 0) {
+                    column.width = self.width(&column.name)?;
+                }
+                self.output.push(header(&columns));
+                self.columns = columns;
+            }
+            Command::Set(pin, value) => {
+                let width = self.width(pin)?;
+                let bits = parse_value(value, width)?;
+                self.target.set(pin, &bits)?;
+            }
+            Command::Eval => self.target.eval()?,
+            Command::Tick => {
+                self.target.tick()?;
+                self.tocked = false;
+            }
+            Command::Tock => {
+                self.target.tock()?;
+                self.time += 1;
+                self.tocked = true;
+            }
+            Command::TickTock => {
+                self.target.tick()?;
+                self.target.tock()?;
+                self.time += 1;
+            }
+            Command::Output => {
+                let row = self
+                    .columns
+                    .iter()
+                    .map(|column| self.cell(column))
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.output.push(format!("|{}|", row.join("|")));
+            }
+            Command::Echo(message) => println!("{}", message),
+            Command::Repeat(n, commands) => {
+                for _ in 0..*n {
+                    self.execute(commands)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn width(&self, pin: &str) -> Result<usize, HdlError> {
+        if pin == "time" {
+            return Ok(4);
+        }
+        Ok(self.target.get(pin)?.len())
+    }
+
+    fn cell(&self, column: &Column) -> Result<String, HdlError> {
+        let value = if column.name == "time" {
+            format!("{}{}", self.time, if self.tocked { "" } else { "+" })
+        } else {
+            format_value(&self.target.get(&column.name)?, column.format)
+        };
+        let value = if column.format == 'S' {
+            format!("{:<width$}", value, width = column.width)
+        } else {
+            format!("{:>width$}", value, width = column.width)
+        };
+        Ok(format!(
+            "{}{}{}",
+            " ".repeat(column.left),
+            value,
+            " ".repeat(column.right)
+        ))
+    }
+}
+
+fn header(columns: &[Column]) -> String {
+    let names: Vec<String> = columns
+        .iter()
+        .map(|column| {
+            let space =
+                (column.left + column.width + column.right).saturating_sub(column.name.len());
+            format!(
+                "{}{}{}",
+                " ".repeat(space / 2),
+                column.name,
+                " ".repeat(space - space / 2)
+            )
+        })
+        .collect();
+    format!("|{}|", names.join("|"))
+}
+
+// LSB first bits
+fn to_u16(bits: &[bit]) -> u16 {
+    bits.iter()
+        .enumerate()
+        .fold(0, |sum, (i, b)| if *b == I { sum | (1 << i) } else { sum })
+}
+
+fn format_value(bits: &[bit], format: char) -> String {
+    let value = to_u16(bits);
+    match format {
+        'D' if bits.len() == 16 => (value as i16).to_string(),
+        'D' => value.to_string(),
+        'X' => format!("{:0width$X}", value, width = bits.len().div_ceil(4)),
+        // B and S
+        _ => bits
+            .iter()
+            .rev()
+            .map(|b| if *b == I { '1' } else { '0' })
+            .collect(),
+    }
+}
+
+// %B0101, %XFF, %D-1 or -1 -> LSB first bits
+fn parse_value(value: &str, width: usize) -> Result<Vec<bit>, HdlError> {
+    let invalid = || HdlError::new(0, &format!("invalid value {}", value));
+    let number = if let Some(binary) = value.strip_prefix("%B") {
+        i32::from_str_radix(binary, 2).map_err(|_| invalid())?
+    } else if let Some(hex) = value.strip_prefix("%X") {
+        i32::from_str_radix(hex, 16).map_err(|_| invalid())?
+    } else {
+        value
+            .strip_prefix("%D")
+            .unwrap_or(value)
+            .parse::<i32>()
+            .map_err(|_| invalid())?
+    };
+    Ok((0..width)
+        .map(|i| if number & (1 << i) != 0 { I } else { O })
+        .collect())
+}
+
+// compares cells with their padding trimmed
+fn compare(expected: &str, output: &[String]) -> Vec<Mismatch> {
+    let cells = |line: &str| -> Vec<String> {
+        line.trim()
+            .split('|')
+            .map(|cell| cell.trim().to_string())
+            .collect()
+    };
+    let expected: Vec<&str> = expected
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .collect();
+    let mut mismatches = Vec::new();
+    for i in 0..expected.len().max(output.len()) {
+        let expected = expected.get(i).copied().unwrap_or("");
+        let actual = output.get(i).map_or("", |line| line.as_str());
+        if cells(expected) != cells(actual) {
+            mismatches.push(Mismatch {
+                line: i + 1,
+                expected: expected.to_string(),
+                actual: actual.to_string(),
+            });
+        }
+    }
+    mismatches
+}
+
+// A dir of its own for a test, e.g. for the output files of scripts, removed afterwards.
+#[cfg(test)]
+pub(crate) fn in_temp<T>(name: &str, run: impl FnOnce(&Path) -> T) -> T {
+    let dir = std::env::temp_dir().join(format!("{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let result = run(&dir);
+    fs::remove_dir_all(&dir).unwrap();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // -> the report and the text of the output file
+    fn run_in_temp(filename: &str) -> (Report, String) {
+        let name = Path::new(filename).file_stem().unwrap().to_string_lossy();
+        in_temp(&format!("hdl-script-{}", name), |dir| {
+            let report = run_with_output(filename, ChipTarget::default(), dir).unwrap();
+            let output = fs::read_to_string(dir.join(format!("{}.out", name))).unwrap();
+            (report, output)
+        })
+    }
+
+    #[test]
+    fn for_script_and() {
+        let (report, output) = run_in_temp("src/hdl/tests/And.tst");
+        assert_eq!(report.output[0], "|   a   |   b   |  out  |");
+        assert_eq!(report.output[4], "|   1   |   1   |   1   |");
+        assert!(report.passed(), "{:?}", report.mismatches);
+        assert_eq!(output, report.output.join("\n") + "\n");
+    }
+
+    #[test]
+    fn for_script_builtin() {
+        // Add16 and Bit are Rust chips
+        assert!(run_in_temp("src/hdl/tests/Add16.tst").0.passed());
+        let (report, _) = run_in_temp("src/hdl/tests/Register.tst");
+        assert!(report.passed(), "{:?}", report.mismatches);
+        assert_eq!(report.output[1], "| 0+   |      0 |  1  |      0 |");
+    }
+
+    #[test]
+    fn for_script_mismatch() {
+        let (report, _) = run_in_temp("src/hdl/tests/Mismatch.tst");
+        assert_eq!(
+            report.mismatches,
+            vec![Mismatch {
+                line: 3,
+                expected: "|   0   |   1   |   1   |".to_string(),
+                actual: "|   0   |   1   |   0   |".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn for_script_values() {
+        assert_eq!(parse_value("%B101", 3).unwrap(), vec![I, O, I]);
+        assert_eq!(parse_value("-1", 2).unwrap(), vec![I, I]);
+        assert_eq!(parse_value("%X1", 4).unwrap(), vec![I, O, O, O]);
+        assert_eq!(format_value(&[I; 16], 'D'), "-1");
+        assert_eq!(format_value(&[O, I, O, O, O], 'X'), "02");
+        assert_eq!(
+            TestScript::new(Path::new("."), ChipTarget::default())
+                .run("load And.hdl,\nset a 1 2;")
+                .err(),
+            Some(HdlError::new(2, "unknown command set a 1 2"))
+        );
+    }
+}
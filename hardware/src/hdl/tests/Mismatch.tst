@@ -0,0 +1,8 @@
+// And.tst compared with the table of Or
+load And.hdl,
+output-file Mismatch.out,
+compare-to Mismatch.cmp,
+output-list a%B3.1.3 b%B3.1.3 out%B3.1.3;
+
+set a 0, set b 0, eval, output;
+set a 0, set b 1, eval, output;
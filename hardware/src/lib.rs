@@ -1,3 +1,10 @@
 pub mod base;
 pub mod computer;
+pub mod depth;
+pub mod display;
+pub mod error;
+pub mod hdl;
+pub mod profile;
+pub mod protocol;
 mod util;
+pub mod vcd;
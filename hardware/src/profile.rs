@@ -0,0 +1,283 @@
+#![allow(dead_code)]
+// Counts nand evaluations while profiling is on, when built with the profile feature.
+// Without it the calls of chips and instructions are still counted, but their nands are 0.
+// Chips open a scope, so each nand is counted for every chip on the stack (inclusive),
+// and GateMachine opens one per instruction.
+use crate::{
+    base::logic::{bit, Word},
+    depth,
+};
+use std::{
+    borrow::Cow,
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    fmt::{self, Formatter},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+// threads which are profiling or tracing; nand doesn't look at the thread locals while it's 0
+pub(crate) static PROFILING: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    static ENABLED: Cell<bool> = const { Cell::new(false) };
+    static NANDS: Cell<u64> = const { Cell::new(0) };
+    static PROFILE: RefCell<Profile> = RefCell::new(Profile::default());
+}
+
+#[derive(Debug, PartialEq, Copy, Clone, Default)]
+pub struct Stats {
+    pub calls: u64,
+    pub nands: u64,
+    // nands of the cheapest and the most expensive call
+    pub min: u64,
+    pub max: u64,
+}
+
+impl Stats {
+    fn add(&mut self, nands: u64) {
+        if self.calls == 0 || nands < self.min {
+            self.min = nands;
+        }
+        if nands > self.max {
+            self.max = nands;
+        }
+        self.calls += 1;
+        self.nands += nands;
+    }
+
+    // the number of nand gates when every call evaluates all of them, i.e. combinational chips
+    pub fn gates(&self) -> Option<u64> {
+        if self.calls > 0 && self.min == self.max {
+            Some(self.min)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Profile {
+    pub nands: u64,
+    pub chips: HashMap<String, Stats>,
+    // instruction -> nands of the cycles which executed it
+    pub instructions: HashMap<u16, Stats>,
+}
+
+impl Profile {
+    pub fn cycles(&self) -> u64 {
+        self.instructions.values().map(|stats| stats.calls).sum()
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "nand evaluations: {}", self.nands)?;
+
+        let mut chips: Vec<_> = self.chips.iter().collect();
+        chips.sort_by(|a, b| b.1.nands.cmp(&a.1.nands).then(a.0.cmp(b.0)));
+        writeln!(
+            f,
+            "{:<16} {:>10} {:>12} {:>14}",
+            "chip", "calls", "nands/call", "nands"
+        )?;
+        for (name, stats) in chips {
+            let per_call = match stats.gates() {
+                Some(gates) => gates.to_string(),
+                None => format!("{}..{}", stats.min, stats.max),
+            };
+            writeln!(
+                f,
+                "{:<16} {:>10} {:>12} {:>14}",
+                name, stats.calls, per_call, stats.nands
+            )?;
+        }
+
+        let cycles = self.cycles();
+        if cycles > 0 {
+            writeln!(
+                f,
+                "\ncycles: {}, nands/cycle: {}",
+                cycles,
+                self.instructions.values().map(|s| s.nands).sum::<u64>() / cycles
+            )?;
+            let mut instructions: Vec<_> = self.instructions.iter().collect();
+            instructions.sort_by_key(|(instruction, _)| **instruction);
+            writeln!(
+                f,
+                "{:<16} {:>10} {:>12} {:>14}",
+                "instruction", "cycles", "nands/cycle", "nands"
+            )?;
+            for (instruction, stats) in instructions {
+                writeln!(
+                    f,
+                    "{} {:>10} {:>12} {:>14}",
+                    Word::from(*instruction),
+                    stats.calls,
+                    stats.nands / stats.calls,
+                    stats.nands
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// reset the counts and start counting on this thread
+pub fn start() {
+    NANDS.with(|n| n.set(0));
+    PROFILE.with(|p| *p.borrow_mut() = Profile::default());
+    if !ENABLED.with(|e| e.replace(true)) {
+        PROFILING.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+pub fn stop() -> Profile {
+    if ENABLED.with(|e| e.replace(false)) {
+        PROFILING.fetch_sub(1, Ordering::Relaxed);
+    }
+    let mut profile = PROFILE.with(|p| p.replace(Profile::default()));
+    profile.nands = NANDS.with(|n| n.replace(0));
+    profile
+}
+
+pub fn is_enabled() -> bool {
+    PROFILING.load(Ordering::Relaxed) > 0 && ENABLED.with(|e| e.get())
+}
+
+// -> out, or what the depth analysis forces it to
+pub(crate) fn nand(a: bit, b: bit, out: bit) -> bit {
+    if PROFILING.load(Ordering::Relaxed) == 0 {
+        return out;
+    }
+    if ENABLED.with(|e| e.get()) {
+        NANDS.with(|n| n.set(n.get() + 1));
+    }
+    depth::nand(a, b, out)
+}
+
+enum Key {
+    Chip(Cow<'static, str>),
+    Instruction(u16),
+}
+
+// counts the nands until it's dropped
+pub struct Scope {
+    key: Option<Key>,
+    start: u64,
+    // the chip is on the stack of the depth analysis
+    traced: bool,
+}
+
+impl Scope {
+    fn new(key: Key) -> Self {
+        if PROFILING.load(Ordering::Relaxed) == 0 {
+            return Self {
+                key: None,
+                start: 0,
+                traced: false,
+            };
+        }
+        let traced = match &key {
+            Key::Chip(name) => depth::enter(name.clone()),
+            Key::Instruction(_) => false,
+        };
+        Self {
+            key: if is_enabled() { Some(key) } else { None },
+            start: NANDS.with(|n| n.get()),
+            traced,
+        }
+    }
+}
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        if self.traced {
+            depth::leave();
+        }
+        if let Some(key) = &self.key {
+            let nands = NANDS.with(|n| n.get()) - self.start;
+            PROFILE.with(|p| {
+                let mut profile = p.borrow_mut();
+                let stats = match key {
+                    Key::Chip(name) => {
+                        if !profile.chips.contains_key(name.as_ref()) {
+                            profile.chips.insert(name.to_string(), Stats::default());
+                        }
+                        profile.chips.get_mut(name.as_ref()).unwrap()
+                    }
+                    Key::Instruction(instruction) => {
+                        profile.instructions.entry(*instruction).or_default()
+                    }
+                };
+                stats.add(nands);
+            });
+        }
+    }
+}
+
+// e.g. chip("alu"), or chip(name.clone()) for chips in HDL
+pub fn chip<S: Into<Cow<'static, str>>>(name: S) -> Scope {
+    Scope::new(Key::Chip(name.into()))
+}
+
+pub fn instruction(instruction: u16) -> Scope {
+    Scope::new(Key::Instruction(instruction))
+}
+
+#[cfg(all(test, feature = "profile"))]
+mod tests {
+    use super::*;
+    use crate::base::{
+        alu::alu,
+        arithmetic::add16,
+        logic::{bit::O, nand, Word},
+    };
+
+    #[test]
+    fn for_profile() {
+        nand(O, O);
+        start();
+        nand(O, O);
+        {
+            let _scope = chip("test");
+            nand(O, O);
+            nand(O, O);
+        }
+        add16(Word::from(1u16), Word::from(2u16));
+        let profile = stop();
+        nand(O, O);
+
+        assert_eq!(profile.chips["test"].gates(), Some(2));
+        // a half adder (and 2, xor 6) and 15 full adders (2 half adders and or 3)
+        assert_eq!(profile.chips["half_adder"].gates(), Some(8));
+        assert_eq!(profile.chips["full_adder"].gates(), Some(19));
+        assert_eq!(profile.chips["add16"].gates(), Some(8 + 15 * 19));
+        assert_eq!(profile.nands, 3 + 293);
+        assert_eq!(stop().nands, 0);
+    }
+
+    #[test]
+    fn for_profile_alu() {
+        start();
+        let zero = Word::from(0u16);
+        alu(zero, zero, O, O, O, O, O, O);
+        alu(zero, Word::from(-1i16), O, O, O, O, O, O);
+        let profile = stop();
+        let stats = profile.chips["alu"];
+        assert_eq!(stats.calls, 2);
+        // every gate is evaluated whatever the inputs are
+        assert_eq!(stats.gates(), Some(stats.nands / 2));
+        assert!(profile.chips["add16"].nands < stats.nands);
+    }
+
+    #[test]
+    fn for_profile_hdl() {
+        // And of Nand and Not, where Not is Nand
+        let mut chip = crate::hdl::load("src/hdl/tests/And.hdl").unwrap();
+        start();
+        chip.eval();
+        let profile = stop();
+        assert_eq!(profile.chips["And"].gates(), Some(2));
+        assert_eq!(profile.chips["Not"].gates(), Some(1));
+    }
+}
@@ -0,0 +1,32 @@
+// Blackens the screen while a key is pressed, and clears it otherwise.
+
+(LOOP)
+    @color
+    M=0
+    @KBD
+    D=M
+    @PAINT
+    D;JEQ
+    @color
+    M=-1
+(PAINT)
+    @SCREEN
+    D=A
+    @pixel
+    M=D
+(NEXT)
+    @pixel
+    D=M
+    @KBD
+    D=D-A
+    @LOOP
+    D;JGE
+    @color
+    D=M
+    @pixel
+    A=M
+    M=D
+    @pixel
+    M=M+1
+    @NEXT
+    0;JMP
@@ -0,0 +1,29 @@
+// Max.asm of project 6 with PC, A and D
+
+load Max.asm,
+output-file Max.out,
+compare-to Max.cmp,
+output-list RAM[0]%D2.6.2 RAM[1]%D2.6.2 RAM[2]%D2.6.2 PC%D0.4.0 A%D1.6.1 D%D1.6.1;
+
+set RAM[0] 3,
+set RAM[1] 5;
+repeat 14 {
+  ticktock;
+}
+output;
+
+set PC 0,
+set RAM[0] 23456,
+set RAM[1] 12345;
+repeat 14 {
+  ticktock;
+}
+output;
+
+set PC 0,
+set RAM[0] -7,
+set RAM[1] -3;
+repeat 14 {
+  ticktock;
+}
+output;
@@ -0,0 +1,134 @@
+// The messages between a computer and a display over the channel: JSON objects tagged
+// by "type". The display says hello with the version it speaks, and the computer answers
+// with welcome and a keyframe, or with an error. Anything the computer can't read is
+// answered with an error too.
+//
+//   display   {"type":"hello","version":1}
+//   computer  {"type":"welcome","version":1}
+//   computer  {"type":"frame","keyframe":true,"runs":[[0,8192,0]]}
+//   display   {"type":"key","key":65,"down":true}
+//   display   {"type":"control","command":"load","path":"Pong.hack"}
+//   computer  {"type":"error","message":"..."}
+use std::fmt::{self, Formatter};
+
+use serde::{Deserialize, Serialize};
+
+pub const VERSION: u32 = 1;
+
+/// From the computer to the display.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ToDisplay {
+    // the version of the computer, after hello
+    Welcome { version: u32 },
+    // the words changed since the last frame, or the whole screen
+    Frame { keyframe: bool, runs: Vec<Run> },
+    Error { message: String },
+}
+
+/// [register_index, count, value]: count words of the screen from register_index are value.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Copy, Clone)]
+pub struct Run(pub u16, pub u16, pub u16);
+
+/// From the display to the computer.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FromDisplay {
+    Hello { version: u32 },
+    // a Hack key code, e.g. 128 for enter
+    Key { key: u16, down: bool },
+    Control(Control),
+}
+
+/// What the display asks the program runner to do, see `Computer::take_controls`.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum Control {
+    Run,
+    Pause,
+    // start the program again from PC 0
+    Reset,
+    Load { path: String },
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ProtocolError(pub String);
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl ToDisplay {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    // the answer to hello
+    pub fn welcome(version: u32) -> Self {
+        if version == VERSION {
+            ToDisplay::Welcome { version: VERSION }
+        } else {
+            ToDisplay::Error {
+                message: format!(
+                    "expected protocol version {} but it was {}",
+                    VERSION, version
+                ),
+            }
+        }
+    }
+}
+
+impl FromDisplay {
+    pub fn parse(text: &str) -> Result<Self, ProtocolError> {
+        serde_json::from_str(text).map_err(|e| ProtocolError(format!("{}: {}", e, text)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn for_protocol() {
+        assert_eq!(
+            FromDisplay::parse(r#"{"type":"hello","version":1}"#),
+            Ok(FromDisplay::Hello { version: 1 })
+        );
+        assert_eq!(
+            FromDisplay::parse(r#"{"type":"key","key":65,"down":true}"#),
+            Ok(FromDisplay::Key {
+                key: 65,
+                down: true
+            })
+        );
+        assert_eq!(
+            FromDisplay::parse(r#"{"type":"control","command":"load","path":"Pong.hack"}"#),
+            Ok(FromDisplay::Control(Control::Load {
+                path: "Pong.hack".to_string()
+            }))
+        );
+        assert_eq!(
+            FromDisplay::parse(r#"{"type":"control","command":"pause"}"#),
+            Ok(FromDisplay::Control(Control::Pause))
+        );
+        assert!(FromDisplay::parse(r#"{"type":"key","key":-1,"down":true}"#).is_err());
+        assert!(FromDisplay::parse(r#"{"key":65,"down":true}"#).is_err());
+        assert!(FromDisplay::parse("hello").is_err());
+
+        assert_eq!(
+            ToDisplay::Frame {
+                keyframe: false,
+                runs: vec![Run(32, 32, 0xffff)]
+            }
+            .to_json(),
+            r#"{"type":"frame","keyframe":false,"runs":[[32,32,65535]]}"#
+        );
+        assert_eq!(
+            ToDisplay::welcome(VERSION).to_json(),
+            r#"{"type":"welcome","version":1}"#
+        );
+        assert!(matches!(ToDisplay::welcome(2), ToDisplay::Error { .. }));
+    }
+}
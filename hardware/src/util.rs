@@ -2,6 +2,7 @@
 
 use crate::base::logic::bit;
 use crate::base::logic::bit::{I, O};
+use crate::error::HardwareError;
 
 pub fn read_stdin<T: std::str::FromStr>() -> T {
     let mut s = String::new();
@@ -9,24 +10,19 @@ pub fn read_stdin<T: std::str::FromStr>() -> T {
     s.trim().parse().ok().unwrap()
 }
 
-pub fn str_to_binary(str: &str) -> Vec<bit> {
-    if str == "" {
-        panic!(format!("Could't convert an empty string"))
+pub fn str_to_binary(str: &str) -> Result<Vec<bit>, HardwareError> {
+    if str.is_empty() {
+        return Err(HardwareError::new(
+            "expected binary digits but it was empty",
+        ));
     }
-
-    let char_bits: Vec<char> = str.chars().collect();
-    let mut bits = Vec::new();
-
-    for i in 0..char_bits.len() {
-        let bit = match char_bits[i].to_digit(10) {
-            Some(0) => O,
-            Some(1) => I,
-            _ => panic!("Unknown number. String should consist of characters neither 0 or 1"),
-        };
-        bits.push(bit);
-    }
-
-    return bits;
+    str.chars()
+        .map(|c| match c {
+            '0' => Ok(O),
+            '1' => Ok(I),
+            _ => Err(HardwareError::at(0, str, "expected only 0 and 1")),
+        })
+        .collect()
 }
 
 #[cfg(test)]
@@ -35,21 +31,22 @@ mod tests {
 
     #[test]
     fn for_str_to_binary() {
-        assert_eq!(str_to_binary("1"), [I]);
-        assert_eq!(str_to_binary("0"), [O]);
-        assert_eq!(str_to_binary("000"), [O, O, O]);
-        assert_eq!(str_to_binary("0101"), [O, I, O, I]);
+        assert_eq!(str_to_binary("1"), Ok(vec![I]));
+        assert_eq!(str_to_binary("0"), Ok(vec![O]));
+        assert_eq!(str_to_binary("000"), Ok(vec![O, O, O]));
+        assert_eq!(str_to_binary("0101"), Ok(vec![O, I, O, I]));
     }
 
     #[test]
-    #[should_panic]
     fn for_str_to_binary_2() {
-        str_to_binary("");
+        assert!(str_to_binary("").is_err());
     }
 
     #[test]
-    #[should_panic]
     fn for_str_to_binary_3() {
-        str_to_binary("a");
+        assert_eq!(
+            str_to_binary("01a"),
+            Err(HardwareError::at(0, "01a", "expected only 0 and 1"))
+        );
     }
 }
@@ -0,0 +1,222 @@
+use std::io::{self, BufWriter, Write};
+
+use crate::base::{
+    dff::{Clock, ClockState},
+    logic::bit::{self, I, O},
+};
+
+/// A Value Change Dump of named signals, sampled on every clock phase,
+/// e.g. for GTKWave.
+pub struct Vcd {
+    writer: BufWriter<Box<dyn Write>>,
+    widths: Vec<usize>,
+    // None until the first sample
+    values: Vec<Option<u16>>,
+    time: u64,
+    // the first error is kept for finish, so that sampling doesn't stop a machine
+    error: Option<io::Error>,
+}
+
+impl Vcd {
+    // writes the header which declares the signals of the module
+    pub fn new(
+        writer: Box<dyn Write>,
+        module: &str,
+        signals: &[(&str, usize)],
+    ) -> io::Result<Self> {
+        let mut writer = BufWriter::new(writer);
+        // a time unit is a clock phase
+        writeln!(writer, "$timescale 1ns $end")?;
+        writeln!(writer, "$scope module {} $end", module)?;
+        for (i, (name, width)) in signals.iter().enumerate() {
+            writeln!(writer, "$var wire {} {} {} $end", width, id(i), name)?;
+        }
+        writeln!(writer, "$upscope $end")?;
+        writeln!(writer, "$enddefinitions $end")?;
+        Ok(Self {
+            writer,
+            widths: signals.iter().map(|(_, width)| *width).collect(),
+            values: vec![None; signals.len()],
+            time: 0,
+            error: None,
+        })
+    }
+
+    // the values of all signals in the order of new, at the next phase
+    pub fn sample(&mut self, values: &[u16]) {
+        assert_eq!(
+            values.len(),
+            self.widths.len(),
+            "expected a value per signal"
+        );
+        if self.error.is_some() {
+            return;
+        }
+        let result = self.write(values);
+        if let Err(e) = result {
+            self.error = Some(e);
+        }
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+        self.writer.flush()
+    }
+
+    fn write(&mut self, values: &[u16]) -> io::Result<()> {
+        writeln!(self.writer, "#{}", self.time)?;
+        let first = self.time == 0;
+        if first {
+            writeln!(self.writer, "$dumpvars")?;
+        }
+        for (i, value) in values.iter().enumerate() {
+            let width = self.widths[i];
+            let value = if width < 16 {
+                value & ((1 << width) - 1)
+            } else {
+                *value
+            };
+            if self.values[i] == Some(value) {
+                continue;
+            }
+            self.values[i] = Some(value);
+            if width == 1 {
+                writeln!(self.writer, "{}{}", value, id(i))?;
+            } else {
+                writeln!(self.writer, "b{:b} {}", value, id(i))?;
+            }
+        }
+        if first {
+            writeln!(self.writer, "$end")?;
+        }
+        self.time += 1;
+        Ok(())
+    }
+}
+
+// the clock signal goes up on Tick and down on Tock
+pub fn clock(clock: &Clock) -> u16 {
+    match clock.state {
+        ClockState::Tick => 1,
+        ClockState::Tock => 0,
+    }
+}
+
+pub fn value(b: bit) -> u16 {
+    match b {
+        O => 0,
+        I => 1,
+    }
+}
+
+// !, ", #, ..., then two characters
+fn id(i: usize) -> String {
+    let base = (b'~' - b'!' + 1) as usize;
+    let mut id = String::new();
+    let mut i = i;
+    loop {
+        id.push((b'!' + (i % base) as u8) as char);
+        i /= base;
+        if i == 0 {
+            return id;
+        }
+        i -= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::base::{logic::Word, register::Register};
+    use std::{cell::RefCell, rc::Rc};
+
+    // a writer which is still readable after Vcd takes it
+    #[derive(Clone, Default)]
+    struct Shared(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn for_vcd() {
+        let out = Shared::default();
+        let mut vcd = Vcd::new(
+            Box::new(out.clone()),
+            "Register",
+            &[("clock", 1), ("in", 16), ("load", 1), ("out", 16)],
+        )
+        .unwrap();
+        let mut register = Register::new();
+        let mut clock = Clock::new();
+        for (input, load) in [(5u16, I), (7, O), (9, I)].iter() {
+            // the rising edge, then the falling edge
+            for _ in 0..2 {
+                clock.next();
+                match clock.state {
+                    ClockState::Tick => register.tick(Word::from(*input), *load),
+                    ClockState::Tock => register.tock(),
+                }
+                vcd.sample(&[
+                    super::clock(&clock),
+                    *input,
+                    value(*load),
+                    u16::from(register.output()),
+                ]);
+            }
+        }
+        vcd.finish().unwrap();
+
+        let dump = String::from_utf8(out.0.borrow().clone()).unwrap();
+        assert_eq!(
+            dump,
+            "$timescale 1ns $end
+$scope module Register $end
+$var wire 1 ! clock $end
+$var wire 16 \" in $end
+$var wire 1 # load $end
+$var wire 16 $ out $end
+$upscope $end
+$enddefinitions $end
+#0
+$dumpvars
+1!
+b101 \"
+1#
+b0 $
+$end
+#1
+0!
+b101 $
+#2
+1!
+b111 \"
+0#
+#3
+0!
+#4
+1!
+b1001 \"
+1#
+#5
+0!
+b1001 $
+"
+        );
+    }
+
+    #[test]
+    fn for_vcd_id() {
+        assert_eq!(id(0), "!");
+        assert_eq!(id(93), "~");
+        assert_eq!(id(94), "!!");
+        assert_eq!(id(95), "\"!");
+    }
+}
@@ -0,0 +1,456 @@
+#![allow(dead_code)]
+// A terminal debugger for Hack programs.
+// cargo run -p integrate --bin debugger -- <program.hack|.txt|.asm|.vm> [--gate]
+extern crate assembler;
+extern crate hardware;
+extern crate vm_translator;
+use assembler::{disassembler::disassemble, parser::Parser};
+use hardware::{
+    base::{
+        image,
+        logic::{bit::I, Word},
+        rom,
+    },
+    computer::{Backend, Computer, RunOutcome},
+    hdl::{from_word, script::Target},
+};
+use vm_translator::vm_translator::VmTranslator;
+
+use std::{
+    env, fs,
+    io::{self, BufRead, Write},
+    path::{Path, PathBuf},
+    process,
+};
+
+const HELP: &str = "\
+step [n]                  execute n instructions (1)
+next                      step over a jump, e.g. to a subroutine which returns to the next instruction
+continue                  run until a breakpoint, a watch, or the end of the program
+break <addr|label>        stop before executing the instruction
+delete <addr|label>       remove a breakpoint
+watch RAM[n]              stop when RAM[n] changes
+print A|D|PC|RAM[n]|RAM[a..b]
+set A|D|PC|RAM[n]=v       v is decimal, 0x.. or 0b..
+disassemble [a..b]        the program around PC, or the instructions from a to b
+quit
+an empty line repeats the last command";
+
+struct Debugger {
+    computer: Computer,
+    rom: Vec<u16>,
+    // (label, ROM address) of .asm and .vm programs
+    labels: Vec<(String, u16)>,
+    breakpoints: Vec<u16>,
+    // RAM address and the value when it was last seen
+    watches: Vec<(u16, Word)>,
+}
+
+impl Debugger {
+    fn new(filename: &str, backend: Backend) -> Result<Self, String> {
+        let path = Path::new(filename);
+        if !path.exists() {
+            return Err(format!("Fail to open {}", filename));
+        }
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let temp = |extension: &str| -> PathBuf {
+            env::temp_dir().join(format!("{}-{}.{}", stem, process::id(), extension))
+        };
+        let write = |path: &Path, contents: String| {
+            fs::write(path, contents)
+                .map_err(|e| format!("Fail to write {}: {}", path.display(), e))
+        };
+
+        let (rom, labels) = match path.extension().and_then(|e| e.to_str()) {
+            Some("asm") | Some("vm") => {
+                let asm = if filename.ends_with(".vm") {
+                    let asm = temp("asm");
+                    write(&asm, VmTranslator::new().translate(filename))?;
+                    asm
+                } else {
+                    path.to_path_buf()
+                };
+                let parsed = Parser::new().run(&asm.to_string_lossy());
+                if asm != path {
+                    let _ = fs::remove_file(&asm);
+                }
+                let rom = image::parse_hack(&parsed.to_string()).map_err(|e| e.to_string())?;
+                (rom, parsed.labels())
+            }
+            _ => (image::read(path).map_err(|e| e.to_string())?, Vec::new()),
+        };
+
+        let mut computer = Computer::with_backend(None, false, backend);
+        computer.load_words(&rom).map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            computer,
+            rom,
+            labels,
+            breakpoints: Vec::new(),
+            watches: Vec::new(),
+        })
+    }
+
+    fn pc(&self) -> u16 {
+        self.computer
+            .pc()
+            .iter()
+            .fold(0, |sum, b| (sum << 1) | if *b == I { 1 } else { 0 })
+    }
+
+    fn ram(&self, address: u16) -> Word {
+        self.computer.memory(address)
+    }
+
+    // 12, or a label
+    fn address(&self, arg: &str) -> Result<u16, String> {
+        if let Ok(address) = arg.parse::<u16>() {
+            return Ok(address);
+        }
+        self.labels
+            .iter()
+            .find(|(label, _)| label == arg)
+            .map(|(_, address)| *address)
+            .ok_or_else(|| format!("unknown address or label: {}", arg))
+    }
+
+    // 12 (LOOP+2): D=M
+    fn location(&self, address: u16) -> String {
+        let label = match self.labels.iter().rev().find(|(_, a)| *a <= address) {
+            Some((label, a)) if *a == address => format!(" ({})", label),
+            Some((label, a)) => format!(" ({}+{})", label, address - a),
+            None => String::new(),
+        };
+        let instruction = match self.rom.get(address as usize) {
+            Some(instruction) => disassemble(*instruction),
+            None => "end of program".to_string(),
+        };
+        format!("{}{}: {}", address, label, instruction)
+    }
+
+    fn execute(&mut self, line: &str) -> Result<String, String> {
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or("");
+        let arg = words.collect::<Vec<&str>>().join("");
+        match command {
+            "step" | "s" => {
+                let steps = if arg.is_empty() {
+                    1
+                } else {
+                    arg.parse::<usize>()
+                        .map_err(|_| format!("step takes a number: {}", arg))?
+                };
+                Ok(self.resume(Some(steps), None))
+            }
+            "next" | "n" => {
+                let pc = self.pc();
+                let jump = self
+                    .rom
+                    .get(pc as usize)
+                    .is_some_and(|instruction| instruction & 0x8007 > 0x8000);
+                // a jump at the last address has no next instruction to stop at
+                match pc
+                    .checked_add(1)
+                    .filter(|next| (*next as usize) < rom::SIZE)
+                {
+                    Some(next) if jump => Ok(self.resume(None, Some(next))),
+                    _ => Ok(self.resume(Some(1), None)),
+                }
+            }
+            "continue" | "c" => Ok(self.resume(None, None)),
+            "break" | "b" => {
+                let address = self.address(&arg)?;
+                if !self.breakpoints.contains(&address) {
+                    self.breakpoints.push(address);
+                }
+                Ok(format!("breakpoint at {}", self.location(address)))
+            }
+            "delete" | "d" => {
+                let address = self.address(&arg)?;
+                self.breakpoints.retain(|b| *b != address);
+                Ok(String::new())
+            }
+            "watch" | "w" => {
+                let address = ram_address(&arg)?;
+                let value = self.ram(address);
+                if !self.watches.iter().any(|(a, _)| *a == address) {
+                    self.watches.push((address, value));
+                }
+                Ok(format!("watching RAM[{}] = {}", address, show(value)))
+            }
+            "print" | "p" => self.print(&arg),
+            "set" => {
+                let mut assignment = arg.splitn(2, '=');
+                let pin = assignment.next().unwrap_or("");
+                let value = assignment
+                    .next()
+                    .ok_or_else(|| "set takes A|D|PC|RAM[n]=v".to_string())?
+                    .parse::<Word>()
+                    .map_err(|e| e.0)?;
+                Target::set(&mut self.computer, pin, &from_word(value)).map_err(|e| e.message)?;
+                for watch in self.watches.iter_mut() {
+                    watch.1 = self.computer.memory(watch.0);
+                }
+                self.print(pin)
+            }
+            "disassemble" | "disas" => {
+                let (from, to) = if arg.is_empty() {
+                    let pc = self.pc();
+                    (pc.saturating_sub(3), pc + 7)
+                } else {
+                    range(&arg)?
+                };
+                let to = to.min(self.rom.len() as u16);
+                let mut lines = Vec::new();
+                for address in from..to {
+                    for (label, _) in self.labels.iter().filter(|(_, a)| *a == address) {
+                        lines.push(format!("({})", label));
+                    }
+                    lines.push(format!(
+                        "{}{} {:>5}  {}",
+                        if address == self.pc() { ">" } else { " " },
+                        if self.breakpoints.contains(&address) {
+                            "*"
+                        } else {
+                            " "
+                        },
+                        address,
+                        disassemble(self.rom[address as usize])
+                    ));
+                }
+                Ok(lines.join("\n"))
+            }
+            "help" | "h" => Ok(HELP.to_string()),
+            _ => Err(format!("unknown command: {}, see help", command)),
+        }
+    }
+
+    fn print(&self, arg: &str) -> Result<String, String> {
+        match arg {
+            "" => Ok(format!(
+                "A: {}\nD: {}\nPC: {}",
+                show(self.computer.a()),
+                show(self.computer.d()),
+                self.location(self.pc())
+            )),
+            "A" => Ok(format!("A: {}", show(self.computer.a()))),
+            "D" => Ok(format!("D: {}", show(self.computer.d()))),
+            "PC" => Ok(format!("PC: {}", self.location(self.pc()))),
+            _ if arg.contains("..") => {
+                let (from, to) = range(
+                    arg.strip_prefix("RAM[")
+                        .and_then(|arg| arg.strip_suffix(']'))
+                        .unwrap_or(arg),
+                )?;
+                // the end is excluded, and the keyboard is the last address
+                if to > KEYBOARD + 1 {
+                    return Err(format!("RAM ends at {}: {}", KEYBOARD, arg));
+                }
+                Ok((from..to)
+                    .map(|address| format!("RAM[{}]: {}", address, show(self.ram(address))))
+                    .collect::<Vec<String>>()
+                    .join("\n"))
+            }
+            _ => {
+                let address = ram_address(arg)?;
+                Ok(format!("RAM[{}]: {}", address, show(self.ram(address))))
+            }
+        }
+    }
+
+    // Executes instructions until one of them stops it, which is reported.
+    // It always executes one, so that continue moves on from a breakpoint.
+    fn resume(&mut self, mut steps: Option<usize>, until: Option<u16>) -> String {
+        loop {
+            if self.pc() as usize >= self.rom.len() {
+                return "end of program".to_string();
+            }
+            let outcome = self.computer.step();
+            let pc = self.pc();
+            match outcome {
+                Some(RunOutcome::EndOfProgram) => return "end of program".to_string(),
+                Some(RunOutcome::Halted) => return format!("halted at {}", self.location(pc)),
+                _ => (),
+            }
+
+            let mut changes = Vec::new();
+            for i in 0..self.watches.len() {
+                let (address, value) = self.watches[i];
+                let current = self.ram(address);
+                if current != value {
+                    changes.push(format!(
+                        "RAM[{}]: {} -> {}",
+                        address,
+                        show(value),
+                        show(current)
+                    ));
+                    self.watches[i].1 = current;
+                }
+            }
+            if !changes.is_empty() {
+                changes.push(self.location(pc));
+                return changes.join("\n");
+            }
+            if self.breakpoints.contains(&pc) {
+                return format!("breakpoint at {}", self.location(pc));
+            }
+            if until == Some(pc) {
+                return self.location(pc);
+            }
+            if let Some(steps) = steps.as_mut() {
+                *steps -= 1;
+                if *steps == 0 {
+                    return self.location(pc);
+                }
+            }
+        }
+    }
+}
+
+// -1 (0xffff)
+fn show(word: Word) -> String {
+    format!("{} (0x{:04x})", word.signed(), word)
+}
+
+// the last address of the memory, above the screen
+const KEYBOARD: u16 = 24576;
+
+// RAM[n] -> n
+fn ram_address(arg: &str) -> Result<u16, String> {
+    arg.strip_prefix("RAM[")
+        .and_then(|arg| arg.strip_suffix(']'))
+        .and_then(|n| n.parse::<u16>().ok())
+        .filter(|n| *n <= KEYBOARD)
+        .ok_or_else(|| format!("expected RAM[n]: {}", arg))
+}
+
+// a..b -> (a, b)
+fn range(arg: &str) -> Result<(u16, u16), String> {
+    let mut bounds = arg.splitn(2, "..").map(|n| n.parse::<u16>());
+    match (bounds.next(), bounds.next()) {
+        (Some(Ok(from)), Some(Ok(to))) if from <= to => Ok((from, to)),
+        _ => Err(format!("expected a..b: {}", arg)),
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let backend = if args.iter().any(|arg| arg == "--gate") {
+        Backend::Gate
+    } else {
+        Backend::Fast
+    };
+    let filename = match args.iter().find(|arg| !arg.starts_with("--")) {
+        Some(filename) => filename,
+        None => {
+            eprintln!("usage: debugger <program.hack|.txt|.asm|.vm> [--gate]");
+            process::exit(1);
+        }
+    };
+    let mut debugger = Debugger::new(filename, backend).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+    println!("{}", debugger.location(0));
+
+    let stdin = io::stdin();
+    let mut last = String::new();
+    loop {
+        print!("(hack) ");
+        io::stdout().flush().unwrap();
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).unwrap_or(0) == 0 {
+            break;
+        }
+        let line = match line.trim() {
+            "" => last.clone(),
+            line => line.to_string(),
+        };
+        if line == "quit" || line == "q" {
+            break;
+        }
+        match debugger.execute(&line) {
+            Ok(output) if output.is_empty() => (),
+            Ok(output) => println!("{}", output),
+            Err(message) => println!("{}", message),
+        }
+        last = line;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn for_debugger() {
+        let mut debugger = Debugger::new("src/programs/Eq.vm", Backend::Fast).unwrap();
+        assert_eq!(debugger.location(0), "0: @256");
+        assert_eq!(
+            debugger.execute("break TRUE_LB_0").unwrap(),
+            format!(
+                "breakpoint at {}",
+                debugger.location(debugger.address("TRUE_LB_0").unwrap())
+            )
+        );
+        assert_eq!(debugger.execute("step 4").unwrap(), "4: @7");
+        assert_eq!(
+            debugger.execute("print RAM[0]").unwrap(),
+            "RAM[0]: 256 (0x0100)"
+        );
+
+        // 7 == 7
+        let stop = debugger.execute("continue").unwrap();
+        assert!(stop.starts_with("breakpoint at"), "{}", stop);
+        assert!(stop.contains("(TRUE_LB_0): D=-1"), "{}", stop);
+
+        assert_eq!(
+            debugger.execute("watch RAM[256]").unwrap(),
+            "watching RAM[256] = 7 (0x0007)"
+        );
+        let stop = debugger.execute("c").unwrap();
+        assert!(
+            stop.starts_with("RAM[256]: 7 (0x0007) -> -1 (0xffff)"),
+            "{}",
+            stop
+        );
+    }
+
+    #[test]
+    fn for_debugger_commands() {
+        let mut debugger = Debugger::new("src/programs/Add.txt", Backend::Fast).unwrap();
+        assert_eq!(debugger.execute("set D=-2").unwrap(), "D: -2 (0xfffe)");
+        assert_eq!(
+            debugger.execute("set RAM[3]=0x10").unwrap(),
+            "RAM[3]: 16 (0x0010)"
+        );
+        assert_eq!(
+            debugger.execute("print RAM[2..4]").unwrap(),
+            "RAM[2]: 0 (0x0000)\nRAM[3]: 16 (0x0010)"
+        );
+        assert_eq!(
+            debugger.execute("print RAM[24576..24577]").unwrap(),
+            "RAM[24576]: 0 (0x0000)"
+        );
+        assert_eq!(
+            debugger.execute("print RAM[0..32769]").err(),
+            Some("RAM ends at 24576: RAM[0..32769]".to_string())
+        );
+        assert_eq!(debugger.execute("set PC=2").unwrap(), "PC: 2: @0");
+        assert_eq!(debugger.execute("break 4").unwrap(), "breakpoint at 4: @7");
+        assert_eq!(
+            debugger.execute("disassemble 1..5").unwrap(),
+            "       1  D=A\n>      2  @0\n       3  M=D\n *     4  @7"
+        );
+        assert_eq!(
+            debugger.execute("step x").err(),
+            Some("step takes a number: x".to_string())
+        );
+        assert_eq!(
+            debugger.execute("print RAM[x]").err(),
+            Some("expected RAM[n]: RAM[x]".to_string())
+        );
+        assert!(debugger.execute("jump").is_err());
+    }
+}
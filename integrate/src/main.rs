@@ -3,7 +3,10 @@ extern crate assembler;
 extern crate hardware;
 extern crate vm_translator;
 use assembler::parser::Parser;
-use hardware::computer::Computer;
+use hardware::{
+    computer::{Backend, Computer, RunOutcome},
+    protocol::Control,
+};
 use vm_translator::vm_translator::VmTranslator;
 
 use std::fs::File;
@@ -13,6 +16,7 @@ use std::{
     net::{TcpListener, TcpStream},
     sync::mpsc,
     thread,
+    time::Duration,
 };
 use tungstenite::{server::accept, Message, WebSocket};
 
@@ -57,7 +61,7 @@ fn main() {
 
     println!("------ start_computer ------");
     let mut computer = Computer::new(None, false);
-    computer.run(&scanner.ml_path, false);
+    computer.run(&scanner.ml_path, false).expect("Run program");
 
     println!("{}", computer.get_memory_info(0, 8));
     println!("{}", computer.get_memory_info(256, 260));
@@ -82,18 +86,52 @@ fn start_computer(mut socket: WebSocket<TcpStream>, filename: &str) {
     let (to_computer, from_external) = mpsc::channel::<String>();
     let (to_external, from_computer) = mpsc::channel::<String>();
     // issue: CPU usage hits 100%
+    // ends when the display or the computer has gone
     thread::spawn(move || loop {
         if let Ok(msg) = socket.read_message() {
-            to_computer.send(msg.to_string()).unwrap();
+            if to_computer.send(msg.to_string()).is_err() {
+                break;
+            }
         }
         if let Ok(msg) = from_computer.try_recv() {
-            socket.write_message(Message::from(msg)).unwrap();
+            if socket.write_message(Message::from(msg)).is_err() {
+                break;
+            }
         }
     });
 
-    let mut computer = Computer::new(Some((to_external, from_external)), false);
+    let mut computer =
+        Computer::with_backend(Some((to_external, from_external)), false, Backend::Fast);
 
-    computer.run(filename, false);
+    // the display can pause, reset and load another program between batches
+    if let Err(e) = computer.load(filename) {
+        eprintln!("{}", e);
+        return;
+    }
+    let mut running = true;
+    loop {
+        for control in computer.take_controls() {
+            match control {
+                Control::Run => running = true,
+                Control::Pause => running = false,
+                Control::Reset => computer.set_pc(0),
+                // a bad program doesn't stop the one running
+                Control::Load { path } => {
+                    if let Err(e) = computer.load(&path) {
+                        eprintln!("{}", e);
+                    }
+                }
+            }
+        }
+        if !running {
+            computer.poll_display();
+            thread::sleep(Duration::from_millis(10));
+            continue;
+        }
+        if computer.run_for(10_000) != RunOutcome::CycleLimit {
+            break;
+        }
+    }
 
     println!("{}", computer.get_memory_info(0, 8));
     println!("{}", computer.get_memory_info(256, 260));
@@ -112,9 +150,9 @@ mod tests {
         let scanner = VmScanner::new("src/programs/Add");
         scanner.run();
         let mut computer = Computer::new(None, false);
-        computer.run(&scanner.ml_path, false);
+        computer.run(&scanner.ml_path, false).unwrap();
         assert_eq!(
-            computer.memory_out("000000100000000"),
+            computer.memory_out("000000100000000").unwrap(),
             Word::from("0000000000001111")
         );
     }
@@ -124,9 +162,9 @@ mod tests {
         let scanner = VmScanner::new("src/programs/Sub");
         scanner.run();
         let mut computer = Computer::new(None, false);
-        computer.run(&scanner.ml_path, false);
+        computer.run(&scanner.ml_path, false).unwrap();
         assert_eq!(
-            computer.memory_out("000000100000000"),
+            computer.memory_out("000000100000000").unwrap(),
             Word::from("1111111111111110")
         );
     }
@@ -136,9 +174,9 @@ mod tests {
         let scanner = VmScanner::new("src/programs/Eq");
         scanner.run();
         let mut computer = Computer::new(None, false);
-        computer.run(&scanner.ml_path, false);
+        computer.run(&scanner.ml_path, false).unwrap();
         assert_eq!(
-            computer.memory_out("000000100000000"),
+            computer.memory_out("000000100000000").unwrap(),
             Word::from("1111111111111111")
         );
     }
@@ -148,9 +186,9 @@ mod tests {
         let scanner = VmScanner::new("src/programs/Lt");
         scanner.run();
         let mut computer = Computer::new(None, false);
-        computer.run(&scanner.ml_path, false);
+        computer.run(&scanner.ml_path, false).unwrap();
         assert_eq!(
-            computer.memory_out("000000100000000"),
+            computer.memory_out("000000100000000").unwrap(),
             Word::from("1111111111111111")
         );
     }
@@ -160,9 +198,9 @@ mod tests {
         let scanner = VmScanner::new("src/programs/Gt");
         scanner.run();
         let mut computer = Computer::new(None, false);
-        computer.run(&scanner.ml_path, false);
+        computer.run(&scanner.ml_path, false).unwrap();
         assert_eq!(
-            computer.memory_out("000000100000000"),
+            computer.memory_out("000000100000000").unwrap(),
             Word::from("0000000000000000")
         );
     }